@@ -1,11 +1,15 @@
 use std::cmp;
 use std::fmt::{self, Display, Write};
 
-use crate::chess::chess_coordinate::notation_to_idx;
+use bitflags::bitflags;
+
+use crate::chess::chess_color::ChessColor;
+use crate::chess::chess_coordinate::{idx_to_notation, notation_to_idx};
 use crate::chess::chess_move::{ChessMove, MoveFlags};
 use crate::chess::chess_piece::ChessPiece;
+use crate::chess::chess_zobrist::ZOBRIST_KEYS;
 use crate::chess::InvalidFENError;
-use crate::chess::{CHESS_BOARD_HEIGHT, CHESS_BOARD_SIZE, CHESS_BOARD_WIDTH};
+use crate::chess::{CHESS_BOARD_HEIGHT, CHESS_BOARD_SIZE, CHESS_BOARD_WIDTH, CHESS_COLORS};
 
 pub const DIRECTION_OFFSETS: [i32; 8] = [
     -(CHESS_BOARD_WIDTH as i32),     //N
@@ -40,15 +44,46 @@ pub const KNIGHT_OFFSETS: [i32; 8] = [
 //     -(CHESS_BOARD_WIDTH as i32 * 2 + 1),
 // ];
 
-#[derive(Debug)]
+bitflags! {
+    pub struct CastlingRights:u8 {
+        const SHORT = 1;
+        const LONG = 2;
+    }
+}
+
+/// A game end the rules impose on the position alone. Repetitions depend on the game's history
+/// and are left to the caller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameOutcome {
+    Checkmate,
+    Stalemate,
+    FiftyMoveRule,
+    InsufficientMaterial,
+}
+
+//Everything make_move_unchecked can't recover from the move itself
+#[derive(Debug, Clone, Copy)]
+struct BoardState {
+    captured_piece: ChessPiece,
+    castling_rights: [CastlingRights; CHESS_COLORS],
+    en_passant_target: Option<u16>,
+    halfmove_clock: u32,
+    zobrist_key: u64,
+    pawn_key: u64,
+}
+
+#[derive(Debug, Clone)]
 pub struct ChessBoard {
     pub layout: [ChessPiece; CHESS_BOARD_SIZE],
-    pub color_to_move: ChessPiece,
-    pub can_catle_long: Vec<ChessPiece>,
-    pub can_catle_short: Vec<ChessPiece>,
+    pub color_to_move: ChessColor,
+    pub castling_rights: [CastlingRights; CHESS_COLORS],
     pub en_passant_target: Option<u16>,
     pub halfmove_clock: u32,
     pub fullmove_number: u32,
+    pub zobrist_key: u64,
+    /// Zobrist key of the pawns alone, for caching pawn structure evaluation.
+    pub pawn_key: u64,
+    history: Vec<BoardState>,
     squares_to_edge: [[usize; 8]; CHESS_BOARD_SIZE],
     knight_jumps: Vec<Vec<usize>>,
 }
@@ -57,16 +92,20 @@ impl ChessBoard {
     pub fn new() -> Self {
         let mut instance = Self {
             layout: [ChessPiece::empty(); CHESS_BOARD_SIZE],
-            color_to_move: ChessPiece::WHITE,
-            can_catle_long: vec![ChessPiece::WHITE, ChessPiece::BLACK],
-            can_catle_short: vec![ChessPiece::WHITE, ChessPiece::BLACK],
+            color_to_move: ChessColor::WHITE,
+            castling_rights: [CastlingRights::all(); CHESS_COLORS],
             en_passant_target: None,
             halfmove_clock: 0,
             fullmove_number: 1,
+            zobrist_key: 0,
+            pawn_key: 0,
+            history: vec![],
             squares_to_edge: [[0; 8]; CHESS_BOARD_SIZE],
             knight_jumps: vec![vec![]; CHESS_BOARD_SIZE],
         };
         instance.precompute_board_data();
+        instance.zobrist_key = instance.compute_zobrist_key();
+        instance.pawn_key = instance.compute_pawn_key();
         instance
     }
 
@@ -87,53 +126,53 @@ impl ChessBoard {
         let halfmove_part = parts[4];
         let fullmove_part = parts[5];
 
-        let color_to_move: ChessPiece;
         let halfmove_clock: u32;
         let fullmove_number: u32;
-        let mut can_catle_short = vec![];
-        let mut can_catle_long = vec![];
+        let mut castling_rights = [CastlingRights::empty(); CHESS_COLORS];
 
         for piece_code in placement_part.chars() {
             let code_string = piece_code.to_string();
             let code = code_string.as_str();
             if let Ok(number) = code.parse::<usize>() {
                 x += number;
-                if number > CHESS_BOARD_SIZE {
+                if x > CHESS_BOARD_WIDTH {
                     return Err(InvalidFENError);
                 }
             } else if code == "/" {
                 x = 0;
                 y += 1;
-            } else {
-                if let Ok(piece) = ChessPiece::new_from_notation(code) {
-                    layout[x + y * 8] = piece;
-                    x += 1;
-                } else {
+            } else if let Ok(piece) = ChessPiece::new_from_notation(code) {
+                //A rank with more than eight squares or a ninth rank
+                if x >= CHESS_BOARD_WIDTH || y >= CHESS_BOARD_HEIGHT {
                     return Err(InvalidFENError);
                 }
+                layout[x + y * 8] = piece;
+                x += 1;
+            } else {
+                return Err(InvalidFENError);
             }
         }
 
-        match active_color_part {
-            "w" => color_to_move = ChessPiece::WHITE,
-            "b" => color_to_move = ChessPiece::BLACK,
-            _ => return Err(InvalidFENError),
-        }
+        let color_to_move =
+            ChessColor::new_from_notation(active_color_part).map_err(|_| InvalidFENError)?;
 
         for chr in castling_part.chars() {
             let code = chr.to_string();
             if let Ok(piece) = ChessPiece::new_from_notation(&code) {
-                match piece & ChessPiece::PIECE_BITMASK {
-                    ChessPiece::KING => can_catle_short.push(piece & ChessPiece::COLOR_BITMASK),
-                    ChessPiece::QUEEN => can_catle_long.push(piece & ChessPiece::COLOR_BITMASK),
-                    _ => (),
+                if let Some(color) = piece.get_color() {
+                    match piece & ChessPiece::PIECE_BITMASK {
+                        ChessPiece::KING => {
+                            castling_rights[color.get_idx()] |= CastlingRights::SHORT
+                        }
+                        ChessPiece::QUEEN => {
+                            castling_rights[color.get_idx()] |= CastlingRights::LONG
+                        }
+                        _ => (),
+                    }
                 }
             }
         }
-        let en_passant_target: Option<u16> = match notation_to_idx(en_passant_part) {
-            Ok(idx) => Some(idx),
-            Err(_) => None,
-        };
+        let en_passant_target: Option<u16> = notation_to_idx(en_passant_part).ok();
 
         if let Ok(number) = halfmove_part.parse::<u32>() {
             halfmove_clock = number;
@@ -148,17 +187,78 @@ impl ChessBoard {
         let mut instance = Self {
             layout,
             color_to_move,
-            can_catle_long,
-            can_catle_short,
+            castling_rights,
             en_passant_target,
             halfmove_clock,
             fullmove_number,
+            zobrist_key: 0,
+            pawn_key: 0,
+            history: vec![],
             squares_to_edge: [[0; 8]; CHESS_BOARD_SIZE],
             knight_jumps: vec![vec![]; CHESS_BOARD_SIZE],
         };
         instance.precompute_board_data();
+        instance.zobrist_key = instance.compute_zobrist_key();
+        instance.pawn_key = instance.compute_pawn_key();
         Ok(instance)
     }
+    /// The position in Forsyth-Edwards notation, the counterpart of `new_from_fen`.
+    pub fn to_fen(&self) -> String {
+        let mut placement = String::new();
+        for (y, row) in self.layout.chunks(CHESS_BOARD_WIDTH).enumerate() {
+            if y > 0 {
+                placement.push('/');
+            }
+            let mut empty = 0;
+            for piece in row {
+                if piece.is_empty() {
+                    empty += 1;
+                    continue;
+                }
+                if empty > 0 {
+                    placement.push_str(&empty.to_string());
+                    empty = 0;
+                }
+                placement.push_str(&piece.to_string());
+            }
+            if empty > 0 {
+                placement.push_str(&empty.to_string());
+            }
+        }
+
+        let active_color = if self.color_to_move == ChessColor::WHITE {
+            "w"
+        } else {
+            "b"
+        };
+        let mut castling = String::new();
+        for (color, short, long) in [(ChessColor::WHITE, 'K', 'Q'), (ChessColor::BLACK, 'k', 'q')] {
+            let rights = self.castling_rights[color.get_idx()];
+            if rights.contains(CastlingRights::SHORT) {
+                castling.push(short);
+            }
+            if rights.contains(CastlingRights::LONG) {
+                castling.push(long);
+            }
+        }
+        if castling.is_empty() {
+            castling.push('-');
+        }
+        let en_passant = self
+            .en_passant_target
+            .map(idx_to_notation)
+            .unwrap_or_else(|| "-".to_string());
+
+        format!(
+            "{} {} {} {} {} {}",
+            placement,
+            active_color,
+            castling,
+            en_passant,
+            self.halfmove_clock,
+            self.fullmove_number
+        )
+    }
     fn precompute_board_data(&mut self) {
         for idx in 0..CHESS_BOARD_SIZE {
             let x = idx % CHESS_BOARD_WIDTH;
@@ -182,16 +282,46 @@ impl ChessBoard {
             self.squares_to_edge[idx][6] = south_west;
             self.squares_to_edge[idx][7] = south_east;
 
-            for i in 0..8 {
+            for (i, offset) in KNIGHT_OFFSETS.iter().enumerate() {
                 if self.squares_to_edge[idx][i / 2] >= 2 - ((i) % 2)
                     && self.squares_to_edge[idx][(i / 2 + 1) % 4] >= 2 - ((i + 1) % 2)
                 {
-                    let offset = KNIGHT_OFFSETS[i];
                     self.knight_jumps[idx].push((idx as i32 + offset) as usize);
                 }
             }
         }
     }
+    pub fn compute_zobrist_key(&self) -> u64 {
+        let mut key = self.castling_key();
+        for (idx, piece) in self.layout.iter().enumerate() {
+            key ^= ZOBRIST_KEYS.piece(*piece, idx);
+        }
+        if let Some(en_passant_idx) = self.en_passant_target {
+            key ^= ZOBRIST_KEYS.en_passant(en_passant_idx);
+        }
+        if self.color_to_move == ChessColor::BLACK {
+            key ^= ZOBRIST_KEYS.black_to_move();
+        }
+        key
+    }
+    pub fn compute_pawn_key(&self) -> u64 {
+        let mut key = 0;
+        for (idx, piece) in self.layout.iter().enumerate() {
+            key ^= ZOBRIST_KEYS.pawn(*piece, idx);
+        }
+        key
+    }
+    fn castling_key(&self) -> u64 {
+        ZOBRIST_KEYS.castling(ChessColor::WHITE, self.castling_rights[0])
+            ^ ZOBRIST_KEYS.castling(ChessColor::BLACK, self.castling_rights[1])
+    }
+    fn move_piece(&mut self, start_idx: usize, end_idx: usize) {
+        let piece = self.layout[start_idx];
+        self.zobrist_key ^=
+            ZOBRIST_KEYS.piece(piece, start_idx) ^ ZOBRIST_KEYS.piece(piece, end_idx);
+        self.layout[end_idx] = piece;
+        self.layout[start_idx] = ChessPiece::empty();
+    }
     pub fn make_move_unchecked(&mut self, chess_move: &ChessMove) {
         let (start_sq, end_sq, flags) = chess_move.get_idx();
 
@@ -202,70 +332,66 @@ impl ChessBoard {
         let captured_piece = self.layout[end_idx];
 
         let piece_is_pawn = piece.contains(ChessPiece::PAWN);
-        let opposite_color = self.color_to_move ^ ChessPiece::COLOR_BITMASK;
+        let opposite_color = self.color_to_move.opposite();
+
+        self.history.push(BoardState {
+            captured_piece,
+            castling_rights: self.castling_rights,
+            en_passant_target: self.en_passant_target,
+            halfmove_clock: self.halfmove_clock,
+            zobrist_key: self.zobrist_key,
+            pawn_key: self.pawn_key,
+        });
+        self.zobrist_key ^= self.castling_key();
+        if let Some(en_passant_idx) = self.en_passant_target {
+            self.zobrist_key ^= ZOBRIST_KEYS.en_passant(en_passant_idx);
+        }
 
         //Check castling
         if piece.contains(ChessPiece::KING) {
             if flags == MoveFlags::CASTLE_LONG {
                 let rook_end_idx = (start_idx as i32 + DIRECTION_OFFSETS[3]) as usize;
                 let rook_start_idx = (start_idx as i32 + DIRECTION_OFFSETS[3] * 4) as usize;
-                let rook = self.layout[rook_start_idx];
-                self.layout[rook_end_idx] = rook;
-                self.layout[rook_start_idx] = ChessPiece::empty();
+                self.move_piece(rook_start_idx, rook_end_idx);
             }
             if flags == MoveFlags::CASTLE_SHORT {
                 let rook_end_idx = (start_idx as i32 + DIRECTION_OFFSETS[1]) as usize;
                 let rook_start_idx = (start_idx as i32 + DIRECTION_OFFSETS[1] * 3) as usize;
-                let rook = self.layout[rook_start_idx];
-                self.layout[rook_end_idx] = rook;
-                self.layout[rook_start_idx] = ChessPiece::empty();
+                self.move_piece(rook_start_idx, rook_end_idx);
             }
 
-            self.can_catle_long
-                .retain(|&color| color != self.color_to_move);
-
-            self.can_catle_short
-                .retain(|&color| color != self.color_to_move);
-        }
-        //Check if rook moves
-        if piece.contains(ChessPiece::ROOK) {
-            if start_idx % 8 < CHESS_BOARD_WIDTH / 2 {
-                self.can_catle_long
-                    .retain(|&color| color != self.color_to_move);
-            } else if start_idx % 8 > CHESS_BOARD_WIDTH / 2 {
-                self.can_catle_short
-                    .retain(|&color| color != self.color_to_move);
-            }
+            self.castling_rights[self.color_to_move.get_idx()] = CastlingRights::empty();
         }
-        //Check if rook  is captured
-        if captured_piece.contains(ChessPiece::ROOK) {
-            if end_idx % 8 < CHESS_BOARD_WIDTH / 2 {
-                self.can_catle_long.retain(|&color| color != opposite_color);
-            } else if end_idx % 8 > CHESS_BOARD_WIDTH / 2 {
-                self.can_catle_short
-                    .retain(|&color| color != opposite_color);
+        //Check if a rook leaves or is captured on its starting square
+        for idx in [start_idx, end_idx] {
+            match idx {
+                0 => self.castling_rights[ChessColor::BLACK.get_idx()] -= CastlingRights::LONG,
+                7 => self.castling_rights[ChessColor::BLACK.get_idx()] -= CastlingRights::SHORT,
+                56 => self.castling_rights[ChessColor::WHITE.get_idx()] -= CastlingRights::LONG,
+                63 => self.castling_rights[ChessColor::WHITE.get_idx()] -= CastlingRights::SHORT,
+                _ => (),
             }
         }
 
         //Check for pormoton
-        if piece_is_pawn
-            && (end_idx < CHESS_BOARD_WIDTH || end_idx > CHESS_BOARD_SIZE - CHESS_BOARD_WIDTH - 1)
-        {
-            piece = ChessPiece::QUEEN | self.color_to_move;
+        if let Some(promotion) = chess_move.get_promotion() {
+            piece = promotion | self.color_to_move.to_piece();
         }
 
         //Check for en passant
-        if let Some(en_passant_idx) = self.en_passant_target {
-            if flags == MoveFlags::EN_PASSANT {
-                let x = en_passant_idx % 8;
-                let y = start_sq / 8;
-                self.layout[(y * CHESS_BOARD_WIDTH as u16 + x) as usize] = ChessPiece::empty();
-            }
+        if flags == MoveFlags::EN_PASSANT {
+            let captured_idx =
+                (start_idx / CHESS_BOARD_WIDTH) * CHESS_BOARD_WIDTH + end_idx % CHESS_BOARD_WIDTH;
+            self.zobrist_key ^= ZOBRIST_KEYS.piece(self.layout[captured_idx], captured_idx);
+            self.pawn_key ^= ZOBRIST_KEYS.pawn(self.layout[captured_idx], captured_idx);
+            self.layout[captured_idx] = ChessPiece::empty();
         }
         //Check if pawn has moved twice
         if flags == MoveFlags::PAWN_TWO_FORWARD {
             let diff = (start_idx as i32 - end_idx as i32) / 2;
-            self.en_passant_target = Some((start_idx as i32 - diff) as u16);
+            let en_passant_idx = (start_idx as i32 - diff) as u16;
+            self.en_passant_target = Some(en_passant_idx);
+            self.zobrist_key ^= ZOBRIST_KEYS.en_passant(en_passant_idx);
         } else {
             self.en_passant_target = None;
         }
@@ -276,32 +402,370 @@ impl ChessBoard {
             self.halfmove_clock = 0;
         }
 
+        self.zobrist_key ^= ZOBRIST_KEYS.piece(self.layout[start_idx], start_idx)
+            ^ ZOBRIST_KEYS.piece(captured_piece, end_idx)
+            ^ ZOBRIST_KEYS.piece(piece, end_idx);
+        //Pawn moves, pawn captures and promotions all change the pawn structure
+        self.pawn_key ^= ZOBRIST_KEYS.pawn(self.layout[start_idx], start_idx)
+            ^ ZOBRIST_KEYS.pawn(captured_piece, end_idx)
+            ^ ZOBRIST_KEYS.pawn(piece, end_idx);
         self.layout[end_idx] = piece;
         self.layout[start_idx] = ChessPiece::empty();
 
-        if self.color_to_move == ChessPiece::BLACK {
+        if self.color_to_move == ChessColor::BLACK {
             self.fullmove_number += 1;
         }
         self.color_to_move = opposite_color;
+        self.zobrist_key ^= self.castling_key() ^ ZOBRIST_KEYS.black_to_move();
+    }
+    /// Takes back `chess_move`, which must be the last move made on this board.
+    pub fn unmake_move(&mut self, chess_move: &ChessMove) {
+        let state = match self.history.pop() {
+            Some(state) => state,
+            None => return,
+        };
+        let (start_sq, end_sq, flags) = chess_move.get_idx();
+
+        let start_idx = start_sq as usize;
+        let end_idx = end_sq as usize;
+
+        self.color_to_move = self.color_to_move.opposite();
+        if self.color_to_move == ChessColor::BLACK {
+            self.fullmove_number -= 1;
+        }
+
+        let mut piece = self.layout[end_idx];
+        if chess_move.get_promotion().is_some() {
+            piece = ChessPiece::PAWN | self.color_to_move.to_piece();
+        }
+        self.layout[start_idx] = piece;
+        self.layout[end_idx] = state.captured_piece;
+
+        if flags == MoveFlags::EN_PASSANT {
+            let captured_idx =
+                (start_idx / CHESS_BOARD_WIDTH) * CHESS_BOARD_WIDTH + end_idx % CHESS_BOARD_WIDTH;
+            self.layout[captured_idx] = ChessPiece::PAWN | self.color_to_move.opposite().to_piece();
+        }
+        if piece.contains(ChessPiece::KING) {
+            if flags == MoveFlags::CASTLE_LONG {
+                let rook_idx = (start_idx as i32 + DIRECTION_OFFSETS[3]) as usize;
+                let rook_start_idx = (start_idx as i32 + DIRECTION_OFFSETS[3] * 4) as usize;
+                self.layout[rook_start_idx] = self.layout[rook_idx];
+                self.layout[rook_idx] = ChessPiece::empty();
+            }
+            if flags == MoveFlags::CASTLE_SHORT {
+                let rook_idx = (start_idx as i32 + DIRECTION_OFFSETS[1]) as usize;
+                let rook_start_idx = (start_idx as i32 + DIRECTION_OFFSETS[1] * 3) as usize;
+                self.layout[rook_start_idx] = self.layout[rook_idx];
+                self.layout[rook_idx] = ChessPiece::empty();
+            }
+        }
+
+        self.castling_rights = state.castling_rights;
+        self.en_passant_target = state.en_passant_target;
+        self.halfmove_clock = state.halfmove_clock;
+        self.zobrist_key = state.zobrist_key;
+        self.pawn_key = state.pawn_key;
+    }
+    /// Passes the turn to the opponent without moving a piece, as used by null-move pruning.
+    pub fn make_null_move(&mut self) {
+        self.history.push(BoardState {
+            captured_piece: ChessPiece::empty(),
+            castling_rights: self.castling_rights,
+            en_passant_target: self.en_passant_target,
+            halfmove_clock: self.halfmove_clock,
+            zobrist_key: self.zobrist_key,
+            pawn_key: self.pawn_key,
+        });
+        if let Some(en_passant_idx) = self.en_passant_target.take() {
+            self.zobrist_key ^= ZOBRIST_KEYS.en_passant(en_passant_idx);
+        }
+        //Positions on either side of a null move are never treated as repetitions
+        self.halfmove_clock = 0;
+        self.color_to_move = self.color_to_move.opposite();
+        self.zobrist_key ^= ZOBRIST_KEYS.black_to_move();
+    }
+    pub fn unmake_null_move(&mut self) {
+        if let Some(state) = self.history.pop() {
+            self.color_to_move = self.color_to_move.opposite();
+            self.en_passant_target = state.en_passant_target;
+            self.halfmove_clock = state.halfmove_clock;
+            self.zobrist_key = state.zobrist_key;
+        }
+    }
+    pub fn has_non_pawn_material(&self, color: ChessColor) -> bool {
+        let non_pawn =
+            ChessPiece::KNIGHT | ChessPiece::BISHOP | ChessPiece::ROOK | ChessPiece::QUEEN;
+        self.layout
+            .iter()
+            .any(|piece| piece.has_color(color) && piece.intersects(non_pawn))
+    }
+    /// Number of pieces on the board, kings and pawns included.
+    pub fn piece_count(&self) -> usize {
+        self.layout.iter().filter(|piece| !piece.is_empty()).count()
+    }
+    /// The material in tablebase notation with the pieces of `first` in front, e.g. KRvK.
+    pub fn material_signature(&self, first: ChessColor) -> String {
+        let side = |color: ChessColor| -> String {
+            let mut signature = String::new();
+            for (code, piece_type) in [
+                ('K', ChessPiece::KING),
+                ('Q', ChessPiece::QUEEN),
+                ('R', ChessPiece::ROOK),
+                ('B', ChessPiece::BISHOP),
+                ('N', ChessPiece::KNIGHT),
+                ('P', ChessPiece::PAWN),
+            ] {
+                let piece = piece_type | color.to_piece();
+                for _ in self.layout.iter().filter(|&&p| p == piece) {
+                    signature.push(code);
+                }
+            }
+            signature
+        };
+        format!("{}v{}", side(first), side(first.opposite()))
+    }
+    /// Whether the current position already occurred since the last capture or pawn move.
+    pub fn is_repetition(&self) -> bool {
+        self.history
+            .iter()
+            .rev()
+            .take(self.halfmove_clock as usize)
+            .skip(1)
+            .step_by(2)
+            .any(|state| state.zobrist_key == self.zobrist_key)
+    }
+    /// How the rules end the game in this position, None while it goes on. When checkmate is
+    /// given on the hundredth half-move, the checkmate counts.
+    pub fn outcome(&self) -> Option<GameOutcome> {
+        if self.generate_legal_moves().is_empty() {
+            return Some(if self.in_check() {
+                GameOutcome::Checkmate
+            } else {
+                GameOutcome::Stalemate
+            });
+        }
+        if self.halfmove_clock >= 100 {
+            return Some(GameOutcome::FiftyMoveRule);
+        }
+        let minor = ChessPiece::KNIGHT | ChessPiece::BISHOP;
+        let pieces = self.piece_count();
+        if pieces == 2 || (pieces == 3 && self.layout.iter().any(|piece| piece.intersects(minor))) {
+            return Some(GameOutcome::InsufficientMaterial);
+        }
+        None
+    }
+    pub fn king_square(&self, color: ChessColor) -> Option<usize> {
+        let king = ChessPiece::KING | color.to_piece();
+        self.layout.iter().position(|&piece| piece == king)
+    }
+    pub fn in_check(&self) -> bool {
+        match self.king_square(self.color_to_move) {
+            Some(king_idx) => {
+                self.is_square_attacked(&self.layout, king_idx, self.color_to_move.opposite())
+            }
+            None => false,
+        }
+    }
+    /// Finds the legal move written in long algebraic notation, e.g. `e2e4` or `e7e8q`.
+    pub fn find_move(&self, notation: &str) -> Option<ChessMove> {
+        self.generate_legal_moves()
+            .into_iter()
+            .find(|chess_move| chess_move.to_string() == notation)
+    }
+    /// Finds the legal move written in standard algebraic notation, e.g. `Nbd7`, `exd5`,
+    /// `e8=Q+` or `O-O`. Check and annotation marks are ignored.
+    pub fn find_san_move(&self, san: &str) -> Option<ChessMove> {
+        let san = san.trim_end_matches(['+', '#', '!', '?']);
+        let legal_moves = self.generate_legal_moves();
+        let castling = match san {
+            "O-O" | "0-0" => Some(MoveFlags::CASTLE_SHORT),
+            "O-O-O" | "0-0-0" => Some(MoveFlags::CASTLE_LONG),
+            _ => None,
+        };
+        if let Some(castling) = castling {
+            return legal_moves
+                .into_iter()
+                .find(|chess_move| chess_move.get_idx().2 == castling);
+        }
+
+        let (san, promotion) = match san.char_indices().last() {
+            Some((i, code)) if "QRBN".contains(code) && i > 0 => {
+                let promotion = ChessPiece::new_from_notation(&code.to_lowercase().to_string())
+                    .ok()?
+                    & ChessPiece::PIECE_BITMASK;
+                (san[..i].trim_end_matches('='), Some(promotion))
+            }
+            _ => (san, None),
+        };
+        let (piece_type, san) = match san.chars().next() {
+            Some(code) if "KQRBN".contains(code) => (
+                ChessPiece::new_from_notation(&code.to_string()).ok()? & ChessPiece::PIECE_BITMASK,
+                &san[1..],
+            ),
+            _ => (ChessPiece::PAWN, san),
+        };
+        if san.len() < 2 || !san.is_char_boundary(san.len() - 2) {
+            return None;
+        }
+        let end_idx = notation_to_idx(&san[san.len() - 2..]).ok()?;
+        //Whatever is left in front of the target square narrows down where the piece comes from
+        let disambiguation: Vec<char> = san[..san.len() - 2]
+            .chars()
+            .filter(|&c| c != 'x' && c != ':')
+            .collect();
+
+        let mut candidates = legal_moves.into_iter().filter(|chess_move| {
+            let (start_idx, move_end_idx, flags) = chess_move.get_idx();
+            let from = idx_to_notation(start_idx);
+            move_end_idx == end_idx
+                && flags != MoveFlags::CASTLE_SHORT
+                && flags != MoveFlags::CASTLE_LONG
+                && self.layout[start_idx as usize].contains(piece_type)
+                && chess_move.get_promotion() == promotion
+                && disambiguation.iter().all(|&c| from.contains(c))
+        });
+        let chess_move = candidates.next()?;
+        //An ambiguous move isn't valid SAN
+        candidates.next().is_none().then_some(chess_move)
+    }
+    /// Writes a legal move in standard algebraic notation, with `+` or `#` when it gives check.
+    pub fn to_san(&self, chess_move: &ChessMove) -> String {
+        let (start_idx, end_idx, flags) = chess_move.get_idx();
+        let piece_type = self.layout[start_idx as usize] & ChessPiece::PIECE_BITMASK;
+        let mut san = match flags {
+            MoveFlags::CASTLE_SHORT => "O-O".to_string(),
+            MoveFlags::CASTLE_LONG => "O-O-O".to_string(),
+            _ => {
+                let from = idx_to_notation(start_idx);
+                let mut san = String::new();
+                if piece_type == ChessPiece::PAWN {
+                    if self.is_capture(chess_move) {
+                        san.push_str(&from[..1]);
+                    }
+                } else {
+                    san.push_str(&piece_type.to_string().to_uppercase());
+                    //Other pieces of the same kind reaching the square call for the file, the
+                    //rank or both of the one that moves
+                    let rivals: Vec<String> = self
+                        .generate_legal_moves()
+                        .iter()
+                        .map(|other| other.get_idx())
+                        .filter(|&(other_start, other_end, _)| {
+                            other_end == end_idx
+                                && other_start != start_idx
+                                && self.layout[other_start as usize].contains(piece_type)
+                        })
+                        .map(|(other_start, _, _)| idx_to_notation(other_start))
+                        .collect();
+                    if !rivals.is_empty() {
+                        if rivals.iter().all(|rival| rival[..1] != from[..1]) {
+                            san.push_str(&from[..1]);
+                        } else if rivals.iter().all(|rival| rival[1..] != from[1..]) {
+                            san.push_str(&from[1..]);
+                        } else {
+                            san.push_str(&from);
+                        }
+                    }
+                }
+                if self.is_capture(chess_move) {
+                    san.push('x');
+                }
+                san.push_str(&idx_to_notation(end_idx));
+                if let Some(promotion) = chess_move.get_promotion() {
+                    san.push('=');
+                    san.push_str(&promotion.to_string().to_uppercase());
+                }
+                san
+            }
+        };
+
+        let mut after = self.clone();
+        after.make_move_unchecked(chess_move);
+        if after.in_check() {
+            san.push(if after.generate_legal_moves().is_empty() {
+                '#'
+            } else {
+                '+'
+            });
+        }
+        san
+    }
+    /// Writes a line of legal moves starting from this position in standard algebraic notation.
+    pub fn to_san_line(&self, line: &[ChessMove]) -> Vec<String> {
+        let mut board = self.clone();
+        line.iter()
+            .map(|chess_move| {
+                let san = board.to_san(chess_move);
+                board.make_move_unchecked(chess_move);
+                san
+            })
+            .collect()
+    }
+    pub fn is_capture(&self, chess_move: &ChessMove) -> bool {
+        let (_start_sq, end_sq, flags) = chess_move.get_idx();
+        !self.layout[end_sq as usize].is_empty() || flags == MoveFlags::EN_PASSANT
     }
     pub fn generate_legal_moves(&self) -> Vec<ChessMove> {
+        let mut moves = self.generate_pseudo_legal_moves();
+        moves.retain(|chess_move| self.is_legal(chess_move));
+        moves
+    }
+    //Plays the move out on a copy of the layout and checks whether it leaves the king attacked
+    fn is_legal(&self, chess_move: &ChessMove) -> bool {
+        let (start_sq, end_sq, flags) = chess_move.get_idx();
+        let start_idx = start_sq as usize;
+        let end_idx = end_sq as usize;
+        let opposite_color = self.color_to_move.opposite();
+
+        let mut layout = self.layout;
+        let piece = layout[start_idx];
+
+        if flags == MoveFlags::CASTLE_SHORT || flags == MoveFlags::CASTLE_LONG {
+            //The king may not castle out of or through check
+            let passed_idx = (start_idx + end_idx) / 2;
+            if self.is_square_attacked(&layout, start_idx, opposite_color)
+                || self.is_square_attacked(&layout, passed_idx, opposite_color)
+            {
+                return false;
+            }
+        }
+        if flags == MoveFlags::EN_PASSANT {
+            let captured_idx =
+                (start_idx / CHESS_BOARD_WIDTH) * CHESS_BOARD_WIDTH + end_idx % CHESS_BOARD_WIDTH;
+            layout[captured_idx] = ChessPiece::empty();
+        }
+        layout[end_idx] = piece;
+        layout[start_idx] = ChessPiece::empty();
+
+        let king_idx = if piece.contains(ChessPiece::KING) {
+            Some(end_idx)
+        } else {
+            self.king_square(self.color_to_move)
+        };
+        match king_idx {
+            Some(king_idx) => !self.is_square_attacked(&layout, king_idx, opposite_color),
+            None => true,
+        }
+    }
+    fn generate_pseudo_legal_moves(&self) -> Vec<ChessMove> {
         let mut moves = vec![];
 
         for idx in 0..CHESS_BOARD_SIZE {
             let square = self.layout[idx];
 
-            if square.contains(self.color_to_move) {
+            if square.has_color(self.color_to_move) {
                 let start_pos = idx as u16;
                 match square & ChessPiece::PIECE_BITMASK {
                     ChessPiece::KING => self.generate_king_moves(&mut moves, start_pos),
-                    ChessPiece::QUEEN => self.generate_sliding_moves(&mut moves, start_pos, 0, 8),
-                    ChessPiece::ROOK => self.generate_sliding_moves(&mut moves, start_pos, 0, 4),
-                    ChessPiece::BISHOP => self.generate_sliding_moves(&mut moves, start_pos, 4, 8),
-                    ChessPiece::KNIGHT => self.generate_knight_moves(&mut moves, start_pos),
+                    ChessPiece::QUEEN
+                    | ChessPiece::ROOK
+                    | ChessPiece::BISHOP
+                    | ChessPiece::KNIGHT => self.generate_piece_moves(&mut moves, idx),
                     ChessPiece::PAWN => self.generate_pawn_moves(
                         &mut moves,
                         start_pos,
-                        (((square.bits() >> ChessPiece::COLOR_OFFSET.bits()) - 1) * 2) as usize,
+                        self.color_to_move.pawn_direction(),
                     ),
                     _ => (),
                 }
@@ -310,17 +774,111 @@ impl ChessBoard {
 
         moves
     }
+    /// Pseudo-legal moves of the knight, bishop, rook or queen on `idx`, whichever side is to
+    /// move. Other pieces get no moves.
+    pub fn generate_piece_moves(&self, moves: &mut Vec<ChessMove>, idx: usize) {
+        let piece = self.layout[idx];
+        let Some(color) = piece.get_color() else {
+            return;
+        };
+        let start_pos = idx as u16;
+        match piece & ChessPiece::PIECE_BITMASK {
+            ChessPiece::QUEEN => self.generate_sliding_moves(moves, start_pos, color, 0, 8),
+            ChessPiece::ROOK => self.generate_sliding_moves(moves, start_pos, color, 0, 4),
+            ChessPiece::BISHOP => self.generate_sliding_moves(moves, start_pos, color, 4, 8),
+            ChessPiece::KNIGHT => self.generate_knight_moves(moves, start_pos, color),
+            _ => (),
+        }
+    }
+    /// Squares of all pieces of `color` attacking `square` on `layout`. The layout is passed
+    /// in so callers can lift pieces off the board to uncover x-ray attackers behind them.
+    pub fn attackers_to(
+        &self,
+        layout: &[ChessPiece; CHESS_BOARD_SIZE],
+        square: usize,
+        color: ChessColor,
+    ) -> Vec<usize> {
+        let mut attackers = vec![];
+        self.find_attackers(layout, square, color, |idx| {
+            attackers.push(idx);
+            false
+        });
+        attackers
+    }
+    pub fn is_square_attacked(
+        &self,
+        layout: &[ChessPiece; CHESS_BOARD_SIZE],
+        square: usize,
+        color: ChessColor,
+    ) -> bool {
+        self.find_attackers(layout, square, color, |_idx| true)
+    }
+    //Calls on_attacker for every attacker until it returns true, returns whether it did
+    fn find_attackers(
+        &self,
+        layout: &[ChessPiece; CHESS_BOARD_SIZE],
+        square: usize,
+        color: ChessColor,
+        mut on_attacker: impl FnMut(usize) -> bool,
+    ) -> bool {
+        let color_piece = color.to_piece();
+        let squares_to_edge = self.squares_to_edge[square];
+
+        //Pawns attack diagonally forward, so look diagonally backwards from the square
+        let backwards = color.opposite().pawn_direction();
+        for i in 0..2 {
+            if squares_to_edge[4 + backwards + i] >= 1 {
+                let idx = (square as i32 + DIRECTION_OFFSETS[4 + backwards + i]) as usize;
+                if layout[idx] == ChessPiece::PAWN | color_piece && on_attacker(idx) {
+                    return true;
+                }
+            }
+        }
+
+        for &idx in &self.knight_jumps[square] {
+            if layout[idx] == ChessPiece::KNIGHT | color_piece && on_attacker(idx) {
+                return true;
+            }
+        }
+
+        for (direction_idx, direction_offset) in DIRECTION_OFFSETS.iter().enumerate() {
+            for num_squares in 1..squares_to_edge[direction_idx] + 1 {
+                let idx = (square as i32 + num_squares as i32 * direction_offset) as usize;
+                let piece = layout[idx];
+                if piece.is_empty() {
+                    continue;
+                }
+                if piece.contains(color_piece) {
+                    let slider = if direction_idx < 4 {
+                        ChessPiece::ROOK | ChessPiece::QUEEN
+                    } else {
+                        ChessPiece::BISHOP | ChessPiece::QUEEN
+                    };
+                    if (piece.intersects(slider)
+                        || (num_squares == 1 && piece.contains(ChessPiece::KING)))
+                        && on_attacker(idx)
+                    {
+                        return true;
+                    }
+                }
+                break;
+            }
+        }
+
+        false
+    }
     fn generate_king_moves(&self, moves: &mut Vec<ChessMove>, start_pos: u16) {
-        for i in 0..8 {
+        for (i, direction_offset) in DIRECTION_OFFSETS.iter().enumerate() {
             let squares_to_edge = self.squares_to_edge[start_pos as usize][i];
             if squares_to_edge >= 1 {
-                let end_pos = (start_pos as i32 + DIRECTION_OFFSETS[i]) as u16;
-                if !self.layout[end_pos as usize].contains(self.color_to_move) {
+                let end_pos = (start_pos as i32 + direction_offset) as u16;
+                if !self.layout[end_pos as usize].has_color(self.color_to_move) {
                     moves.push(ChessMove::new(start_pos, end_pos, MoveFlags::empty()));
                 }
             }
         }
-        if self.can_catle_short.contains(&self.color_to_move) {
+        let castling_rights = self.castling_rights[self.color_to_move.get_idx()];
+        if castling_rights.contains(CastlingRights::SHORT) {
             let mut occupied = false;
             for i in 1..3 {
                 let end_idx = (start_pos as i32 + DIRECTION_OFFSETS[1] * i) as u16;
@@ -335,7 +893,7 @@ impl ChessBoard {
                 moves.push(ChessMove::new(start_pos, end_pos, MoveFlags::CASTLE_SHORT))
             }
         }
-        if self.can_catle_long.contains(&self.color_to_move) {
+        if castling_rights.contains(CastlingRights::LONG) {
             let mut occupied = false;
             for i in 1..4 {
                 let end_idx = (start_pos as i32 + DIRECTION_OFFSETS[3] * i) as u16;
@@ -350,10 +908,10 @@ impl ChessBoard {
             }
         }
     }
-    fn generate_knight_moves(&self, moves: &mut Vec<ChessMove>, start_pos: u16) {
+    fn generate_knight_moves(&self, moves: &mut Vec<ChessMove>, start_pos: u16, color: ChessColor) {
         let jumps = &self.knight_jumps[start_pos as usize];
         for end_pos in jumps {
-            if !self.layout[*end_pos].contains(self.color_to_move) {
+            if !self.layout[*end_pos].has_color(color) {
                 let chess_move = ChessMove::new(start_pos, *end_pos as u16, MoveFlags::empty());
                 moves.push(chess_move);
             }
@@ -370,7 +928,7 @@ impl ChessBoard {
         let end_pos = (start_pos as i32 + direction_offset) as u16;
 
         if self.layout[end_pos as usize] == ChessPiece::empty() {
-            moves.push(ChessMove::new(start_pos, end_pos, MoveFlags::empty()));
+            Self::push_pawn_move(moves, start_pos, end_pos);
             if squares_to_edge[offset] == CHESS_BOARD_HEIGHT - 2 {
                 let end_pos_2 = (start_pos as i32 + direction_offset * 2) as u16;
 
@@ -399,36 +957,52 @@ impl ChessBoard {
             let squares_to_side = squares_to_edge[4 + offset + i];
             if squares_to_side >= 1 {
                 let end_pos = (start_pos as i32 + DIRECTION_OFFSETS[4 + offset + i]) as u16;
-                if !self.layout[end_pos as usize].contains(self.color_to_move)
+                if !self.layout[end_pos as usize].has_color(self.color_to_move)
                     && !self.layout[end_pos as usize].is_empty()
                 {
-                    moves.push(ChessMove::new(start_pos, end_pos, MoveFlags::empty()));
+                    Self::push_pawn_move(moves, start_pos, end_pos);
                 }
             }
         }
     }
+    fn push_pawn_move(moves: &mut Vec<ChessMove>, start_pos: u16, end_pos: u16) {
+        let end_idx = end_pos as usize;
+        if (CHESS_BOARD_WIDTH..CHESS_BOARD_SIZE - CHESS_BOARD_WIDTH).contains(&end_idx) {
+            moves.push(ChessMove::new(start_pos, end_pos, MoveFlags::empty()));
+            return;
+        }
+        for flags in [
+            MoveFlags::PROMOTE_QUEEN,
+            MoveFlags::PROMOTE_ROOK,
+            MoveFlags::PROMOTE_BISHOP,
+            MoveFlags::PROMOTE_KNIGHT,
+        ] {
+            moves.push(ChessMove::new(start_pos, end_pos, flags));
+        }
+    }
     fn generate_sliding_moves(
         &self,
         moves: &mut Vec<ChessMove>,
         start_pos: u16,
+        color: ChessColor,
         start: usize,
         end: usize,
     ) {
-        for direction_idx in start..end {
+        for (direction_idx, direction_offset) in
+            DIRECTION_OFFSETS.iter().enumerate().take(end).skip(start)
+        {
             let squares_to_edge = self.squares_to_edge[start_pos as usize][direction_idx];
 
             for num_squares in 1..squares_to_edge + 1 {
-                let end_pos = (start_pos as i32
-                    + (num_squares as i32 * DIRECTION_OFFSETS[direction_idx]))
-                    as u16;
+                let end_pos = (start_pos as i32 + (num_squares as i32 * direction_offset)) as u16;
                 let square = self.layout[end_pos as usize];
 
-                if square.contains(self.color_to_move) {
+                if square.has_color(color) {
                     break;
                 }
 
                 moves.push(ChessMove::new(start_pos, end_pos, MoveFlags::empty()));
-                if !square.is_empty() && !square.contains(self.color_to_move) {
+                if !square.is_empty() {
                     break;
                 }
             }
@@ -436,6 +1010,12 @@ impl ChessBoard {
     }
 }
 
+impl Default for ChessBoard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Display for ChessBoard {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.write_str("┌───")?;
@@ -468,3 +1048,84 @@ impl Display for ChessBoard {
         f.write_str("  a   b   c   d   e   f   g   h")
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    //En passant, castling, promotions and capture-promotions within three plies
+    pub(crate) const POSITIONS: [&str; 3] = [
+        "rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 3",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+    ];
+
+    //Calls `visit` on every position up to `depth` plies away, before and after each move
+    pub(crate) fn walk(board: &mut ChessBoard, depth: usize, visit: &mut impl FnMut(&ChessBoard)) {
+        visit(board);
+        if depth == 0 {
+            return;
+        }
+        for chess_move in board.generate_legal_moves() {
+            board.make_move_unchecked(&chess_move);
+            walk(board, depth - 1, visit);
+            board.unmake_move(&chess_move);
+            visit(board);
+        }
+    }
+
+    #[test]
+    fn rejects_placements_off_the_board() {
+        for fen in [
+            "8/8/8/8/8/8/8/8/p w - - 0 1",
+            "rnbqkbnrp/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "8/8/8/8/8/8/8/7k1K w - - 0 1",
+        ] {
+            assert!(ChessBoard::new_from_fen(fen).is_err(), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn pawn_key_is_updated_incrementally() {
+        for fen in POSITIONS {
+            let mut board = ChessBoard::new_from_fen(fen).unwrap();
+            walk(&mut board, 3, &mut |board| {
+                assert_eq!(
+                    board.pawn_key,
+                    board.compute_pawn_key(),
+                    "{}",
+                    board.to_fen()
+                );
+            });
+        }
+    }
+
+    #[test]
+    fn outcome_follows_the_rules() {
+        for (fen, expected) in [
+            (crate::chess::START_POSITION_FEN, None),
+            (
+                "7k/6Q1/6K1/8/8/8/8/8 b - - 0 1",
+                Some(GameOutcome::Checkmate),
+            ),
+            ("7k/8/6QK/8/8/8/8/8 b - - 0 1", Some(GameOutcome::Stalemate)),
+            (
+                "7k/6Q1/6K1/8/8/8/8/8 b - - 100 80",
+                Some(GameOutcome::Checkmate),
+            ),
+            (
+                "7k/8/8/8/8/8/6QK/8 b - - 100 80",
+                Some(GameOutcome::FiftyMoveRule),
+            ),
+            (
+                "7k/8/8/8/8/8/6NK/8 b - - 0 1",
+                Some(GameOutcome::InsufficientMaterial),
+            ),
+            ("7k/8/8/8/8/8/6PK/8 b - - 0 1", None),
+        ] {
+            let board = ChessBoard::new_from_fen(fen).unwrap();
+            assert_eq!(board.outcome(), expected, "{}", fen);
+        }
+    }
+}
@@ -2,6 +2,7 @@ use std::fmt::{self, Display};
 
 use bitflags::bitflags;
 
+use super::chess_piece::ChessPiece;
 use super::InvalidNotationError;
 
 bitflags! {
@@ -12,8 +13,43 @@ bitflags! {
 }
 
 impl ChessColor {
+    pub fn new_from_notation(code: &str) -> Result<Self, InvalidNotationError> {
+        match code {
+            "w" => Ok(Self::WHITE),
+            "b" => Ok(Self::BLACK),
+            _ => Err(InvalidNotationError),
+        }
+    }
     #[inline(always)]
     pub fn get_idx(&self) -> usize {
         (self.bits() - 1) as usize
     }
+    #[inline(always)]
+    pub fn opposite(&self) -> Self {
+        *self ^ Self::all()
+    }
+    /// The color bit used by `ChessPiece` for pieces of this color.
+    #[inline(always)]
+    pub fn to_piece(&self) -> ChessPiece {
+        if *self == Self::WHITE {
+            ChessPiece::WHITE
+        } else {
+            ChessPiece::BLACK
+        }
+    }
+    /// Index into `DIRECTION_OFFSETS` of the direction pawns of this color move in.
+    #[inline(always)]
+    pub fn pawn_direction(&self) -> usize {
+        self.get_idx() * 2
+    }
+}
+
+impl Display for ChessColor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if *self == Self::WHITE {
+            write!(f, "white")
+        } else {
+            write!(f, "black")
+        }
+    }
 }
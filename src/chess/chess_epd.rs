@@ -0,0 +1,202 @@
+use std::fmt::{self, Display};
+
+use crate::chess::chess_board::ChessBoard;
+use crate::chess::chess_move::ChessMove;
+use crate::chess::InvalidFENError;
+
+/// A line of Extended Position Description: the first four fields of a FEN followed by
+/// operations such as `bm Qxh7+; id "WAC.001";`.
+#[derive(Debug, Clone)]
+pub struct EpdPosition {
+    pub board: ChessBoard,
+    /// Every operation with its operands, in the order they appear.
+    pub operations: Vec<(String, Vec<String>)>,
+}
+
+impl EpdPosition {
+    /// The move counters come from the `hmvc` and `fmvn` operations when present.
+    pub fn parse(epd: &str) -> Result<Self, InvalidFENError> {
+        //Fields are separated by any run of whitespace, the operations keep theirs for quoted
+        //operands
+        let mut rest = epd.trim_start();
+        let mut fen_fields = vec![];
+        for _ in 0..4 {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            if end == 0 {
+                return Err(InvalidFENError);
+            }
+            fen_fields.push(&rest[..end]);
+            rest = rest[end..].trim_start();
+        }
+        let operations = parse_operations(rest);
+
+        let counter = |opcode: &str, default: &str| -> String {
+            operations
+                .iter()
+                .find(|(name, _)| name == opcode)
+                .and_then(|(_, operands)| operands.first().cloned())
+                .unwrap_or_else(|| default.to_string())
+        };
+        let fen = format!(
+            "{} {} {}",
+            fen_fields.join(" "),
+            counter("hmvc", "0"),
+            counter("fmvn", "1")
+        );
+        Ok(Self {
+            board: ChessBoard::new_from_fen(&fen)?,
+            operations,
+        })
+    }
+    pub fn operation(&self, opcode: &str) -> Option<&[String]> {
+        self.operations
+            .iter()
+            .find(|(name, _)| name == opcode)
+            .map(|(_, operands)| operands.as_slice())
+    }
+    pub fn id(&self) -> Option<&str> {
+        self.operation("id")?.first().map(String::as_str)
+    }
+    /// The moves of the `bm` operation, written in SAN or in long algebraic notation.
+    pub fn best_moves(&self) -> Vec<ChessMove> {
+        self.moves("bm")
+    }
+    /// The moves of the `am` operation.
+    pub fn avoid_moves(&self) -> Vec<ChessMove> {
+        self.moves("am")
+    }
+    fn moves(&self, opcode: &str) -> Vec<ChessMove> {
+        self.operation(opcode)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|notation| {
+                self.board
+                    .find_san_move(notation)
+                    .or_else(|| self.board.find_move(notation))
+            })
+            .collect()
+    }
+}
+
+impl Display for EpdPosition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let fen = self.board.to_fen();
+        let fields: Vec<&str> = fen.split_whitespace().take(4).collect();
+        f.write_str(&fields.join(" "))?;
+        for (opcode, operands) in &self.operations {
+            write!(f, " {}", opcode)?;
+            for operand in operands {
+                if operand.contains(char::is_whitespace) || operand.contains(';') {
+                    write!(f, " \"{}\"", operand)?;
+                } else {
+                    write!(f, " {}", operand)?;
+                }
+            }
+            f.write_str(";")?;
+        }
+        Ok(())
+    }
+}
+
+//opcode operand operand; opcode "quoted operand"; ...
+fn parse_operations(text: &str) -> Vec<(String, Vec<String>)> {
+    let mut operations = vec![];
+    let mut tokens: Vec<String> = vec![];
+    let mut token = String::new();
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                //Quoted operands may hold spaces and semicolons
+                for c in chars.by_ref() {
+                    if c == '"' {
+                        break;
+                    }
+                    token.push(c);
+                }
+                tokens.push(std::mem::take(&mut token));
+            }
+            ';' => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+                if !tokens.is_empty() {
+                    let opcode = tokens.remove(0);
+                    operations.push((opcode, std::mem::take(&mut tokens)));
+                }
+            }
+            c if c.is_whitespace() => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+            }
+            c => token.push(c),
+        }
+    }
+    //The last operation may lack its semicolon
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+    if !tokens.is_empty() {
+        let opcode = tokens.remove(0);
+        operations.push((opcode, tokens));
+    }
+    operations
+}
+
+/// Reads every position of an EPD file, skipping blank lines and lines that don't parse.
+pub fn parse_epd(text: &str) -> Vec<EpdPosition> {
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| EpdPosition::parse(line).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_operations() {
+        let position = EpdPosition::parse(
+            "r1b1k2r/ppppnppp/2n2q2/2b5/3NP3/2P1B3/PP3PPP/RN1QKB1R w KQkq - bm Nxc6 Qd2; \
+             am Bg5; id \"two best; one avoided\";",
+        )
+        .unwrap();
+        assert_eq!(position.id(), Some("two best; one avoided"));
+        let moves: Vec<String> = position
+            .best_moves()
+            .iter()
+            .map(|m| m.to_string())
+            .collect();
+        assert_eq!(moves, ["d4c6", "d1d2"]);
+        let moves: Vec<String> = position
+            .avoid_moves()
+            .iter()
+            .map(|m| m.to_string())
+            .collect();
+        assert_eq!(moves, ["e3g5"]);
+    }
+
+    #[test]
+    fn accepts_whitespace_runs() {
+        let position = EpdPosition::parse(
+            "  6k1/5ppp/8/8/8/8/5PPP/3R2K1   w \t -  -   bm  Rd8#;  id   \"back  rank\";",
+        )
+        .unwrap();
+        assert_eq!(
+            position.board.to_fen(),
+            "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1"
+        );
+        assert_eq!(position.best_moves().len(), 1);
+        assert_eq!(position.id(), Some("back  rank"));
+    }
+
+    #[test]
+    fn rejects_missing_fields() {
+        assert!(EpdPosition::parse("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w -").is_err());
+        assert!(EpdPosition::parse("").is_err());
+        assert!(EpdPosition::parse("8/8/8/8/8/8/8/8/p w - - bm a1;").is_err());
+    }
+}
@@ -1,5 +1,10 @@
+use std::fmt::{self, Display};
+
 use bitflags::bitflags;
 
+use crate::chess::chess_coordinate::idx_to_notation;
+use crate::chess::chess_piece::ChessPiece;
+
 const START_BITMASK: u16 = 0b0000000000111111;
 const END_BITMASK: u16 = 0b0000111111000000;
 const FLAG_BITMASK: u16 = 0b1111000000000000;
@@ -21,7 +26,7 @@ bitflags! {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct ChessMove {
     pub data: u16,
 }
@@ -37,6 +42,34 @@ impl ChessMove {
         let end_idx = (self.data & END_BITMASK) >> END_OFFSET;
 
         let flags = MoveFlags::from_bits_truncate((self.data & FLAG_BITMASK) >> FLAG_OFFSET); // maybe unchecked
-        return (start_idx, end_idx, flags);
+        (start_idx, end_idx, flags)
+    }
+    /// The colorless piece a pawn turns into, if this move is a promotion.
+    pub fn get_promotion(&self) -> Option<ChessPiece> {
+        let (_start_idx, _end_idx, flags) = self.get_idx();
+        match flags {
+            MoveFlags::PROMOTE_QUEEN => Some(ChessPiece::QUEEN),
+            MoveFlags::PROMOTE_ROOK => Some(ChessPiece::ROOK),
+            MoveFlags::PROMOTE_BISHOP => Some(ChessPiece::BISHOP),
+            MoveFlags::PROMOTE_KNIGHT => Some(ChessPiece::KNIGHT),
+            _ => None,
+        }
+    }
+}
+
+/// Long algebraic notation as used by UCI, e.g. `e2e4` or `e7e8q`.
+impl Display for ChessMove {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (start_idx, end_idx, _flags) = self.get_idx();
+        write!(
+            f,
+            "{}{}",
+            idx_to_notation(start_idx),
+            idx_to_notation(end_idx)
+        )?;
+        if let Some(promotion) = self.get_promotion() {
+            write!(f, "{}", promotion)?;
+        }
+        Ok(())
     }
 }
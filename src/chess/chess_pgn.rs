@@ -0,0 +1,191 @@
+use std::fmt::{self, Display};
+
+use crate::chess::chess_board::ChessBoard;
+use crate::chess::chess_color::ChessColor;
+use crate::chess::chess_move::ChessMove;
+use crate::chess::START_POSITION_FEN;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+    Unknown,
+}
+
+impl GameResult {
+    fn from_notation(notation: &str) -> Option<Self> {
+        match notation {
+            "1-0" => Some(Self::WhiteWins),
+            "0-1" => Some(Self::BlackWins),
+            "1/2-1/2" => Some(Self::Draw),
+            "*" => Some(Self::Unknown),
+            _ => None,
+        }
+    }
+}
+
+impl Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let notation = match self {
+            GameResult::WhiteWins => "1-0",
+            GameResult::BlackWins => "0-1",
+            GameResult::Draw => "1/2-1/2",
+            GameResult::Unknown => "*",
+        };
+        f.pad(notation)
+    }
+}
+
+/// One game of a PGN file: its tag pairs and the moves of the main line in SAN.
+#[derive(Debug, Clone)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub moves: Vec<String>,
+    pub result: GameResult,
+}
+
+impl PgnGame {
+    fn new() -> Self {
+        Self {
+            tags: vec![],
+            moves: vec![],
+            result: GameResult::Unknown,
+        }
+    }
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag_name, _value)| tag_name == name)
+            .map(|(_name, value)| value.as_str())
+    }
+    /// The Elo of the player of `color`, if the game records one.
+    pub fn rating(&self, color: ChessColor) -> Option<u32> {
+        let tag = if color == ChessColor::WHITE {
+            "WhiteElo"
+        } else {
+            "BlackElo"
+        };
+        self.tag(tag)?.parse().ok()
+    }
+    /// The position the game starts from, taken from the FEN tag if there is one.
+    pub fn start_board(&self) -> Option<ChessBoard> {
+        ChessBoard::new_from_fen(self.tag("FEN").unwrap_or(START_POSITION_FEN)).ok()
+    }
+    /// Converts the moves to `ChessMove`s by playing them. The list stops early at the first
+    /// move that is illegal or can't be read.
+    pub fn replay(&self) -> Vec<ChessMove> {
+        let mut chess_moves = Vec::with_capacity(self.moves.len());
+        let Some(mut board) = self.start_board() else {
+            return chess_moves;
+        };
+        for san in &self.moves {
+            let Some(chess_move) = board.find_san_move(san) else {
+                break;
+            };
+            board.make_move_unchecked(&chess_move);
+            chess_moves.push(chess_move);
+        }
+        chess_moves
+    }
+}
+
+/// Reads every game of a PGN text. Comments, variations and numeric annotations are skipped.
+pub fn parse_pgn(text: &str) -> Vec<PgnGame> {
+    let mut games = vec![];
+    let mut game = PgnGame::new();
+    let mut movetext = String::new();
+
+    for line in text.lines() {
+        let line = line.trim();
+        //Lines starting with % are escaped
+        if line.starts_with('%') {
+            continue;
+        }
+        if line.starts_with('[') {
+            //A tag after the moves starts the next game
+            if !movetext.trim().is_empty() {
+                finish_game(&mut game, &movetext);
+                games.push(std::mem::replace(&mut game, PgnGame::new()));
+                movetext.clear();
+            }
+            if let Some(tag) = parse_tag(line) {
+                game.tags.push(tag);
+            }
+        } else {
+            movetext.push_str(line);
+            movetext.push('\n');
+        }
+    }
+    if !movetext.trim().is_empty() || !game.tags.is_empty() {
+        finish_game(&mut game, &movetext);
+        games.push(game);
+    }
+    games
+}
+
+//[Name "Value"]
+fn parse_tag(line: &str) -> Option<(String, String)> {
+    let inner = line.strip_prefix('[')?.trim_end().strip_suffix(']')?;
+    let (name, value) = inner.split_once(char::is_whitespace)?;
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+    Some((name.to_string(), value.replace("\\\"", "\"")))
+}
+
+fn finish_game(game: &mut PgnGame, movetext: &str) {
+    let mut token = String::new();
+    let mut chars = movetext.chars();
+    let mut variation_depth = 0;
+
+    while let Some(c) = chars.next() {
+        if !c.is_whitespace() && !"{;()".contains(c) {
+            token.push(c);
+            continue;
+        }
+        if variation_depth == 0 {
+            add_token(game, &token);
+        }
+        token.clear();
+        match c {
+            '{' => {
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        break;
+                    }
+                }
+            }
+            ';' => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '(' => variation_depth += 1,
+            ')' => variation_depth = (variation_depth - 1).max(0),
+            _ => {}
+        }
+    }
+    add_token(game, &token);
+}
+
+fn add_token(game: &mut PgnGame, token: &str) {
+    if let Some(result) = GameResult::from_notation(token) {
+        game.result = result;
+        return;
+    }
+    if token.is_empty() || token.starts_with('$') {
+        return;
+    }
+    //Move numbers may be glued to the move, as in 12.e4 or 12...e5
+    let digits = token.len() - token.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let san = if token[digits..].starts_with('.') {
+        token[digits..].trim_start_matches('.')
+    } else {
+        token
+    };
+    //Nothing left, or a move number written apart from its dots as in "12 ..."
+    if !san.chars().all(|c| c.is_ascii_digit() || c == '.') {
+        game.moves.push(san.to_string());
+    }
+}
@@ -2,6 +2,7 @@ use std::fmt::{self, Display};
 
 use bitflags::bitflags;
 
+use super::chess_color::ChessColor;
 use super::InvalidNotationError;
 
 bitflags! {
@@ -41,6 +42,24 @@ impl ChessPiece {
 
         Ok(piece)
     }
+    /// Index of the piece type, from 0 for a pawn up to 5 for a king.
+    #[inline(always)]
+    pub fn get_type_idx(&self) -> usize {
+        (*self & Self::PIECE_BITMASK).bits().trailing_zeros() as usize
+    }
+    #[inline(always)]
+    pub fn has_color(&self, color: ChessColor) -> bool {
+        self.contains(color.to_piece())
+    }
+    pub fn get_color(&self) -> Option<ChessColor> {
+        if self.contains(Self::WHITE) {
+            Some(ChessColor::WHITE)
+        } else if self.contains(Self::BLACK) {
+            Some(ChessColor::BLACK)
+        } else {
+            None
+        }
+    }
 }
 
 impl Display for ChessPiece {
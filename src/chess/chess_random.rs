@@ -0,0 +1,33 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// splitmix64: small, fast and good enough for Zobrist keys and for varying the engine's play.
+/// Not for anything that has to be unpredictable.
+#[derive(Debug, Clone)]
+pub struct ChessRandom(u64);
+
+impl ChessRandom {
+    pub const fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+    /// Seeded from the clock, so every run gets a different sequence.
+    pub fn from_clock() -> Self {
+        Self::new(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|time| time.as_nanos() as u64)
+                .unwrap_or(0),
+        )
+    }
+    //Usable in a const context so the Zobrist keys are baked into the binary
+    pub const fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+    /// A number from 0 up to but excluding `n`, which must not be 0.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
@@ -0,0 +1,85 @@
+use crate::chess::chess_board::CastlingRights;
+use crate::chess::chess_color::ChessColor;
+use crate::chess::chess_piece::ChessPiece;
+use crate::chess::chess_random::ChessRandom;
+use crate::chess::{CHESS_BOARD_SIZE, CHESS_BOARD_WIDTH, CHESS_COLORS};
+
+const PIECE_TYPES: usize = 6;
+
+pub struct ZobristKeys {
+    pieces: [[u64; CHESS_BOARD_SIZE]; PIECE_TYPES * CHESS_COLORS],
+    castling: [[u64; 4]; CHESS_COLORS],
+    en_passant: [u64; CHESS_BOARD_WIDTH],
+    black_to_move: u64,
+}
+
+impl ZobristKeys {
+    const fn new() -> Self {
+        let mut random = ChessRandom::new(0x4A4D434845535321);
+        let mut keys = Self {
+            pieces: [[0; CHESS_BOARD_SIZE]; PIECE_TYPES * CHESS_COLORS],
+            castling: [[0; 4]; CHESS_COLORS],
+            en_passant: [0; CHESS_BOARD_WIDTH],
+            black_to_move: 0,
+        };
+
+        let mut piece = 0;
+        while piece < PIECE_TYPES * CHESS_COLORS {
+            let mut square = 0;
+            while square < CHESS_BOARD_SIZE {
+                keys.pieces[piece][square] = random.next();
+                square += 1;
+            }
+            piece += 1;
+        }
+        let mut color = 0;
+        while color < CHESS_COLORS {
+            //No rights hash to zero so an empty key doesn't need special casing
+            let mut rights = 1;
+            while rights < 4 {
+                keys.castling[color][rights] = random.next();
+                rights += 1;
+            }
+            color += 1;
+        }
+        let mut file = 0;
+        while file < CHESS_BOARD_WIDTH {
+            keys.en_passant[file] = random.next();
+            file += 1;
+        }
+        keys.black_to_move = random.next();
+        keys
+    }
+    #[inline(always)]
+    pub fn piece(&self, piece: ChessPiece, square: usize) -> u64 {
+        match piece.get_color() {
+            Some(color) => {
+                self.pieces[color.get_idx() * PIECE_TYPES + piece.get_type_idx()][square]
+            }
+            None => 0,
+        }
+    }
+    /// The piece key if `piece` is a pawn, 0 for everything else.
+    #[inline(always)]
+    pub fn pawn(&self, piece: ChessPiece, square: usize) -> u64 {
+        if piece.contains(ChessPiece::PAWN) {
+            self.piece(piece, square)
+        } else {
+            0
+        }
+    }
+    #[inline(always)]
+    pub fn castling(&self, color: ChessColor, rights: CastlingRights) -> u64 {
+        self.castling[color.get_idx()][rights.bits() as usize]
+    }
+    #[inline(always)]
+    pub fn en_passant(&self, square: u16) -> u64 {
+        self.en_passant[square as usize % CHESS_BOARD_WIDTH]
+    }
+    #[inline(always)]
+    pub fn black_to_move(&self) -> u64 {
+        self.black_to_move
+    }
+}
+
+pub static ZOBRIST_KEYS: ZobristKeys = ZobristKeys::new();
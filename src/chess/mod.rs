@@ -1,8 +1,12 @@
 pub mod chess_board;
 pub mod chess_color;
 pub mod chess_coordinate;
+pub mod chess_epd;
 pub mod chess_move;
+pub mod chess_pgn;
 pub mod chess_piece;
+pub mod chess_random;
+pub mod chess_zobrist;
 use std::fmt;
 
 pub const CHESS_BOARD_WIDTH: usize = 8;
@@ -11,6 +15,8 @@ pub const CHESS_BOARD_SIZE: usize = CHESS_BOARD_WIDTH * CHESS_BOARD_HEIGHT;
 
 pub const CHESS_COLORS: usize = 2;
 
+pub const START_POSITION_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
 #[derive(Debug)]
 pub struct InvalidFENError;
 impl fmt::Display for InvalidFENError {
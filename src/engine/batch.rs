@@ -0,0 +1,284 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+use crate::chess::chess_board::{ChessBoard, GameOutcome};
+use crate::chess::chess_epd::EpdPosition;
+use crate::chess::chess_move::ChessMove;
+use crate::engine::score::{mated_in, Score};
+use crate::engine::search::{SearchLimits, Searcher};
+
+/// Settings of a batch run.
+#[derive(Debug, Clone)]
+pub struct BatchConfig {
+    pub threads: usize,
+    /// Search depth per position, 0 only evaluates.
+    pub depth: i32,
+    /// Optional node limit per position on top of the depth.
+    pub nodes: Option<u64>,
+    /// Transposition table size of every thread.
+    pub hash_size_mb: usize,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self {
+            threads: 1,
+            depth: 8,
+            nodes: None,
+            hash_size_mb: 16,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameStatus {
+    Ongoing,
+    Checkmate,
+    Stalemate,
+    FiftyMoveRule,
+    InsufficientMaterial,
+}
+
+impl GameStatus {
+    pub fn of(board: &ChessBoard) -> Self {
+        match board.outcome() {
+            None => GameStatus::Ongoing,
+            Some(GameOutcome::Checkmate) => GameStatus::Checkmate,
+            Some(GameOutcome::Stalemate) => GameStatus::Stalemate,
+            Some(GameOutcome::FiftyMoveRule) => GameStatus::FiftyMoveRule,
+            Some(GameOutcome::InsufficientMaterial) => GameStatus::InsufficientMaterial,
+        }
+    }
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GameStatus::Ongoing => "ongoing",
+            GameStatus::Checkmate => "checkmate",
+            GameStatus::Stalemate => "stalemate",
+            GameStatus::FiftyMoveRule => "fifty_move_rule",
+            GameStatus::InsufficientMaterial => "insufficient_material",
+        }
+    }
+}
+
+/// The analysis of one input line.
+#[derive(Debug, Clone)]
+pub struct BatchResult {
+    /// Line number in the input, from 1.
+    pub line: usize,
+    pub input: String,
+    /// None when the line holds no valid FEN or EPD.
+    pub analysis: Option<Analysis>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Analysis {
+    pub fen: String,
+    pub status: GameStatus,
+    /// For the side to move.
+    pub score: Score,
+    pub depth: i32,
+    pub best_move: Option<ChessMove>,
+    pub best_move_san: Option<String>,
+    pub pv: Vec<ChessMove>,
+    pub pv_san: Vec<String>,
+    pub nodes: u64,
+}
+
+impl BatchResult {
+    /// One line of JSON, keys in a fixed order.
+    pub fn to_json(&self) -> String {
+        let Some(analysis) = &self.analysis else {
+            return format!(
+                "{{\"line\":{},\"input\":{},\"error\":\"invalid position\"}}",
+                self.line,
+                json_string(&self.input)
+            );
+        };
+        let (score_type, score_value) = match analysis.score {
+            Score::Centipawns(centipawns) => ("cp", centipawns),
+            Score::Mate(moves) => ("mate", moves),
+        };
+        let optional = |value: &Option<String>| match value {
+            Some(value) => json_string(value),
+            None => "null".to_string(),
+        };
+        let list = |values: Vec<String>| {
+            let values: Vec<String> = values.iter().map(|value| json_string(value)).collect();
+            format!("[{}]", values.join(","))
+        };
+        format!(
+            "{{\"line\":{},\"fen\":{},\"status\":\"{}\",\"score\":{{\"{}\":{}}},\"depth\":{},\
+             \"bestmove\":{},\"bestmove_san\":{},\"pv\":{},\"pv_san\":{},\"nodes\":{}}}",
+            self.line,
+            json_string(&analysis.fen),
+            analysis.status.as_str(),
+            score_type,
+            score_value,
+            analysis.depth,
+            optional(&analysis.best_move.map(|m| m.to_string())),
+            optional(&analysis.best_move_san),
+            list(analysis.pv.iter().map(|m| m.to_string()).collect()),
+            list(analysis.pv_san.clone()),
+            analysis.nodes
+        )
+    }
+}
+
+fn json_string(text: &str) -> String {
+    let mut json = String::with_capacity(text.len() + 2);
+    json.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// Analyses every non blank line of `lines`, a FEN or an EPD, on `config.threads` threads that
+/// search with the settings of `searcher`. Results are written to `out` as JSON lines in input
+/// order, however the threads finish. `on_result` is called after each one is written.
+pub fn run_batch(
+    searcher: &Searcher,
+    lines: &[&str],
+    config: &BatchConfig,
+    out: &mut impl Write,
+    mut on_result: impl FnMut(&BatchResult),
+) -> io::Result<usize> {
+    let inputs: Vec<(usize, &str)> = lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .collect();
+    let next_input = AtomicUsize::new(0);
+    let mut written = 0;
+
+    thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel();
+        for _ in 0..config.threads.max(1) {
+            let sender = sender.clone();
+            let (next_input, inputs) = (&next_input, &inputs);
+            let mut searcher = searcher.fork(config.hash_size_mb);
+            scope.spawn(move || loop {
+                let i = next_input.fetch_add(1, Ordering::Relaxed);
+                let Some(&(line, input)) = inputs.get(i) else {
+                    break;
+                };
+                let result = BatchResult {
+                    line,
+                    input: input.to_string(),
+                    analysis: parse_position(input)
+                        .map(|board| analyse(&mut searcher, &board, config)),
+                };
+                if sender.send((i, result)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        //Results that arrive early wait here until every one before them is written
+        let mut pending = BTreeMap::new();
+        for (i, result) in receiver {
+            pending.insert(i, result);
+            while let Some(result) = pending.remove(&written) {
+                writeln!(out, "{}", result.to_json())?;
+                written += 1;
+                on_result(&result);
+            }
+        }
+        out.flush()
+    })?;
+    Ok(written)
+}
+
+//A FEN with its move counters, else an EPD line
+fn parse_position(line: &str) -> Option<ChessBoard> {
+    let fen: Vec<&str> = line.split_whitespace().take(6).collect();
+    ChessBoard::new_from_fen(&fen.join(" "))
+        .or_else(|_| EpdPosition::parse(line).map(|position| position.board))
+        .ok()
+}
+
+fn analyse(searcher: &mut Searcher, board: &ChessBoard, config: &BatchConfig) -> Analysis {
+    let status = GameStatus::of(board);
+    let mut analysis = Analysis {
+        fen: board.to_fen(),
+        status,
+        score: Score::Centipawns(0),
+        depth: 0,
+        best_move: None,
+        best_move_san: None,
+        pv: vec![],
+        pv_san: vec![],
+        nodes: 0,
+    };
+    match status {
+        GameStatus::Checkmate => analysis.score = Score::new(mated_in(0)),
+        GameStatus::Stalemate => (),
+        _ if config.depth <= 0 => analysis.score = Score::Centipawns(searcher.static_eval(board)),
+        _ => {
+            searcher.clear();
+            let limits = SearchLimits {
+                depth: Some(config.depth),
+                nodes: config.nodes,
+                ..Default::default()
+            };
+            let result = searcher.search(board, &limits, |_| ());
+            let pv = match result.lines.first() {
+                Some(line) => line.pv.clone(),
+                None => result.best_move.into_iter().collect(),
+            };
+            analysis.score = Score::new(result.score);
+            analysis.depth = result.depth;
+            analysis.best_move = result.best_move;
+            analysis.best_move_san = result.best_move.map(|m| board.to_san(&m));
+            analysis.pv_san = board.to_san_line(&pv);
+            analysis.pv = pv;
+            analysis.nodes = result.nodes;
+        }
+    }
+    analysis
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_malformed_lines() {
+        let lines = [
+            "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1",
+            "8/8/8/8/8/8/8/8/p w - - 0 1",
+            "rnbqkbnr/ppppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - bm Rd8#;",
+        ];
+        let config = BatchConfig {
+            threads: 2,
+            depth: 2,
+            hash_size_mb: 1,
+            ..Default::default()
+        };
+        let mut out = vec![];
+        let written = run_batch(&Searcher::new(1), &lines, &config, &mut out, |_| ()).unwrap();
+        assert_eq!(written, 4);
+
+        let out = String::from_utf8(out).unwrap();
+        let results: Vec<&str> = out.lines().collect();
+        assert!(results[0].contains("\"bestmove\":\"d1d8\""));
+        assert!(results[1].contains("\"error\":\"invalid position\""));
+        assert!(results[2].contains("\"error\":\"invalid position\""));
+        assert!(results[3].contains("\"score\":{\"mate\":1}"));
+    }
+}
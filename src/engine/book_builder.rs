@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+
+use crate::chess::chess_color::ChessColor;
+use crate::chess::chess_pgn::{GameResult, PgnGame};
+use crate::engine::opening_book::{BookEntry, OpeningBook};
+use crate::engine::polyglot::{encode_move, polyglot_key};
+
+/// How a move fared in the games it was played in, from the point of view of its player.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MoveStats {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl MoveStats {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+    //Polyglot's usual weighting: two points for a win, one for a draw
+    fn score(&self) -> u32 {
+        2 * self.wins + self.draws
+    }
+}
+
+/// Collects opening statistics from PGN games and turns them into a Polyglot book.
+pub struct BookBuilder {
+    /// Moves after this many plies aren't recorded.
+    pub max_ply: usize,
+    /// Moves played in fewer games are left out of the book.
+    pub min_games: u32,
+    /// Moves of players rated below this are ignored, as are those of unrated players when it
+    /// isn't 0.
+    pub min_rating: u32,
+    stats: HashMap<(u64, u16), MoveStats>,
+    games: usize,
+}
+
+impl BookBuilder {
+    pub fn new(max_ply: usize, min_games: u32, min_rating: u32) -> Self {
+        Self {
+            max_ply,
+            min_games,
+            min_rating,
+            stats: HashMap::new(),
+            games: 0,
+        }
+    }
+    /// Number of games that contributed moves.
+    pub fn games(&self) -> usize {
+        self.games
+    }
+    /// Number of distinct position and move pairs seen so far.
+    pub fn move_count(&self) -> usize {
+        self.stats.len()
+    }
+    /// Replays the game and counts its moves, returns false if nothing was used because the
+    /// result is unknown, no player passes the rating filter or the game doesn't start right.
+    pub fn add_game(&mut self, game: &PgnGame) -> bool {
+        if game.result == GameResult::Unknown {
+            return false;
+        }
+        let rated = |color| game.rating(color).unwrap_or(0) >= self.min_rating;
+        let counted = [rated(ChessColor::WHITE), rated(ChessColor::BLACK)];
+        if !counted.contains(&true) {
+            return false;
+        }
+        let Some(mut board) = game.start_board() else {
+            return false;
+        };
+
+        let mut used = false;
+        for san in game.moves.iter().take(self.max_ply) {
+            let Some(chess_move) = board.find_san_move(san) else {
+                break;
+            };
+            let color = board.color_to_move;
+            if counted[color.get_idx()] {
+                let stats = self
+                    .stats
+                    .entry((polyglot_key(&board), encode_move(chess_move)))
+                    .or_default();
+                match (game.result, color) {
+                    (GameResult::Draw, _) => stats.draws += 1,
+                    (GameResult::WhiteWins, ChessColor::WHITE)
+                    | (GameResult::BlackWins, ChessColor::BLACK) => stats.wins += 1,
+                    _ => stats.losses += 1,
+                }
+                used = true;
+            }
+            board.make_move_unchecked(&chess_move);
+        }
+        self.games += used as usize;
+        used
+    }
+    /// The book of every move played in at least `min_games` games. Weights are scaled per
+    /// position so the best move still fits into 16 bits.
+    pub fn build(&self) -> OpeningBook {
+        let mut positions: HashMap<u64, Vec<(u16, MoveStats)>> = HashMap::new();
+        for (&(key, polyglot_move), &stats) in &self.stats {
+            if stats.games() >= self.min_games {
+                positions
+                    .entry(key)
+                    .or_default()
+                    .push((polyglot_move, stats));
+            }
+        }
+
+        let mut entries = vec![];
+        for (key, moves) in positions {
+            let best_score = moves
+                .iter()
+                .map(|(_, stats)| stats.score())
+                .max()
+                .unwrap_or(0);
+            let scale = (u16::MAX as f64 / best_score as f64).min(1.0);
+            for (polyglot_move, stats) in moves {
+                entries.push(BookEntry {
+                    key,
+                    polyglot_move,
+                    weight: (stats.score() as f64 * scale) as u16,
+                    learn: 0,
+                });
+            }
+        }
+        //Same key order on every run, the best move first
+        entries.sort_by_key(|entry| (entry.key, u16::MAX - entry.weight, entry.polyglot_move));
+        OpeningBook::from_entries(entries)
+    }
+}
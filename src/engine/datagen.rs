@@ -0,0 +1,221 @@
+use std::fmt::{self, Display};
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+use crate::chess::chess_board::{ChessBoard, GameOutcome};
+use crate::chess::chess_color::ChessColor;
+use crate::chess::chess_move::ChessMove;
+use crate::chess::chess_pgn::GameResult;
+use crate::chess::chess_random::ChessRandom;
+use crate::chess::START_POSITION_FEN;
+use crate::engine::score::is_mate_score;
+use crate::engine::search::{SearchLimits, Searcher};
+use crate::engine::see::see;
+
+//Games still going after this many plies are called a draw
+const MAX_GAME_PLIES: usize = 400;
+
+/// Settings of a self-play run.
+#[derive(Debug, Clone)]
+pub struct DatagenConfig {
+    pub games: usize,
+    pub threads: usize,
+    /// Nodes searched per move, used together with `depth` when both are set.
+    pub nodes: Option<u64>,
+    pub depth: Option<i32>,
+    /// Random moves played at the start of every game so no two games are alike.
+    pub random_plies: usize,
+    /// Transposition table size of every thread.
+    pub hash_size_mb: usize,
+    pub seed: u64,
+}
+
+impl Default for DatagenConfig {
+    fn default() -> Self {
+        Self {
+            games: 100,
+            threads: 1,
+            nodes: Some(5000),
+            depth: None,
+            random_plies: 8,
+            hash_size_mb: 16,
+            seed: ChessRandom::from_clock().next(),
+        }
+    }
+}
+
+/// A recorded position, written as `fen | score | result` with the score in centipawns and the
+/// result as 1.0, 0.5 or 0.0, both for white. The tuner reads this format as it is.
+#[derive(Debug, Clone)]
+pub struct DataPosition {
+    pub fen: String,
+    pub score: i32,
+    pub result: f64,
+}
+
+impl Display for DataPosition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} | {} | {:.1}", self.fen, self.score, self.result)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DatagenStats {
+    pub games: usize,
+    pub positions: usize,
+    pub white_wins: usize,
+    pub black_wins: usize,
+    pub draws: usize,
+}
+
+/// Plays `config.games` games on `config.threads` threads and writes the quiet positions of
+/// every game to `out` as soon as it ends. `on_game` is called after each game.
+pub fn generate(
+    config: &DatagenConfig,
+    out: &mut impl Write,
+    mut on_game: impl FnMut(&DatagenStats),
+) -> io::Result<DatagenStats> {
+    let next_game = AtomicUsize::new(0);
+    let mut stats = DatagenStats::default();
+
+    thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel();
+        for id in 0..config.threads.max(1) {
+            let sender = sender.clone();
+            let next_game = &next_game;
+            scope.spawn(move || {
+                let mut searcher = Searcher::new(config.hash_size_mb);
+                let mut random =
+                    ChessRandom::new(config.seed ^ (id as u64).wrapping_mul(0x9E3779B97F4A7C15));
+                while next_game.fetch_add(1, Ordering::Relaxed) < config.games {
+                    let game = play_game(&mut searcher, config, &mut random);
+                    if sender.send(game).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        //The loop below ends once every thread dropped its sender
+        drop(sender);
+
+        for (positions, result) in receiver {
+            for position in &positions {
+                writeln!(out, "{}", position)?;
+            }
+            stats.games += 1;
+            stats.positions += positions.len();
+            match result {
+                GameResult::WhiteWins => stats.white_wins += 1,
+                GameResult::BlackWins => stats.black_wins += 1,
+                _ => stats.draws += 1,
+            }
+            on_game(&stats);
+        }
+        out.flush()
+    })?;
+    Ok(stats)
+}
+
+fn play_game(
+    searcher: &mut Searcher,
+    config: &DatagenConfig,
+    random: &mut ChessRandom,
+) -> (Vec<DataPosition>, GameResult) {
+    let limits = SearchLimits {
+        nodes: config.nodes,
+        depth: config.depth,
+        ..Default::default()
+    };
+    let (mut board, mut positions) = loop {
+        if let Some(board) = random_opening(config.random_plies, random) {
+            break (board, vec![]);
+        }
+    };
+    searcher.clear();
+
+    let result = loop {
+        if let Some(result) = game_over(&board) {
+            break result;
+        }
+        let search = searcher.search(&board, &limits, |_| ());
+        let Some(best_move) = search.best_move else {
+            break GameResult::Draw;
+        };
+        //A found mate will be played out, the game is decided
+        if is_mate_score(search.score) {
+            let winner = if search.score > 0 {
+                board.color_to_move
+            } else {
+                board.color_to_move.opposite()
+            };
+            break if winner == ChessColor::WHITE {
+                GameResult::WhiteWins
+            } else {
+                GameResult::BlackWins
+            };
+        }
+        if is_quiet(&board, &best_move) {
+            let score = if board.color_to_move == ChessColor::WHITE {
+                search.score
+            } else {
+                -search.score
+            };
+            positions.push(DataPosition {
+                fen: board.to_fen(),
+                score,
+                result: 0.0,
+            });
+        }
+        board.make_move_unchecked(&best_move);
+    };
+
+    let white_result = match result {
+        GameResult::WhiteWins => 1.0,
+        GameResult::BlackWins => 0.0,
+        _ => 0.5,
+    };
+    for position in positions.iter_mut() {
+        position.result = white_result;
+    }
+    (positions, result)
+}
+
+//None when the random moves happen to end the game
+fn random_opening(plies: usize, random: &mut ChessRandom) -> Option<ChessBoard> {
+    let mut board = ChessBoard::new_from_fen(START_POSITION_FEN).unwrap();
+    for _ in 0..plies {
+        let moves = board.generate_legal_moves();
+        if moves.is_empty() {
+            return None;
+        }
+        board.make_move_unchecked(&moves[random.below(moves.len())]);
+    }
+    (!board.generate_legal_moves().is_empty()).then_some(board)
+}
+
+fn game_over(board: &ChessBoard) -> Option<GameResult> {
+    match board.outcome() {
+        Some(GameOutcome::Checkmate) if board.color_to_move == ChessColor::WHITE => {
+            Some(GameResult::BlackWins)
+        }
+        Some(GameOutcome::Checkmate) => Some(GameResult::WhiteWins),
+        Some(_) => Some(GameResult::Draw),
+        None => {
+            let long_game = (board.fullmove_number as usize) * 2 > MAX_GAME_PLIES;
+            (board.is_repetition() || long_game).then_some(GameResult::Draw)
+        }
+    }
+}
+
+//Positions whose score depends on an exchange in progress make poor training data
+fn is_quiet(board: &ChessBoard, best_move: &ChessMove) -> bool {
+    if board.in_check() || board.is_capture(best_move) || best_move.get_promotion().is_some() {
+        return false;
+    }
+    !board
+        .generate_legal_moves()
+        .iter()
+        .any(|chess_move| board.is_capture(chess_move) && see(board, *chess_move) > 0)
+}
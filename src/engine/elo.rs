@@ -0,0 +1,185 @@
+use std::fmt::{self, Display};
+
+//Two-sided 95% confidence
+const CONFIDENCE_Z: f64 = 1.959964;
+
+/// Expected score against an opponent `elo` points weaker.
+pub fn elo_to_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+pub fn score_to_elo(score: f64) -> f64 {
+    400.0 * (score / (1.0 - score)).log10()
+}
+
+/// The results of a match from the point of view of its first engine.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MatchScore {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl MatchScore {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+    /// Points per game, from 0 to 1.
+    pub fn score(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games().max(1) as f64
+    }
+    //Variance of the result of a single game
+    fn variance(&self) -> f64 {
+        let games = self.games().max(1) as f64;
+        let score = self.score();
+        (self.wins as f64 * (1.0 - score).powi(2)
+            + self.draws as f64 * (0.5 - score).powi(2)
+            + self.losses as f64 * score.powi(2))
+            / games
+    }
+    /// The Elo difference and the half width of its 95% confidence interval. None until both
+    /// engines have scored, as a perfect score has no finite Elo.
+    pub fn elo(&self) -> Option<(f64, f64)> {
+        let score = self.score();
+        if self.games() == 0 || score <= 0.0 || score >= 1.0 {
+            return None;
+        }
+        let margin = CONFIDENCE_Z * (self.variance() / self.games() as f64).sqrt();
+        let low = (score - margin).max(f64::EPSILON);
+        let high = (score + margin).min(1.0 - f64::EPSILON);
+        Some((
+            score_to_elo(score),
+            (score_to_elo(high) - score_to_elo(low)) / 2.0,
+        ))
+    }
+}
+
+impl Display for MatchScore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "+{} ={} -{} ({:.1}%)",
+            self.wins,
+            self.draws,
+            self.losses,
+            100.0 * self.score()
+        )?;
+        match self.elo() {
+            Some((elo, error)) => write!(f, "  Elo {:+.1} ± {:.1}", elo, error),
+            None => write!(f, "  Elo n/a"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SprtVerdict {
+    /// The change is no better than `elo0`.
+    H0Accepted,
+    /// The change is at least `elo1` better.
+    H1Accepted,
+    Continue,
+}
+
+/// A sequential probability ratio test of `elo0` against `elo1`, stopping a match as soon as
+/// the results are clear enough for the given error rates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    /// Chance of accepting H1 when H0 holds.
+    pub alpha: f64,
+    /// Chance of accepting H0 when H1 holds.
+    pub beta: f64,
+}
+
+impl Sprt {
+    pub fn new(elo0: f64, elo1: f64) -> Self {
+        Self {
+            elo0,
+            elo1,
+            alpha: 0.05,
+            beta: 0.05,
+        }
+    }
+    /// The log-likelihood ratios at which H0 and H1 are accepted.
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    }
+    /// Log-likelihood ratio of H1 over H0, in the usual normal approximation of the game
+    /// results (the generalised SPRT).
+    pub fn llr(&self, score: &MatchScore) -> f64 {
+        let variance = score.variance();
+        if score.games() == 0 || variance <= 0.0 {
+            return 0.0;
+        }
+        let score0 = elo_to_score(self.elo0);
+        let score1 = elo_to_score(self.elo1);
+        score.games() as f64 * (score1 - score0) * (2.0 * score.score() - score0 - score1)
+            / (2.0 * variance)
+    }
+    pub fn verdict(&self, score: &MatchScore) -> SprtVerdict {
+        let llr = self.llr(score);
+        let (lower, upper) = self.bounds();
+        if llr >= upper {
+            SprtVerdict::H1Accepted
+        } else if llr <= lower {
+            SprtVerdict::H0Accepted
+        } else {
+            SprtVerdict::Continue
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //55% over 1000 games: +400 =300 -300
+    const SCORE: MatchScore = MatchScore {
+        wins: 400,
+        draws: 300,
+        losses: 300,
+    };
+
+    #[test]
+    fn elo_of_a_known_score() {
+        let (elo, error) = SCORE.elo().unwrap();
+        assert!((elo - 34.86).abs() < 0.01, "{}", elo);
+        assert!((error - 18.08).abs() < 0.01, "{}", error);
+        assert_eq!(MatchScore::default().elo(), None);
+        assert_eq!(
+            MatchScore {
+                wins: 3,
+                draws: 0,
+                losses: 0
+            }
+            .elo(),
+            None
+        );
+    }
+
+    #[test]
+    fn sprt_bounds_for_five_percent_errors() {
+        let (lower, upper) = Sprt::new(0.0, 5.0).bounds();
+        assert!((lower + 2.944).abs() < 0.001, "{}", lower);
+        assert!((upper - 2.944).abs() < 0.001, "{}", upper);
+    }
+
+    #[test]
+    fn llr_of_a_known_score() {
+        let sprt = Sprt::new(0.0, 5.0);
+        let llr = sprt.llr(&SCORE);
+        assert!((llr - 1.935).abs() < 0.001, "{}", llr);
+        assert_eq!(sprt.verdict(&SCORE), SprtVerdict::Continue);
+        //The same score over four times the games is conclusive
+        let longer = MatchScore {
+            wins: 1600,
+            draws: 1200,
+            losses: 1200,
+        };
+        assert_eq!(sprt.verdict(&longer), SprtVerdict::H1Accepted);
+    }
+}
@@ -0,0 +1,574 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::chess::chess_board::{CastlingRights, ChessBoard};
+use crate::chess::chess_color::ChessColor;
+use crate::chess::chess_move::ChessMove;
+use crate::chess::chess_piece::ChessPiece;
+use crate::chess::{CHESS_BOARD_SIZE, CHESS_COLORS};
+
+//Distance-to-mate tables for small endings, built here by retrograde analysis instead of being
+//downloaded. Squares are numbered from a1 = 0 to h8 = 63, the board index XOR 56, so ranks and
+//files can be mirrored with plain bit operations.
+
+const MAGIC: [u8; 4] = *b"JMTB";
+const VERSION: u8 = 1;
+pub const TABLE_EXTENSION: &str = "jmtb";
+pub const MAX_TABLE_PIECES: usize = 4;
+
+// In table name order
+const PIECE_TYPES: [(char, ChessPiece); 6] = [
+    ('K', ChessPiece::KING),
+    ('Q', ChessPiece::QUEEN),
+    ('R', ChessPiece::ROOK),
+    ('B', ChessPiece::BISHOP),
+    ('N', ChessPiece::KNIGHT),
+    ('P', ChessPiece::PAWN),
+];
+
+/// Distance to mate in plies for the side to move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dtm {
+    Win(u32),
+    Loss(u32),
+    Draw,
+}
+
+impl Dtm {
+    //Stored as plies + 1 with 0 for a draw, the parity of the plies tells wins from losses
+    fn from_byte(byte: u8) -> Self {
+        match byte {
+            0 => Dtm::Draw,
+            _ if byte.is_multiple_of(2) => Dtm::Win(byte as u32 - 1),
+            _ => Dtm::Loss(byte as u32 - 1),
+        }
+    }
+    /// Moves to mate, negative when the side to move gets mated, None for a draw.
+    pub fn moves(&self) -> Option<i32> {
+        match self {
+            Dtm::Win(plies) => Some((*plies as i32 + 1) / 2),
+            Dtm::Loss(plies) => Some(-(*plies as i32) / 2),
+            Dtm::Draw => None,
+        }
+    }
+}
+
+fn file_of(sq: usize) -> usize {
+    sq & 7
+}
+fn rank_of(sq: usize) -> usize {
+    sq >> 3
+}
+
+//The pieces of a material signature like KRvK, the first side playing white
+fn parse_material(material: &str) -> Option<Vec<ChessPiece>> {
+    let (white, black) = material.split_once('v')?;
+    let mut pieces = vec![];
+    for (side, color) in [(white, ChessPiece::WHITE), (black, ChessPiece::BLACK)] {
+        if !side.starts_with('K') || side[1..].contains('K') {
+            return None;
+        }
+        for code in side.chars() {
+            let (_code, piece_type) = PIECE_TYPES.iter().find(|(c, _)| *c == code)?;
+            pieces.push(*piece_type | color);
+        }
+    }
+    (pieces.len() <= MAX_TABLE_PIECES).then_some(pieces)
+}
+
+fn material_of(pieces: &[ChessPiece]) -> String {
+    let side = |color: ChessPiece| -> String {
+        PIECE_TYPES
+            .iter()
+            .flat_map(|&(code, piece_type)| {
+                let count = pieces.iter().filter(|&&p| p == piece_type | color).count();
+                std::iter::repeat_n(code, count)
+            })
+            .collect()
+    };
+    format!("{}v{}", side(ChessPiece::WHITE), side(ChessPiece::BLACK))
+}
+
+//Every material one capture and/or one promotion away
+fn successor_materials(pieces: &[ChessPiece]) -> Vec<String> {
+    let mut after_capture = vec![pieces.to_vec()];
+    for (i, piece) in pieces.iter().enumerate() {
+        if !piece.contains(ChessPiece::KING) {
+            let mut remaining = pieces.to_vec();
+            remaining.remove(i);
+            after_capture.push(remaining);
+        }
+    }
+    let mut materials = vec![];
+    for material in &after_capture {
+        if material.len() < pieces.len() {
+            materials.push(material_of(material));
+        }
+        for (i, piece) in material.iter().enumerate() {
+            if !piece.contains(ChessPiece::PAWN) {
+                continue;
+            }
+            let color = *piece & ChessPiece::COLOR_BITMASK;
+            for promotion in [
+                ChessPiece::QUEEN,
+                ChessPiece::ROOK,
+                ChessPiece::BISHOP,
+                ChessPiece::KNIGHT,
+            ] {
+                let mut promoted = material.clone();
+                promoted[i] = promotion | color;
+                materials.push(material_of(&promoted));
+            }
+        }
+    }
+    materials.sort();
+    materials.dedup();
+    materials
+        .into_iter()
+        .filter(|material| material.len() > "KvK".len())
+        .collect()
+}
+
+/// A distance-to-mate table for one material, e.g. KRvK. Both sides to move are stored, one
+/// byte per position, with the board mirrored so the first king stays in one corner region.
+/// En passant rights are not part of the positions, so there are no tables with pawns on both
+/// sides.
+pub struct EndgameTable {
+    material: String,
+    pieces: Vec<ChessPiece>,
+    has_pawns: bool,
+    king_squares: Vec<usize>,
+    king_square_idx: [usize; CHESS_BOARD_SIZE],
+    data: Vec<u8>,
+}
+
+impl EndgameTable {
+    fn new(material: &str) -> Option<Self> {
+        let pieces = parse_material(material)?;
+        let has_pawns = pieces.iter().any(|piece| piece.contains(ChessPiece::PAWN));
+        //A double push next to an enemy pawn would need the en passant square in the index
+        let both_sides_have_pawns = [ChessPiece::WHITE, ChessPiece::BLACK]
+            .iter()
+            .all(|&color| pieces.contains(&(ChessPiece::PAWN | color)));
+        if both_sides_have_pawns {
+            return None;
+        }
+        //Without pawns the board can be mirrored in 8 ways, leaving the a1-d1-d4 triangle for
+        //the first king, pawns only allow mirroring the files
+        let king_squares: Vec<usize> = (0..CHESS_BOARD_SIZE)
+            .filter(|&sq| {
+                file_of(sq) <= 3 && (has_pawns || (rank_of(sq) <= 3 && rank_of(sq) <= file_of(sq)))
+            })
+            .collect();
+        let mut king_square_idx = [usize::MAX; CHESS_BOARD_SIZE];
+        for (idx, &sq) in king_squares.iter().enumerate() {
+            king_square_idx[sq] = idx;
+        }
+        let size =
+            CHESS_COLORS * king_squares.len() * CHESS_BOARD_SIZE.pow(pieces.len() as u32 - 1);
+        Some(Self {
+            material: material.to_string(),
+            pieces,
+            has_pawns,
+            king_squares,
+            king_square_idx,
+            data: vec![0; size],
+        })
+    }
+    pub fn material(&self) -> &str {
+        &self.material
+    }
+    /// Number of positions, legal or not.
+    pub fn size(&self) -> usize {
+        self.data.len()
+    }
+    /// The longest forced mate in the table, in plies.
+    pub fn longest_mate(&self) -> u32 {
+        self.data
+            .iter()
+            .map(|&byte| match Dtm::from_byte(byte) {
+                Dtm::Win(plies) => plies,
+                _ => 0,
+            })
+            .max()
+            .unwrap_or(0)
+    }
+    fn canonicalize(&self, squares: &mut [usize]) {
+        if file_of(squares[0]) > 3 {
+            squares.iter_mut().for_each(|sq| *sq ^= 7);
+        }
+        if self.has_pawns {
+            return;
+        }
+        if rank_of(squares[0]) > 3 {
+            squares.iter_mut().for_each(|sq| *sq ^= 56);
+        }
+        if rank_of(squares[0]) > file_of(squares[0]) {
+            squares
+                .iter_mut()
+                .for_each(|sq| *sq = ((*sq >> 3) | (*sq << 3)) & 63);
+        }
+    }
+    fn index(&self, white_to_move: bool, squares: &[usize]) -> usize {
+        let mut idx =
+            (!white_to_move as usize) * self.king_squares.len() + self.king_square_idx[squares[0]];
+        for &sq in &squares[1..] {
+            idx = idx * CHESS_BOARD_SIZE + sq;
+        }
+        idx
+    }
+    fn decode(&self, mut idx: usize, squares: &mut [usize]) -> bool {
+        for i in (1..squares.len()).rev() {
+            squares[i] = idx % CHESS_BOARD_SIZE;
+            idx /= CHESS_BOARD_SIZE;
+        }
+        squares[0] = self.king_squares[idx % self.king_squares.len()];
+        idx / self.king_squares.len() == 0
+    }
+    //Squares of the table's pieces on the board, in table order. With `flip` the table's
+    //white pieces are the board's black ones and the board is seen from the other side.
+    fn squares_on(&self, board: &ChessBoard, flip: bool) -> Option<Vec<usize>> {
+        let mut squares = Vec::with_capacity(self.pieces.len());
+        for (i, &piece) in self.pieces.iter().enumerate() {
+            let board_piece = if flip {
+                (piece & ChessPiece::PIECE_BITMASK)
+                    | if piece.contains(ChessPiece::WHITE) {
+                        ChessPiece::BLACK
+                    } else {
+                        ChessPiece::WHITE
+                    }
+            } else {
+                piece
+            };
+            //Several pieces of one kind take their squares in board order
+            let nth = self.pieces[..i].iter().filter(|&&p| p == piece).count();
+            let idx = (0..CHESS_BOARD_SIZE)
+                .filter(|&idx| board.layout[idx] == board_piece)
+                .nth(nth)?;
+            squares.push(if flip { idx } else { idx ^ 56 });
+        }
+        Some(squares)
+    }
+    /// DTM of the position, None if the board doesn't have this table's material.
+    pub fn probe(&self, board: &ChessBoard) -> Option<Dtm> {
+        let flip = if board.material_signature(ChessColor::WHITE) == self.material {
+            false
+        } else if board.material_signature(ChessColor::BLACK) == self.material {
+            true
+        } else {
+            return None;
+        };
+        let mut squares = self.squares_on(board, flip)?;
+        self.canonicalize(&mut squares);
+        let white_to_move = (board.color_to_move == ChessColor::WHITE) != flip;
+        Some(Dtm::from_byte(
+            self.data[self.index(white_to_move, &squares)],
+        ))
+    }
+    //Sets up the position of a table index, None if it is illegal
+    fn setup(&self, board: &mut ChessBoard, idx: usize, squares: &mut [usize]) -> Option<()> {
+        let white_to_move = self.decode(idx, squares);
+        board.layout = [ChessPiece::empty(); CHESS_BOARD_SIZE];
+        for (&sq, &piece) in squares.iter().zip(&self.pieces) {
+            let on_last_rank = rank_of(sq) == 0 || rank_of(sq) == 7;
+            if !board.layout[sq ^ 56].is_empty()
+                || (piece.contains(ChessPiece::PAWN) && on_last_rank)
+            {
+                return None;
+            }
+            board.layout[sq ^ 56] = piece;
+        }
+        board.color_to_move = if white_to_move {
+            ChessColor::WHITE
+        } else {
+            ChessColor::BLACK
+        };
+        board.castling_rights = [CastlingRights::empty(); CHESS_COLORS];
+        board.en_passant_target = None;
+        board.halfmove_clock = 0;
+
+        //The side that just moved can't be left in check
+        let waiting_king = board.king_square(board.color_to_move.opposite())?;
+        if board.is_square_attacked(&board.layout, waiting_king, board.color_to_move) {
+            return None;
+        }
+        Some(())
+    }
+}
+
+//A move either stays in the table or leaves it with a known result
+enum Successor {
+    Position(usize),
+    Known(Dtm),
+}
+
+/// A set of endgame tables, generated here or loaded from disk, probed by material.
+#[derive(Default, Clone)]
+pub struct EndgameTables {
+    tables: HashMap<String, Arc<EndgameTable>>,
+}
+
+impl EndgameTables {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn len(&self) -> usize {
+        self.tables.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+    pub fn get(&self, material: &str) -> Option<&EndgameTable> {
+        self.tables.get(material).map(|table| table.as_ref())
+    }
+    pub fn materials(&self) -> Vec<&str> {
+        let mut materials: Vec<&str> = self.tables.keys().map(|m| m.as_str()).collect();
+        materials.sort();
+        materials
+    }
+    /// DTM of the position if a table covers it. Bare kings are a draw.
+    pub fn probe(&self, board: &ChessBoard) -> Option<Dtm> {
+        if board.piece_count() > MAX_TABLE_PIECES {
+            return None;
+        }
+        for first in [ChessColor::WHITE, ChessColor::BLACK] {
+            let material = board.material_signature(first);
+            if material == "KvK" {
+                return Some(Dtm::Draw);
+            }
+            if let Some(table) = self.tables.get(&material) {
+                return table.probe(board);
+            }
+        }
+        None
+    }
+    /// Builds the table for `material`, e.g. KRvK, and first every table it depends on
+    /// through captures and promotions. Returns false for materials that can't be generated.
+    pub fn generate(&mut self, material: &str) -> bool {
+        if self.tables.contains_key(material) {
+            return true;
+        }
+        let Some(mut table) = EndgameTable::new(material) else {
+            return false;
+        };
+        for successor in successor_materials(&table.pieces) {
+            if !self.generate(&successor) {
+                return false;
+            }
+        }
+        self.retrograde(&mut table);
+        self.tables.insert(material.to_string(), Arc::new(table));
+        true
+    }
+    //Resolves the table ply by ply: a position is won in n if some move reaches a position
+    //lost in n - 1, and lost in n once every move reaches a position won in fewer plies.
+    //Whatever is left at the end is a draw.
+    fn retrograde(&self, table: &mut EndgameTable) {
+        let size = table.size();
+        let mut board = ChessBoard::new();
+        let mut squares = vec![0; table.pieces.len()];
+
+        //Positions without moves are resolved right away. Results reached through captures and
+        //promotions can still resolve positions after a quiet pass, until the longest of them
+        //has been passed.
+        let mut resolved = vec![false; size];
+        let mut values = vec![0u8; size];
+        let mut longest_known = 0;
+        for idx in 0..size {
+            if table.setup(&mut board, idx, &mut squares).is_none() {
+                resolved[idx] = true;
+                continue;
+            }
+            let moves = board.generate_legal_moves();
+            if moves.is_empty() {
+                resolved[idx] = true;
+                if board.in_check() {
+                    values[idx] = 1;
+                }
+                continue;
+            }
+            for chess_move in &moves {
+                if let Successor::Known(Dtm::Win(plies) | Dtm::Loss(plies)) =
+                    self.successor(table, &mut board, chess_move)
+                {
+                    longest_known = longest_known.max(plies);
+                }
+            }
+        }
+
+        let mut plies = 1;
+        let mut quiet_passes = 0;
+        //Two passes without news means neither wins nor losses can grow any longer
+        while quiet_passes < 2 && plies < u8::MAX as u32 - 1 {
+            let mut newly_resolved = vec![];
+            for idx in 0..size {
+                if resolved[idx] {
+                    continue;
+                }
+                //Successors are found again on every pass, storing them takes far more memory
+                //than the table itself
+                table.setup(&mut board, idx, &mut squares);
+                let mut moves =
+                    board.generate_legal_moves().into_iter().map(|chess_move| {
+                        match self.successor(table, &mut board, &chess_move) {
+                            Successor::Position(idx) if resolved[idx] => {
+                                Some(Dtm::from_byte(values[idx]))
+                            }
+                            Successor::Position(_) => None,
+                            Successor::Known(dtm) => Some(dtm),
+                        }
+                    });
+                let resolves = if plies % 2 == 1 {
+                    moves.any(|dtm| dtm == Some(Dtm::Loss(plies - 1)))
+                } else {
+                    moves.all(|dtm| matches!(dtm, Some(Dtm::Win(won)) if won < plies))
+                };
+                if resolves {
+                    newly_resolved.push(idx);
+                }
+            }
+
+            //Resolved only after the pass so every position found in it gets the same distance
+            for &idx in &newly_resolved {
+                resolved[idx] = true;
+                values[idx] = plies as u8 + 1;
+            }
+            if newly_resolved.is_empty() && plies > longest_known {
+                quiet_passes += 1;
+            } else {
+                quiet_passes = 0;
+            }
+            plies += 1;
+        }
+
+        table.data = values
+            .iter()
+            .zip(&resolved)
+            .map(|(&value, &resolved)| if resolved { value } else { 0 })
+            .collect();
+    }
+    //Where a move from the position on `board` leads
+    fn successor(
+        &self,
+        table: &EndgameTable,
+        board: &mut ChessBoard,
+        chess_move: &ChessMove,
+    ) -> Successor {
+        board.make_move_unchecked(chess_move);
+        let successor = match table.squares_on(board, false) {
+            Some(mut squares) if board.piece_count() == table.pieces.len() => {
+                table.canonicalize(&mut squares);
+                let white_to_move = board.color_to_move == ChessColor::WHITE;
+                Successor::Position(table.index(white_to_move, &squares))
+            }
+            _ => Successor::Known(self.probe(board).unwrap_or(Dtm::Draw)),
+        };
+        board.unmake_move(chess_move);
+        successor
+    }
+    /// Writes every table to `directory` as `<material>.jmtb`.
+    pub fn save(&self, directory: &Path) -> io::Result<()> {
+        fs::create_dir_all(directory)?;
+        for table in self.tables.values() {
+            let mut bytes = Vec::with_capacity(table.data.len() + 16);
+            bytes.extend_from_slice(&MAGIC);
+            bytes.push(VERSION);
+            bytes.push(table.material.len() as u8);
+            bytes.extend_from_slice(table.material.as_bytes());
+            bytes.extend_from_slice(&(table.data.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&table.data);
+            let file_name = format!("{}.{}", table.material, TABLE_EXTENSION);
+            fs::write(directory.join(file_name), bytes)?;
+        }
+        Ok(())
+    }
+    /// Reads every `.jmtb` table in `directory`.
+    pub fn load(directory: &Path) -> io::Result<Self> {
+        let mut tables = Self::new();
+        for entry in fs::read_dir(directory)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(TABLE_EXTENSION) {
+                continue;
+            }
+            let table = Self::read_table(&fs::read(&path)?).ok_or_else(|| {
+                io::Error::new(ErrorKind::InvalidData, path.display().to_string())
+            })?;
+            tables
+                .tables
+                .insert(table.material.clone(), Arc::new(table));
+        }
+        Ok(tables)
+    }
+    fn read_table(bytes: &[u8]) -> Option<EndgameTable> {
+        if bytes.len() < 6 || bytes[..4] != MAGIC || bytes[4] != VERSION {
+            return None;
+        }
+        let material_end = 6 + bytes[5] as usize;
+        let material = std::str::from_utf8(bytes.get(6..material_end)?).ok()?;
+        let size = u32::from_le_bytes(bytes.get(material_end..material_end + 4)?.try_into().ok()?);
+        let mut table = EndgameTable::new(material)?;
+        let data = bytes.get(material_end + 4..)?;
+        if data.len() != size as usize || data.len() != table.size() {
+            return None;
+        }
+        table.data = data.to_vec();
+        Some(table)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn longest_mate_moves(tables: &EndgameTables, material: &str) -> Option<i32> {
+        Dtm::Win(tables.get(material)?.longest_mate()).moves()
+    }
+
+    #[test]
+    fn generates_krvk() {
+        let mut tables = EndgameTables::new();
+        assert!(tables.generate("KRvK"));
+        assert_eq!(longest_mate_moves(&tables, "KRvK"), Some(16));
+        //Mated in 16, the rook can't be taken
+        let board = ChessBoard::new_from_fen("8/8/8/8/8/2k5/1R6/K7 b - - 0 1").unwrap();
+        assert_eq!(tables.probe(&board), Some(Dtm::Loss(32)));
+        let board = ChessBoard::new_from_fen("8/8/8/8/8/2k5/1R6/7K b - - 0 1").unwrap();
+        assert_eq!(tables.probe(&board), Some(Dtm::Draw));
+    }
+
+    #[test]
+    fn generates_kqvk() {
+        let mut tables = EndgameTables::new();
+        assert!(tables.generate("KQvK"));
+        assert_eq!(longest_mate_moves(&tables, "KQvK"), Some(10));
+        //Black to move, so the table is probed with colours reversed
+        let board = ChessBoard::new_from_fen("8/8/8/8/8/2k5/7q/K7 b - - 0 1").unwrap();
+        assert_eq!(tables.probe(&board), Some(Dtm::Win(1)));
+    }
+
+    #[test]
+    fn refuses_pawns_on_both_sides() {
+        let mut tables = EndgameTables::new();
+        assert!(!tables.generate("KPvKP"));
+        assert!(tables.is_empty());
+        assert!(EndgameTable::new("KPvKP").is_none());
+        assert!(EndgameTable::new("KPvKR").is_some());
+    }
+
+    #[test]
+    fn saves_and_loads_tables() {
+        let mut tables = EndgameTables::new();
+        assert!(tables.generate("KRvK"));
+        let directory = std::env::temp_dir().join(format!("jmtb-test-{}", std::process::id()));
+        tables.save(&directory).unwrap();
+        let loaded = EndgameTables::load(&directory);
+        fs::remove_dir_all(&directory).unwrap();
+
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.materials(), vec!["KRvK"]);
+        assert!(loaded.get("KRvK").unwrap().data == tables.get("KRvK").unwrap().data);
+    }
+}
@@ -0,0 +1,644 @@
+use std::fmt::{self, Display};
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::Path;
+
+use crate::chess::chess_board::ChessBoard;
+use crate::chess::chess_color::ChessColor;
+use crate::chess::chess_piece::ChessPiece;
+use crate::chess::{CHESS_BOARD_SIZE, CHESS_BOARD_WIDTH, CHESS_COLORS};
+use crate::engine::pawn_table::PawnTable;
+
+// Indexed by piece type, pawn = 0 .. king = 5
+pub const PIECE_VALUES: [i32; 6] = [100, 320, 330, 500, 900, 0];
+
+// Contribution of each piece type to the game phase, a full set of pieces adds up to MAX_PHASE
+const PHASE_WEIGHTS: [i32; 6] = [0, 1, 1, 2, 4, 0];
+pub const MAX_PHASE: i32 = 24;
+
+/// A value that fades from its middlegame to its endgame version as material comes off.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Weight {
+    pub midgame: i32,
+    pub endgame: i32,
+}
+
+impl Weight {
+    pub const fn new(midgame: i32, endgame: i32) -> Self {
+        Self { midgame, endgame }
+    }
+    pub fn taper(&self, phase: i32) -> i32 {
+        (self.midgame * phase + self.endgame * (MAX_PHASE - phase)) / MAX_PHASE
+    }
+    fn add(&mut self, weight: Weight, count: i32) {
+        self.midgame += weight.midgame * count;
+        self.endgame += weight.endgame * count;
+    }
+}
+
+/// The weights of the evaluation terms, all from the point of view of the side they apply to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EvalWeights {
+    /// Indexed by piece type from pawn = 0 to queen = 4.
+    pub material: [Weight; 5],
+    pub doubled_pawn: Weight,
+    pub isolated_pawn: Weight,
+    pub backward_pawn: Weight,
+    /// Indexed by the rank the pawn is on, counted from its own side.
+    pub passed_pawn: [Weight; 8],
+    /// Per reachable square, indexed by piece type from knight = 0 to queen = 3.
+    pub mobility: [Weight; 4],
+    pub bishop_pair: Weight,
+    pub rook_open_file: Weight,
+    pub rook_semi_open_file: Weight,
+    /// Per file next to the king without a pawn in front of it.
+    pub king_shelter: Weight,
+    /// Per move of an enemy piece into the squares around the king.
+    pub king_attack: Weight,
+}
+
+pub const DEFAULT_WEIGHTS: EvalWeights = EvalWeights {
+    material: [
+        Weight::new(PIECE_VALUES[0], PIECE_VALUES[0]),
+        Weight::new(PIECE_VALUES[1], PIECE_VALUES[1]),
+        Weight::new(PIECE_VALUES[2], PIECE_VALUES[2]),
+        Weight::new(PIECE_VALUES[3], PIECE_VALUES[3]),
+        Weight::new(PIECE_VALUES[4], PIECE_VALUES[4]),
+    ],
+    doubled_pawn: Weight::new(-10, -20),
+    isolated_pawn: Weight::new(-10, -15),
+    backward_pawn: Weight::new(-8, -10),
+    passed_pawn: [
+        Weight::new(0, 0),
+        Weight::new(5, 10),
+        Weight::new(5, 15),
+        Weight::new(10, 25),
+        Weight::new(20, 45),
+        Weight::new(35, 75),
+        Weight::new(60, 120),
+        Weight::new(0, 0),
+    ],
+    mobility: [
+        Weight::new(4, 4),
+        Weight::new(5, 5),
+        Weight::new(2, 4),
+        Weight::new(1, 2),
+    ],
+    bishop_pair: Weight::new(30, 50),
+    rook_open_file: Weight::new(25, 10),
+    rook_semi_open_file: Weight::new(12, 6),
+    king_shelter: Weight::new(-15, 0),
+    king_attack: Weight::new(-6, -2),
+};
+
+const PIECE_NAMES: [&str; 5] = ["pawn", "knight", "bishop", "rook", "queen"];
+
+impl Default for EvalWeights {
+    fn default() -> Self {
+        DEFAULT_WEIGHTS
+    }
+}
+
+impl EvalWeights {
+    /// Every weight with the name it has in a weights file, e.g. `passed_pawn.5` or
+    /// `mobility.rook`.
+    pub fn named_mut(&mut self) -> Vec<(String, &mut Weight)> {
+        let mut named: Vec<(String, &mut Weight)> = vec![];
+        for (name, weight) in PIECE_NAMES.iter().zip(self.material.iter_mut()) {
+            named.push((format!("material.{}", name), weight));
+        }
+        named.push(("doubled_pawn".to_string(), &mut self.doubled_pawn));
+        named.push(("isolated_pawn".to_string(), &mut self.isolated_pawn));
+        named.push(("backward_pawn".to_string(), &mut self.backward_pawn));
+        for (rank, weight) in self.passed_pawn.iter_mut().enumerate() {
+            named.push((format!("passed_pawn.{}", rank), weight));
+        }
+        for (name, weight) in PIECE_NAMES[1..].iter().zip(self.mobility.iter_mut()) {
+            named.push((format!("mobility.{}", name), weight));
+        }
+        named.push(("bishop_pair".to_string(), &mut self.bishop_pair));
+        named.push(("rook_open_file".to_string(), &mut self.rook_open_file));
+        named.push((
+            "rook_semi_open_file".to_string(),
+            &mut self.rook_semi_open_file,
+        ));
+        named.push(("king_shelter".to_string(), &mut self.king_shelter));
+        named.push(("king_attack".to_string(), &mut self.king_attack));
+        named
+    }
+    pub fn named(&self) -> Vec<(String, Weight)> {
+        let mut weights = *self;
+        weights
+            .named_mut()
+            .into_iter()
+            .map(|(name, weight)| (name, *weight))
+            .collect()
+    }
+    /// Reads a weights file: one `name midgame endgame` line per weight, blank lines and lines
+    /// starting with # ignored. Weights the file leaves out keep their default.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let mut weights = DEFAULT_WEIGHTS;
+        let mut named = weights.named_mut();
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || io::Error::new(ErrorKind::InvalidData, line.to_string());
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let [name, midgame, endgame] = parts[..] else {
+                return Err(invalid());
+            };
+            let weight = named
+                .iter_mut()
+                .find(|(weight_name, _)| weight_name == name)
+                .ok_or_else(invalid)?;
+            *weight.1 = Weight::new(
+                midgame.parse().map_err(|_| invalid())?,
+                endgame.parse().map_err(|_| invalid())?,
+            );
+        }
+        Ok(weights)
+    }
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+}
+
+impl Display for EvalWeights {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "# name midgame endgame")?;
+        for (name, weight) in self.named() {
+            writeln!(f, "{} {} {}", name, weight.midgame, weight.endgame)?;
+        }
+        Ok(())
+    }
+}
+
+/// The parts the evaluation is made of, as listed by a trace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvalTerm {
+    Material,
+    PieceSquares,
+    DoubledPawns,
+    IsolatedPawns,
+    BackwardPawns,
+    PassedPawns,
+    Mobility,
+    BishopPair,
+    RookFiles,
+    KingShelter,
+    KingAttack,
+}
+
+impl EvalTerm {
+    pub const ALL: [EvalTerm; 11] = [
+        EvalTerm::Material,
+        EvalTerm::PieceSquares,
+        EvalTerm::DoubledPawns,
+        EvalTerm::IsolatedPawns,
+        EvalTerm::BackwardPawns,
+        EvalTerm::PassedPawns,
+        EvalTerm::Mobility,
+        EvalTerm::BishopPair,
+        EvalTerm::RookFiles,
+        EvalTerm::KingShelter,
+        EvalTerm::KingAttack,
+    ];
+}
+
+impl Display for EvalTerm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            EvalTerm::Material => "Material",
+            EvalTerm::PieceSquares => "Piece squares",
+            EvalTerm::DoubledPawns => "Doubled pawns",
+            EvalTerm::IsolatedPawns => "Isolated pawns",
+            EvalTerm::BackwardPawns => "Backward pawns",
+            EvalTerm::PassedPawns => "Passed pawns",
+            EvalTerm::Mobility => "Mobility",
+            EvalTerm::BishopPair => "Bishop pair",
+            EvalTerm::RookFiles => "Rook files",
+            EvalTerm::KingShelter => "King shelter",
+            EvalTerm::KingAttack => "King attack",
+        };
+        f.pad(name)
+    }
+}
+
+/// What every term adds up to for each side, before tapering.
+#[derive(Debug, Clone, Default)]
+pub struct EvalTrace {
+    pub phase: i32,
+    terms: [[Weight; CHESS_COLORS]; EvalTerm::ALL.len()],
+}
+
+impl EvalTrace {
+    fn add(&mut self, term: EvalTerm, color: ChessColor, weight: Weight, count: i32) {
+        self.terms[term as usize][color.get_idx()].add(weight, count);
+    }
+    /// The tapered contribution of a term for one side in centipawns.
+    pub fn term(&self, term: EvalTerm, color: ChessColor) -> i32 {
+        self.terms[term as usize][color.get_idx()].taper(self.phase)
+    }
+    /// White's terms minus black's, before tapering.
+    pub fn sum(&self) -> Weight {
+        let mut sum = Weight::default();
+        for [white, black] in self.terms {
+            sum.add(white, 1);
+            sum.add(black, -1);
+        }
+        sum
+    }
+    /// The whole evaluation from white's point of view.
+    pub fn total(&self) -> i32 {
+        self.sum().taper(self.phase)
+    }
+}
+
+impl Display for EvalTrace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<16}{:>8}{:>8}{:>8}",
+            "Term", "White", "Black", "Total"
+        )?;
+        for term in EvalTerm::ALL {
+            let white = self.term(term, ChessColor::WHITE);
+            let black = self.term(term, ChessColor::BLACK);
+            writeln!(
+                f,
+                "{:<16}{:>8}{:>8}{:>8}",
+                term,
+                white,
+                black,
+                white - black
+            )?;
+        }
+        writeln!(
+            f,
+            "Phase {}/{}, total {} for white",
+            self.phase,
+            MAX_PHASE,
+            self.total()
+        )
+    }
+}
+
+// Piece-square tables from white's point of view, laid out like ChessBoard::layout with a8 first
+#[rustfmt::skip]
+const PAWN_TABLE: [i32; CHESS_BOARD_SIZE] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    10, 10, 20, 30, 30, 20, 10, 10,
+     5,  5, 10, 25, 25, 10,  5,  5,
+     0,  0,  0, 20, 20,  0,  0,  0,
+     5, -5,-10,  0,  0,-10, -5,  5,
+     5, 10, 10,-20,-20, 10, 10,  5,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+#[rustfmt::skip]
+const KNIGHT_TABLE: [i32; CHESS_BOARD_SIZE] = [
+    -50,-40,-30,-30,-30,-30,-40,-50,
+    -40,-20,  0,  0,  0,  0,-20,-40,
+    -30,  0, 10, 15, 15, 10,  0,-30,
+    -30,  5, 15, 20, 20, 15,  5,-30,
+    -30,  0, 15, 20, 20, 15,  0,-30,
+    -30,  5, 10, 15, 15, 10,  5,-30,
+    -40,-20,  0,  5,  5,  0,-20,-40,
+    -50,-40,-30,-30,-30,-30,-40,-50,
+];
+#[rustfmt::skip]
+const BISHOP_TABLE: [i32; CHESS_BOARD_SIZE] = [
+    -20,-10,-10,-10,-10,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5, 10, 10,  5,  0,-10,
+    -10,  5,  5, 10, 10,  5,  5,-10,
+    -10,  0, 10, 10, 10, 10,  0,-10,
+    -10, 10, 10, 10, 10, 10, 10,-10,
+    -10,  5,  0,  0,  0,  0,  5,-10,
+    -20,-10,-10,-10,-10,-10,-10,-20,
+];
+#[rustfmt::skip]
+const ROOK_TABLE: [i32; CHESS_BOARD_SIZE] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+     5, 10, 10, 10, 10, 10, 10,  5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+     0,  0,  0,  5,  5,  0,  0,  0,
+];
+#[rustfmt::skip]
+const QUEEN_TABLE: [i32; CHESS_BOARD_SIZE] = [
+    -20,-10,-10, -5, -5,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5,  5,  5,  5,  0,-10,
+     -5,  0,  5,  5,  5,  5,  0, -5,
+      0,  0,  5,  5,  5,  5,  0, -5,
+    -10,  5,  5,  5,  5,  5,  0,-10,
+    -10,  0,  5,  0,  0,  0,  0,-10,
+    -20,-10,-10, -5, -5,-10,-10,-20,
+];
+#[rustfmt::skip]
+const KING_MIDGAME_TABLE: [i32; CHESS_BOARD_SIZE] = [
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -20,-30,-30,-40,-40,-30,-30,-20,
+    -10,-20,-20,-20,-20,-20,-20,-10,
+     20, 20,  0,  0,  0,  0, 20, 20,
+     20, 30, 10,  0,  0, 10, 30, 20,
+];
+#[rustfmt::skip]
+const KING_ENDGAME_TABLE: [i32; CHESS_BOARD_SIZE] = [
+    -50,-40,-30,-20,-20,-30,-40,-50,
+    -30,-20,-10,  0,  0,-10,-20,-30,
+    -30,-10, 20, 30, 30, 20,-10,-30,
+    -30,-10, 30, 40, 40, 30,-10,-30,
+    -30,-10, 30, 40, 40, 30,-10,-30,
+    -30,-10, 20, 30, 30, 20,-10,-30,
+    -30,-30,  0,  0,  0,  0,-30,-30,
+    -50,-30,-30,-30,-30,-30,-30,-50,
+];
+
+const PIECE_TABLES: [&[i32; CHESS_BOARD_SIZE]; 5] = [
+    &PAWN_TABLE,
+    &KNIGHT_TABLE,
+    &BISHOP_TABLE,
+    &ROOK_TABLE,
+    &QUEEN_TABLE,
+];
+
+/// How much non-pawn material is left, from MAX_PHASE at the start of the game down to 0.
+pub fn game_phase(board: &ChessBoard) -> i32 {
+    let mut phase = 0;
+    for piece in board.layout.iter() {
+        if !piece.is_empty() {
+            phase += PHASE_WEIGHTS[piece.get_type_idx()];
+        }
+    }
+    phase.min(MAX_PHASE)
+}
+
+/// Static evaluation in centipawns from the point of view of the side to move, with the pawn
+/// structure looked up in `pawn_table` when it was seen before.
+pub fn evaluate(board: &ChessBoard, weights: &EvalWeights, pawn_table: &mut PawnTable) -> i32 {
+    let pawns = pawn_table.probe(board);
+    let score = trace_with_pawns(board, weights, &pawns).total();
+    if board.color_to_move == ChessColor::WHITE {
+        score
+    } else {
+        -score
+    }
+}
+
+/// Evaluates the position with the given weights, keeping every term apart.
+pub fn trace(board: &ChessBoard, weights: &EvalWeights) -> EvalTrace {
+    trace_with_pawns(board, weights, &PawnStructure::new(board))
+}
+
+fn trace_with_pawns(board: &ChessBoard, weights: &EvalWeights, pawns: &PawnStructure) -> EvalTrace {
+    let mut trace = EvalTrace {
+        phase: game_phase(board),
+        ..Default::default()
+    };
+    let kings = [
+        board.king_square(ChessColor::WHITE),
+        board.king_square(ChessColor::BLACK),
+    ];
+    let mut bishops = [0; CHESS_COLORS];
+    let mut moves = vec![];
+
+    for (idx, piece) in board.layout.iter().enumerate() {
+        let color = match piece.get_color() {
+            Some(color) => color,
+            None => continue,
+        };
+        //Tables are written for white, black reads them with the ranks flipped
+        let table_idx = if color == ChessColor::WHITE {
+            idx
+        } else {
+            idx ^ 56
+        };
+        let type_idx = piece.get_type_idx();
+
+        if piece.contains(ChessPiece::KING) {
+            let king_table =
+                Weight::new(KING_MIDGAME_TABLE[table_idx], KING_ENDGAME_TABLE[table_idx]);
+            trace.add(EvalTerm::PieceSquares, color, king_table, 1);
+            continue;
+        }
+        trace.add(EvalTerm::Material, color, weights.material[type_idx], 1);
+        let square_value = PIECE_TABLES[type_idx][table_idx];
+        trace.add(
+            EvalTerm::PieceSquares,
+            color,
+            Weight::new(square_value, square_value),
+            1,
+        );
+        if piece.contains(ChessPiece::PAWN) {
+            continue;
+        }
+
+        moves.clear();
+        board.generate_piece_moves(&mut moves, idx);
+        trace.add(
+            EvalTerm::Mobility,
+            color,
+            weights.mobility[type_idx - 1],
+            moves.len() as i32,
+        );
+        //Pressure on the enemy king counts against the side that owns it
+        if let Some(king_idx) = kings[color.opposite().get_idx()] {
+            let attacks = moves
+                .iter()
+                .filter(|chess_move| distance(chess_move.get_idx().1 as usize, king_idx) <= 1)
+                .count();
+            trace.add(
+                EvalTerm::KingAttack,
+                color.opposite(),
+                weights.king_attack,
+                attacks as i32,
+            );
+        }
+
+        if piece.contains(ChessPiece::BISHOP) {
+            bishops[color.get_idx()] += 1;
+        } else if piece.contains(ChessPiece::ROOK) {
+            let file = (idx % CHESS_BOARD_WIDTH) as i32;
+            if pawns.on_file(color, file) == 0 {
+                let weight = if pawns.on_file(color.opposite(), file) == 0 {
+                    weights.rook_open_file
+                } else {
+                    weights.rook_semi_open_file
+                };
+                trace.add(EvalTerm::RookFiles, color, weight, 1);
+            }
+        }
+    }
+
+    for color in [ChessColor::WHITE, ChessColor::BLACK] {
+        if bishops[color.get_idx()] >= 2 {
+            trace.add(EvalTerm::BishopPair, color, weights.bishop_pair, 1);
+        }
+
+        let [doubled, isolated, backward] = pawns.counts[color.get_idx()].map(i32::from);
+        trace.add(EvalTerm::DoubledPawns, color, weights.doubled_pawn, doubled);
+        trace.add(
+            EvalTerm::IsolatedPawns,
+            color,
+            weights.isolated_pawn,
+            isolated,
+        );
+        trace.add(
+            EvalTerm::BackwardPawns,
+            color,
+            weights.backward_pawn,
+            backward,
+        );
+        let mut passed = pawns.passed[color.get_idx()];
+        while passed != 0 {
+            let idx = passed.trailing_zeros() as usize;
+            passed &= passed - 1;
+            let relative_rank = if color == ChessColor::WHITE {
+                rank_of(idx)
+            } else {
+                7 - rank_of(idx)
+            };
+            trace.add(
+                EvalTerm::PassedPawns,
+                color,
+                weights.passed_pawn[relative_rank],
+                1,
+            );
+        }
+
+        if let Some(king_idx) = kings[color.get_idx()] {
+            let missing = pawns.missing_shelter(color, king_idx);
+            trace.add(EvalTerm::KingShelter, color, weights.king_shelter, missing);
+        }
+    }
+    trace
+}
+
+/// Everything the evaluation needs to know about the pawns alone, which is what the pawn hash
+/// table caches. It holds counts rather than scores so it stays valid whatever the weights.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PawnStructure {
+    //The ranks of the pawns on every file as bits, rank 1 = bit 0
+    files: [[u8; CHESS_BOARD_WIDTH]; CHESS_COLORS],
+    //Doubled, isolated and backward pawns
+    counts: [[u8; 3]; CHESS_COLORS],
+    /// Squares of the passed pawns of each color, bit n standing for `ChessBoard::layout[n]`.
+    pub passed: [u64; CHESS_COLORS],
+}
+
+impl PawnStructure {
+    pub fn new(board: &ChessBoard) -> Self {
+        let mut pawns = Self::default();
+        for (idx, piece) in board.layout.iter().enumerate() {
+            if let (true, Some(color)) = (piece.contains(ChessPiece::PAWN), piece.get_color()) {
+                pawns.files[color.get_idx()][idx % CHESS_BOARD_WIDTH] |= 1 << rank_of(idx);
+            }
+        }
+        for color in [ChessColor::WHITE, ChessColor::BLACK] {
+            pawns.classify(color);
+        }
+        pawns
+    }
+    //Pawns of `color` on the file, 0 for files off the board
+    fn on_file(&self, color: ChessColor, file: i32) -> u8 {
+        if (0..CHESS_BOARD_WIDTH as i32).contains(&file) {
+            self.files[color.get_idx()][file as usize]
+        } else {
+            0
+        }
+    }
+    fn classify(&mut self, color: ChessColor) {
+        let enemy = color.opposite();
+        for file in 0..CHESS_BOARD_WIDTH as i32 {
+            let own = self.on_file(color, file);
+            let neighbours = self.on_file(color, file - 1) | self.on_file(color, file + 1);
+            let enemy_neighbours = self.on_file(enemy, file - 1) | self.on_file(enemy, file + 1);
+            let enemy_pawns = enemy_neighbours | self.on_file(enemy, file);
+
+            for rank in (0..8usize).filter(|rank| own & 1 << rank != 0) {
+                //Ranks further up the board from this pawn's point of view
+                let (ahead, stop_attackers) = if color == ChessColor::WHITE {
+                    ((!0u16 << (rank + 1)) as u8, (1u16 << (rank + 2)) as u8)
+                } else {
+                    (((1u16 << rank) - 1) as u8, ((1u16 << rank) >> 2) as u8)
+                };
+                let counts = &mut self.counts[color.get_idx()];
+
+                if own & ahead != 0 {
+                    counts[0] += 1;
+                } else if enemy_pawns & ahead == 0 {
+                    let idx = (7 - rank) * CHESS_BOARD_WIDTH + file as usize;
+                    self.passed[color.get_idx()] |= 1 << idx;
+                }
+                if neighbours == 0 {
+                    counts[1] += 1;
+                } else if neighbours & !ahead == 0 && enemy_neighbours & stop_attackers != 0 {
+                    //Left behind by its neighbours and unable to advance safely
+                    counts[2] += 1;
+                }
+            }
+        }
+    }
+    //Files next to the king, its own included, without a pawn one or two ranks in front of it.
+    //Only a king on its first two ranks has a shelter to speak of.
+    fn missing_shelter(&self, color: ChessColor, king_idx: usize) -> i32 {
+        let rank = rank_of(king_idx);
+        let relative_rank = if color == ChessColor::WHITE {
+            rank
+        } else {
+            7 - rank
+        };
+        if relative_rank > 1 {
+            return 0;
+        }
+        let shield = if color == ChessColor::WHITE {
+            0b11 << (rank + 1)
+        } else {
+            0b11 << (rank - 2)
+        };
+        let file = (king_idx % CHESS_BOARD_WIDTH) as i32;
+        (file - 1..=file + 1)
+            .filter(|&f| (0..CHESS_BOARD_WIDTH as i32).contains(&f))
+            .filter(|&f| self.on_file(color, f) & shield == 0)
+            .count() as i32
+    }
+}
+
+//Rank of the square counted from white's side, a1 is on rank 0
+fn rank_of(idx: usize) -> usize {
+    7 - idx / CHESS_BOARD_WIDTH
+}
+
+fn distance(a: usize, b: usize) -> usize {
+    let file_distance = (a % CHESS_BOARD_WIDTH).abs_diff(b % CHESS_BOARD_WIDTH);
+    let rank_distance = (a / CHESS_BOARD_WIDTH).abs_diff(b / CHESS_BOARD_WIDTH);
+    file_distance.max(rank_distance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::chess_board::tests::{walk, POSITIONS};
+
+    #[test]
+    fn pawn_table_returns_computed_structure() {
+        let mut pawn_table = PawnTable::new();
+        for fen in POSITIONS {
+            let mut board = ChessBoard::new_from_fen(fen).unwrap();
+            walk(&mut board, 3, &mut |board| {
+                assert_eq!(pawn_table.probe(board), PawnStructure::new(board));
+            });
+        }
+        assert!(pawn_table.hits() > 0);
+    }
+}
@@ -0,0 +1,674 @@
+use std::fmt::{self, Display};
+use std::fs;
+use std::io::{self, BufRead, BufReader, ErrorKind, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::chess::chess_board::{ChessBoard, GameOutcome};
+use crate::chess::chess_color::ChessColor;
+use crate::chess::chess_epd::EpdPosition;
+use crate::chess::chess_move::ChessMove;
+use crate::chess::chess_pgn::{parse_pgn, GameResult};
+use crate::chess::START_POSITION_FEN;
+use crate::engine::elo::{MatchScore, Sprt, SprtVerdict};
+use crate::engine::score::{mate_in, mated_in};
+use crate::engine::search::{SearchLimits, Searcher};
+use crate::engine::time_manager::TimeControl;
+
+//How long an external engine may take to start up or to answer isready
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+//How long an engine that overstepped its time gets to answer a stop
+const STOP_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How to start one side of a match: `internal` with optional options, as in
+/// `internal:NullMovePruning=false,Hash=32`, or the path of a UCI executable, optionally
+/// followed by options the same way.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EngineSpec {
+    /// None for the engine itself.
+    pub path: Option<String>,
+    pub options: Vec<(String, String)>,
+}
+
+impl EngineSpec {
+    pub fn parse(spec: &str) -> Self {
+        let (path, options) = match spec.split_once(':') {
+            Some((path, options)) if options.contains('=') => (path, options),
+            _ => (spec, ""),
+        };
+        Self {
+            path: (path != "internal").then(|| path.to_string()),
+            options: options
+                .split(',')
+                .filter_map(|option| option.split_once('='))
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+        }
+    }
+}
+
+impl Display for EngineSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = self.path.as_deref().unwrap_or("internal");
+        let options: Vec<String> = self
+            .options
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        if options.is_empty() {
+            f.write_str(name)
+        } else {
+            write!(f, "{}:{}", name, options.join(","))
+        }
+    }
+}
+
+/// When games are called before they are over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Adjudication {
+    /// A draw is given from this move on once both engines keep their scores within
+    /// `draw_score` for `draw_move_count` moves each.
+    pub draw_move_number: usize,
+    pub draw_move_count: usize,
+    pub draw_score: i32,
+    /// A game is over once both engines agree one side is `resign_score` ahead for
+    /// `resign_move_count` moves each.
+    pub resign_move_count: usize,
+    pub resign_score: i32,
+    /// Games reaching this many moves are drawn.
+    pub max_moves: usize,
+}
+
+impl Default for Adjudication {
+    fn default() -> Self {
+        Self {
+            draw_move_number: 40,
+            draw_move_count: 8,
+            draw_score: 10,
+            resign_move_count: 3,
+            resign_score: 1000,
+            max_moves: 300,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MatchConfig {
+    /// Games to play, every opening is played twice with the colours reversed.
+    pub games: usize,
+    pub base_time: Duration,
+    pub increment: Duration,
+    /// How far an engine may overstep its clock before it loses on time.
+    pub time_margin: Duration,
+    /// Transposition table size of internal engines that don't set Hash themselves.
+    pub hash_size_mb: usize,
+    /// Start positions as FENs, the normal one when empty.
+    pub openings: Vec<String>,
+    pub adjudication: Adjudication,
+    /// Ends the match early once the test has a verdict.
+    pub sprt: Option<Sprt>,
+}
+
+impl Default for MatchConfig {
+    fn default() -> Self {
+        Self {
+            games: 100,
+            base_time: Duration::from_secs(10),
+            increment: Duration::from_millis(100),
+            time_margin: Duration::ZERO,
+            hash_size_mb: 16,
+            openings: vec![],
+            adjudication: Adjudication::default(),
+            sprt: None,
+        }
+    }
+}
+
+/// Reads start positions from a PGN file, taking the position at the end of every game, or
+/// from a file of FEN or EPD lines.
+pub fn load_openings(path: &Path) -> io::Result<Vec<String>> {
+    let text = String::from_utf8_lossy(&fs::read(path)?).into_owned();
+    let is_pgn = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("pgn"));
+    let openings = if is_pgn {
+        parse_pgn(&text)
+            .iter()
+            .filter_map(|game| {
+                let mut board = game.start_board()?;
+                for chess_move in game.replay() {
+                    board.make_move_unchecked(&chess_move);
+                }
+                Some(board.to_fen())
+            })
+            .collect()
+    } else {
+        text.lines()
+            .filter(|line| !line.trim_start().starts_with('#'))
+            .filter_map(|line| {
+                //A FEN with its move counters, else an EPD line carrying operations instead
+                let fen: Vec<&str> = line.split_whitespace().take(6).collect();
+                ChessBoard::new_from_fen(&fen.join(" "))
+                    .or_else(|_| EpdPosition::parse(line).map(|position| position.board))
+                    .ok()
+                    .map(|board| board.to_fen())
+            })
+            .collect()
+    };
+    Ok(openings)
+}
+
+/// Why a game ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameEnd {
+    Checkmate,
+    Stalemate,
+    Repetition,
+    FiftyMoves,
+    InsufficientMaterial,
+    TimeForfeit,
+    IllegalMove,
+    EngineFailure,
+    AdjudicatedDraw,
+    AdjudicatedWin,
+    MaxLength,
+}
+
+impl Display for GameEnd {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reason = match self {
+            GameEnd::Checkmate => "checkmate",
+            GameEnd::Stalemate => "stalemate",
+            GameEnd::Repetition => "threefold repetition",
+            GameEnd::FiftyMoves => "fifty move rule",
+            GameEnd::InsufficientMaterial => "insufficient material",
+            GameEnd::TimeForfeit => "loss on time",
+            GameEnd::IllegalMove => "illegal move",
+            GameEnd::EngineFailure => "engine failure",
+            GameEnd::AdjudicatedDraw => "adjudicated draw",
+            GameEnd::AdjudicatedWin => "adjudicated win",
+            GameEnd::MaxLength => "maximum length",
+        };
+        f.pad(reason)
+    }
+}
+
+/// One finished game of a match.
+#[derive(Debug, Clone)]
+pub struct GameRecord {
+    pub number: usize,
+    pub opening: String,
+    /// Index of the engine playing white, 0 for the first.
+    pub white: usize,
+    pub result: GameResult,
+    pub end: GameEnd,
+    pub moves: Vec<ChessMove>,
+}
+
+impl GameRecord {
+    /// The result for the first engine: 1 for a win, 0.5 for a draw and 0 for a loss.
+    pub fn first_engine_points(&self) -> f64 {
+        match (self.result, self.white) {
+            (GameResult::WhiteWins, 0) | (GameResult::BlackWins, 1) => 1.0,
+            (GameResult::WhiteWins, _) | (GameResult::BlackWins, _) => 0.0,
+            _ => 0.5,
+        }
+    }
+}
+
+/// Plays the match and returns the score of the first engine. `on_game` is called after
+/// every game with the game, the score so far and the SPRT verdict if one is running.
+pub fn run_match(
+    specs: [&EngineSpec; 2],
+    config: &MatchConfig,
+    mut on_game: impl FnMut(&GameRecord, &MatchScore, Option<SprtVerdict>),
+) -> io::Result<MatchScore> {
+    let mut engines = [
+        Engine::start(specs[0], config.hash_size_mb)?,
+        Engine::start(specs[1], config.hash_size_mb)?,
+    ];
+    let start_position = [START_POSITION_FEN.to_string()];
+    let openings = if config.openings.is_empty() {
+        &start_position[..]
+    } else {
+        &config.openings[..]
+    };
+
+    let mut score = MatchScore::default();
+    for number in 0..config.games {
+        let opening = &openings[(number / 2) % openings.len()];
+        let record = play_game(&mut engines, number, opening, number % 2, config);
+        let points = record.first_engine_points();
+        if points == 1.0 {
+            score.wins += 1;
+        } else if points == 0.0 {
+            score.losses += 1;
+        } else {
+            score.draws += 1;
+        }
+        let verdict = config.sprt.map(|sprt| sprt.verdict(&score));
+        on_game(&record, &score, verdict);
+        if verdict.is_some_and(|verdict| verdict != SprtVerdict::Continue) {
+            break;
+        }
+    }
+    Ok(score)
+}
+
+fn win_for(color: ChessColor) -> GameResult {
+    if color == ChessColor::WHITE {
+        GameResult::WhiteWins
+    } else {
+        GameResult::BlackWins
+    }
+}
+
+fn play_game(
+    engines: &mut [Engine; 2],
+    number: usize,
+    opening: &str,
+    white: usize,
+    config: &MatchConfig,
+) -> GameRecord {
+    let mut board = ChessBoard::new_from_fen(opening).unwrap();
+    let mut record = GameRecord {
+        number,
+        opening: opening.to_string(),
+        white,
+        result: GameResult::Unknown,
+        end: GameEnd::EngineFailure,
+        moves: vec![],
+    };
+    //Zobrist keys of every position of the game so far, for threefold repetition
+    let mut keys = vec![board.zobrist_key];
+    //Indexed by color, not by engine
+    let mut clocks = [config.base_time; 2];
+    let mut adjudicator = Adjudicator::new(config.adjudication);
+
+    for (i, engine) in engines.iter_mut().enumerate() {
+        if engine.new_game().is_err() {
+            let color = if i == white {
+                ChessColor::WHITE
+            } else {
+                ChessColor::BLACK
+            };
+            record.result = win_for(color.opposite());
+            return record;
+        }
+    }
+
+    let (result, end) = loop {
+        if let Some(outcome) = rules_outcome(&board, &keys) {
+            break outcome;
+        }
+        if record.moves.len() >= 2 * config.adjudication.max_moves {
+            break (GameResult::Draw, GameEnd::MaxLength);
+        }
+        let color = board.color_to_move;
+        let mover = if color == ChessColor::WHITE {
+            white
+        } else {
+            1 - white
+        };
+        let own_clock = clocks[color.get_idx()];
+        let clock = Clock {
+            white: clocks[ChessColor::WHITE.get_idx()],
+            black: clocks[ChessColor::BLACK.get_idx()],
+            increment: config.increment,
+            deadline: own_clock + config.time_margin,
+        };
+
+        let timer = Instant::now();
+        let answer = engines[mover].think(opening, &record.moves, &board, &clock);
+        let elapsed = timer.elapsed();
+        let (notation, score) = match answer {
+            Ok(Some(answer)) if elapsed <= clock.deadline => answer,
+            Ok(_) => break (time_forfeit(&board, color), GameEnd::TimeForfeit),
+            Err(_) => break (win_for(color.opposite()), GameEnd::EngineFailure),
+        };
+        let Some(chess_move) = board.find_move(&notation) else {
+            break (win_for(color.opposite()), GameEnd::IllegalMove);
+        };
+        clocks[color.get_idx()] = own_clock.saturating_sub(elapsed) + config.increment;
+
+        board.make_move_unchecked(&chess_move);
+        record.moves.push(chess_move);
+        keys.push(board.zobrist_key);
+
+        //Scores are kept from white's point of view
+        let white_score = score.map(|score| {
+            if color == ChessColor::WHITE {
+                score
+            } else {
+                -score
+            }
+        });
+        if let Some(outcome) = adjudicator.update(white_score, board.fullmove_number as usize) {
+            break outcome;
+        }
+    };
+    record.result = result;
+    record.end = end;
+    record
+}
+
+//A flag only loses when the opponent still has something to mate with
+fn time_forfeit(board: &ChessBoard, color: ChessColor) -> GameResult {
+    let opponent = color.opposite();
+    let opponent_pieces = board
+        .layout
+        .iter()
+        .filter(|piece| piece.has_color(opponent))
+        .count();
+    if opponent_pieces > 1 {
+        win_for(opponent)
+    } else {
+        GameResult::Draw
+    }
+}
+
+fn rules_outcome(board: &ChessBoard, keys: &[u64]) -> Option<(GameResult, GameEnd)> {
+    if let Some(outcome) = board.outcome() {
+        return Some(match outcome {
+            GameOutcome::Checkmate => (win_for(board.color_to_move.opposite()), GameEnd::Checkmate),
+            GameOutcome::Stalemate => (GameResult::Draw, GameEnd::Stalemate),
+            GameOutcome::FiftyMoveRule => (GameResult::Draw, GameEnd::FiftyMoves),
+            GameOutcome::InsufficientMaterial => (GameResult::Draw, GameEnd::InsufficientMaterial),
+        });
+    }
+    //Only positions since the last capture or pawn move can repeat
+    let reversible = keys
+        .iter()
+        .rev()
+        .take(board.halfmove_clock as usize + 1)
+        .filter(|&&key| key == board.zobrist_key)
+        .count();
+    (reversible >= 3).then_some((GameResult::Draw, GameEnd::Repetition))
+}
+
+//Counts the moves in a row the engines' scores meet the adjudication rules
+struct Adjudicator {
+    rules: Adjudication,
+    draw_plies: usize,
+    win_plies: usize,
+    //Which side the agreed advantage is for
+    winner: Option<ChessColor>,
+}
+
+impl Adjudicator {
+    fn new(rules: Adjudication) -> Self {
+        Self {
+            rules,
+            draw_plies: 0,
+            win_plies: 0,
+            winner: None,
+        }
+    }
+    fn update(
+        &mut self,
+        white_score: Option<i32>,
+        move_number: usize,
+    ) -> Option<(GameResult, GameEnd)> {
+        let Some(score) = white_score else {
+            self.draw_plies = 0;
+            self.win_plies = 0;
+            return None;
+        };
+
+        if score.abs() <= self.rules.draw_score && move_number >= self.rules.draw_move_number {
+            self.draw_plies += 1;
+        } else {
+            self.draw_plies = 0;
+        }
+        if self.draw_plies >= 2 * self.rules.draw_move_count {
+            return Some((GameResult::Draw, GameEnd::AdjudicatedDraw));
+        }
+
+        let leader = match score {
+            score if score >= self.rules.resign_score => Some(ChessColor::WHITE),
+            score if score <= -self.rules.resign_score => Some(ChessColor::BLACK),
+            _ => None,
+        };
+        if leader.is_some() && leader == self.winner {
+            self.win_plies += 1;
+        } else {
+            self.win_plies = leader.map_or(0, |_| 1);
+        }
+        self.winner = leader;
+        match self.winner {
+            Some(winner) if self.win_plies >= 2 * self.rules.resign_move_count => {
+                Some((win_for(winner), GameEnd::AdjudicatedWin))
+            }
+            _ => None,
+        }
+    }
+}
+
+//Both clocks for the go command and the time the side to move has before it loses
+struct Clock {
+    white: Duration,
+    black: Duration,
+    increment: Duration,
+    deadline: Duration,
+}
+
+enum Engine {
+    Internal(Box<Searcher>),
+    External(UciEngine),
+}
+
+impl Engine {
+    fn start(spec: &EngineSpec, hash_size_mb: usize) -> io::Result<Self> {
+        match &spec.path {
+            None => {
+                let mut searcher = Searcher::new(hash_size_mb);
+                for (name, value) in &spec.options {
+                    if !searcher.set_option(name, value) {
+                        return Err(io::Error::new(
+                            ErrorKind::InvalidInput,
+                            format!("invalid option {}={}", name, value),
+                        ));
+                    }
+                }
+                Ok(Engine::Internal(Box::new(searcher)))
+            }
+            Some(path) => Ok(Engine::External(UciEngine::start(path, &spec.options)?)),
+        }
+    }
+    fn new_game(&mut self) -> io::Result<()> {
+        match self {
+            Engine::Internal(searcher) => {
+                searcher.clear();
+                Ok(())
+            }
+            Engine::External(engine) => engine.new_game(),
+        }
+    }
+    //The move in UCI notation with the engine's score for the side to move, None when it
+    //didn't answer in time
+    fn think(
+        &mut self,
+        opening: &str,
+        moves: &[ChessMove],
+        board: &ChessBoard,
+        clock: &Clock,
+    ) -> io::Result<Option<(String, Option<i32>)>> {
+        match self {
+            Engine::Internal(searcher) => {
+                let time_left = if board.color_to_move == ChessColor::WHITE {
+                    clock.white
+                } else {
+                    clock.black
+                };
+                let limits = SearchLimits {
+                    time_control: Some(TimeControl {
+                        time_left,
+                        increment: clock.increment,
+                        moves_to_go: None,
+                    }),
+                    ..Default::default()
+                };
+                let result = searcher.search(board, &limits, |_| ());
+                Ok(result
+                    .best_move
+                    .map(|best_move| (best_move.to_string(), Some(result.score))))
+            }
+            Engine::External(engine) => engine.think(opening, moves, clock),
+        }
+    }
+}
+
+/// An external engine spoken to over UCI.
+struct UciEngine {
+    process: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+}
+
+impl UciEngine {
+    fn start(path: &str, options: &[(String, String)]) -> io::Result<Self> {
+        let mut process = Command::new(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let stdin = process.stdin.take().unwrap();
+        let stdout = process.stdout.take().unwrap();
+        //Reading happens on its own thread so waiting for an answer can time out
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut engine = Self {
+            process,
+            stdin,
+            lines,
+        };
+        engine.send("uci")?;
+        engine.wait_for("uciok", HANDSHAKE_TIMEOUT)?;
+        for (name, value) in options {
+            engine.send(&format!("setoption name {} value {}", name, value))?;
+        }
+        engine.send("isready")?;
+        engine.wait_for("readyok", HANDSHAKE_TIMEOUT)?;
+        Ok(engine)
+    }
+    fn send(&mut self, command: &str) -> io::Result<()> {
+        writeln!(self.stdin, "{}", command)?;
+        self.stdin.flush()
+    }
+    fn wait_for(&mut self, token: &str, timeout: Duration) -> io::Result<String> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match self.lines.recv_timeout(remaining) {
+                Ok(line) if line.split_whitespace().next() == Some(token) => return Ok(line),
+                Ok(_) => (),
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(io::Error::new(ErrorKind::TimedOut, token.to_string()))
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(io::Error::new(ErrorKind::BrokenPipe, token.to_string()))
+                }
+            }
+        }
+    }
+    fn new_game(&mut self) -> io::Result<()> {
+        self.send("ucinewgame")?;
+        self.send("isready")?;
+        self.wait_for("readyok", HANDSHAKE_TIMEOUT).map(|_| ())
+    }
+    fn think(
+        &mut self,
+        opening: &str,
+        moves: &[ChessMove],
+        clock: &Clock,
+    ) -> io::Result<Option<(String, Option<i32>)>> {
+        let mut position = format!("position fen {}", opening);
+        if !moves.is_empty() {
+            let moves: Vec<String> = moves.iter().map(|m| m.to_string()).collect();
+            position = format!("{} moves {}", position, moves.join(" "));
+        }
+        self.send(&position)?;
+        self.send(&format!(
+            "go wtime {} btime {} winc {} binc {}",
+            clock.white.as_millis(),
+            clock.black.as_millis(),
+            clock.increment.as_millis(),
+            clock.increment.as_millis()
+        ))?;
+
+        let deadline = Instant::now() + clock.deadline;
+        let mut score = None;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match self.lines.recv_timeout(remaining) {
+                Ok(line) => {
+                    let parts: Vec<&str> = line.split_whitespace().collect();
+                    match parts.first() {
+                        Some(&"bestmove") => {
+                            let best_move = parts.get(1).unwrap_or(&"").to_string();
+                            return Ok(Some((best_move, score)));
+                        }
+                        Some(&"info") => score = parse_score(&parts).or(score),
+                        _ => (),
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    //Out of time, but the engine must still be brought back to idle
+                    self.send("stop")?;
+                    self.wait_for("bestmove", STOP_TIMEOUT)?;
+                    return Ok(None);
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(io::Error::new(ErrorKind::BrokenPipe, "engine exited"))
+                }
+            }
+        }
+    }
+}
+
+impl Drop for UciEngine {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        let deadline = Instant::now() + STOP_TIMEOUT;
+        while Instant::now() < deadline {
+            if let Ok(Some(_status)) = self.process.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
+
+//The score of an info line, mates turned into mate scores
+fn parse_score(parts: &[&str]) -> Option<i32> {
+    let idx = parts.iter().position(|&part| part == "score")?;
+    let value: i32 = parts.get(idx + 2)?.parse().ok()?;
+    //Bounds from a failed aspiration window aren't the engine's opinion yet
+    if matches!(
+        parts.get(idx + 3),
+        Some(&"lowerbound") | Some(&"upperbound")
+    ) {
+        return None;
+    }
+    match *parts.get(idx + 1)? {
+        "cp" => Some(value),
+        "mate" if value > 0 => Some(mate_in(2 * value as usize - 1)),
+        "mate" => Some(mated_in(2 * value.unsigned_abs() as usize)),
+        _ => None,
+    }
+}
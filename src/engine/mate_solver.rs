@@ -0,0 +1,179 @@
+use std::collections::HashSet;
+use std::fmt::{self, Display};
+
+use crate::chess::chess_board::ChessBoard;
+use crate::chess::chess_move::ChessMove;
+
+/// A proven mate: the attacker's move and, for every legal defence against it, how the
+/// attack goes on. A node without defences is the mating move itself.
+#[derive(Debug, Clone)]
+pub struct MateSolution {
+    pub attacker_move: ChessMove,
+    pub defences: Vec<(ChessMove, MateSolution)>,
+}
+
+impl MateSolution {
+    /// Number of attacker moves in the longest line of the tree.
+    pub fn length(&self) -> u32 {
+        1 + self
+            .defences
+            .iter()
+            .map(|(_defence, solution)| solution.length())
+            .max()
+            .unwrap_or(0)
+    }
+    fn write_tree(&self, f: &mut fmt::Formatter, move_number: u32, indent: usize) -> fmt::Result {
+        if self.defences.is_empty() {
+            return writeln!(
+                f,
+                "{:indent$}{}. {}#",
+                "",
+                move_number,
+                self.attacker_move,
+                indent = indent
+            );
+        }
+        writeln!(
+            f,
+            "{:indent$}{}. {}",
+            "",
+            move_number,
+            self.attacker_move,
+            indent = indent
+        )?;
+        for (defence, solution) in &self.defences {
+            writeln!(
+                f,
+                "{:indent$}{}... {}",
+                "",
+                move_number,
+                defence,
+                indent = indent + 2
+            )?;
+            solution.write_tree(f, move_number + 1, indent + 4)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for MateSolution {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write_tree(f, 1, 0)
+    }
+}
+
+/// Proves or refutes forced mates by trying every move, without the evaluation, pruning or
+/// move ordering of the regular search, so the answer is exact.
+pub struct MateSolver {
+    board: ChessBoard,
+    checks_only: bool,
+    nodes: u64,
+    //Positions (with the mate length tried) already shown not to be a forced mate
+    refuted: HashSet<(u64, u32)>,
+}
+
+impl MateSolver {
+    /// With `checks_only`, the attacker may only play moves that give check, as some problem
+    /// genres require.
+    pub fn new(board: &ChessBoard, checks_only: bool) -> Self {
+        Self {
+            board: board.clone(),
+            checks_only,
+            nodes: 0,
+            refuted: HashSet::new(),
+        }
+    }
+    pub fn nodes(&self) -> u64 {
+        self.nodes
+    }
+    /// Finds a mate in at most `moves` moves for the side to move, with every defence answered
+    /// by the shortest mate left.
+    pub fn solve(&mut self, moves: u32) -> Option<MateSolution> {
+        (1..=moves).find_map(|moves| self.mate_in(moves))
+    }
+    fn mate_in(&mut self, moves: u32) -> Option<MateSolution> {
+        let key = (self.board.zobrist_key, moves);
+        if self.refuted.contains(&key) {
+            return None;
+        }
+
+        for attacker_move in self.board.generate_legal_moves() {
+            self.nodes += 1;
+            self.board.make_move_unchecked(&attacker_move);
+            let solution = if self.checks_only && !self.board.in_check() {
+                None
+            } else {
+                self.refute_all_defences(moves)
+            };
+            self.board.unmake_move(&attacker_move);
+
+            if let Some(defences) = solution {
+                return Some(MateSolution {
+                    attacker_move,
+                    defences,
+                });
+            }
+        }
+
+        self.refuted.insert(key);
+        None
+    }
+    //Called after the attacker's move, gives the mates against every defence if there are
+    //any, with `moves` counting the move just played
+    fn refute_all_defences(&mut self, moves: u32) -> Option<Vec<(ChessMove, MateSolution)>> {
+        let defences = self.board.generate_legal_moves();
+        if defences.is_empty() {
+            //Stalemate is no mate
+            return self.board.in_check().then(Vec::new);
+        }
+        if moves == 1 {
+            return None;
+        }
+
+        let mut solutions = Vec::with_capacity(defences.len());
+        for defence in defences {
+            self.nodes += 1;
+            self.board.make_move_unchecked(&defence);
+            let solution = (1..moves).find_map(|moves| self.mate_in(moves));
+            self.board.unmake_move(&defence);
+
+            solutions.push((defence, solution?));
+        }
+        Some(solutions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solve(fen: &str, moves: u32, checks_only: bool) -> Option<(String, u32)> {
+        let board = ChessBoard::new_from_fen(fen).unwrap();
+        MateSolver::new(&board, checks_only)
+            .solve(moves)
+            .map(|solution| (solution.attacker_move.to_string(), solution.length()))
+    }
+
+    #[test]
+    fn finds_mate_in_two() {
+        //Morphy: 1. Ra6! bxa6 2. b7#
+        let fen = "kbK5/pp6/1P6/8/8/8/8/R7 w - - 0 1";
+        assert_eq!(solve(fen, 2, false), Some(("a1a6".to_string(), 2)));
+        assert_eq!(solve(fen, 1, false), None);
+    }
+
+    #[test]
+    fn finds_mate_in_three() {
+        let fen = "r1b1kb1r/pppp1ppp/5q2/4n3/3KP3/2N3PN/PPP4P/R1BQ1B1R b kq - 0 1";
+        assert_eq!(solve(fen, 3, false), Some(("f8c5".to_string(), 3)));
+        assert_eq!(solve(fen, 3, true), Some(("f8c5".to_string(), 3)));
+        assert_eq!(solve(fen, 2, false), None);
+    }
+
+    #[test]
+    fn rejects_positions_without_mate() {
+        let fen = "8/8/8/4k3/8/8/8/R3K3 w - - 0 1";
+        assert_eq!(solve(fen, 3, false), None);
+        assert_eq!(solve(fen, 3, true), None);
+    }
+}
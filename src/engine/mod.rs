@@ -0,0 +1,25 @@
+pub mod batch;
+pub mod book_builder;
+pub mod datagen;
+pub mod elo;
+pub mod endgame_table;
+pub mod evaluation;
+pub mod match_runner;
+pub mod mate_solver;
+pub mod move_ordering;
+pub mod nnue;
+pub mod opening_book;
+pub mod pawn_table;
+pub mod polyglot;
+pub mod score;
+pub mod search;
+pub mod search_thread;
+pub mod see;
+pub mod skill;
+pub mod syzygy;
+pub mod test_suite;
+pub mod time_manager;
+pub mod transposition_table;
+pub mod tuner;
+
+pub const MAX_PLY: usize = 128;
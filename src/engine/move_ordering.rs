@@ -0,0 +1,266 @@
+use crate::chess::chess_board::ChessBoard;
+use crate::chess::chess_color::ChessColor;
+use crate::chess::chess_move::ChessMove;
+use crate::chess::chess_piece::ChessPiece;
+use crate::chess::{CHESS_BOARD_SIZE, CHESS_COLORS};
+use crate::engine::see::see;
+use crate::engine::MAX_PLY;
+
+// Indexed by [victim][attacker], pawn = 0 .. king = 5
+const MVV_LVA: [[i32; 6]; 6] = [
+    [15, 14, 13, 12, 11, 10],
+    [25, 24, 23, 22, 21, 20],
+    [35, 34, 33, 32, 31, 30],
+    [45, 44, 43, 42, 41, 40],
+    [55, 54, 53, 52, 51, 50],
+    [65, 64, 63, 62, 61, 60],
+];
+
+const HISTORY_MAX: i32 = 16384;
+
+pub fn mvv_lva(board: &ChessBoard, chess_move: &ChessMove) -> i32 {
+    let (start_sq, end_sq, _flags) = chess_move.get_idx();
+    let attacker = board.layout[start_sq as usize];
+    let victim = board.layout[end_sq as usize];
+
+    //En passant captures land on an empty square
+    let victim_idx = if victim.is_empty() {
+        ChessPiece::PAWN.get_type_idx()
+    } else {
+        victim.get_type_idx()
+    };
+    MVV_LVA[victim_idx][attacker.get_type_idx()]
+}
+
+pub struct KillerMoves {
+    moves: [[Option<ChessMove>; 2]; MAX_PLY],
+}
+
+impl KillerMoves {
+    pub fn new() -> Self {
+        Self {
+            moves: [[None; 2]; MAX_PLY],
+        }
+    }
+    pub fn store(&mut self, ply: usize, chess_move: ChessMove) {
+        if ply >= MAX_PLY || self.moves[ply][0] == Some(chess_move) {
+            return;
+        }
+        self.moves[ply][1] = self.moves[ply][0];
+        self.moves[ply][0] = Some(chess_move);
+    }
+    pub fn get(&self, ply: usize) -> [Option<ChessMove>; 2] {
+        if ply >= MAX_PLY {
+            return [None; 2];
+        }
+        self.moves[ply]
+    }
+    pub fn clear(&mut self) {
+        self.moves = [[None; 2]; MAX_PLY];
+    }
+}
+
+impl Default for KillerMoves {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Butterfly history: a score per side, start square and end square for
+/// quiet moves that caused cutoffs.
+pub struct HistoryTable {
+    scores: [[[i32; CHESS_BOARD_SIZE]; CHESS_BOARD_SIZE]; CHESS_COLORS],
+}
+
+impl HistoryTable {
+    pub fn new() -> Self {
+        Self {
+            scores: [[[0; CHESS_BOARD_SIZE]; CHESS_BOARD_SIZE]; CHESS_COLORS],
+        }
+    }
+    #[inline(always)]
+    pub fn get(&self, color: ChessColor, chess_move: &ChessMove) -> i32 {
+        let (start_sq, end_sq, _flags) = chess_move.get_idx();
+        self.scores[color.get_idx()][start_sq as usize][end_sq as usize]
+    }
+    pub fn reward(&mut self, color: ChessColor, chess_move: &ChessMove, depth: i32) {
+        self.apply_bonus(color, chess_move, depth * depth);
+    }
+    pub fn penalize(&mut self, color: ChessColor, chess_move: &ChessMove, depth: i32) {
+        self.apply_bonus(color, chess_move, -depth * depth);
+    }
+    //Scales the bonus down as the entry approaches HISTORY_MAX so scores never overflow
+    fn apply_bonus(&mut self, color: ChessColor, chess_move: &ChessMove, bonus: i32) {
+        let (start_sq, end_sq, _flags) = chess_move.get_idx();
+        let bonus = bonus.clamp(-HISTORY_MAX, HISTORY_MAX);
+        let entry = &mut self.scores[color.get_idx()][start_sq as usize][end_sq as usize];
+        *entry += bonus - *entry * bonus.abs() / HISTORY_MAX;
+    }
+    pub fn age(&mut self) {
+        for color_scores in self.scores.iter_mut() {
+            for start_scores in color_scores.iter_mut() {
+                for score in start_scores.iter_mut() {
+                    *score /= 2;
+                }
+            }
+        }
+    }
+    pub fn clear(&mut self) {
+        self.scores = [[[0; CHESS_BOARD_SIZE]; CHESS_BOARD_SIZE]; CHESS_COLORS];
+    }
+}
+
+impl Default for HistoryTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PickerStage {
+    HashMove,
+    GoodCaptures,
+    Killers,
+    Quiets,
+    BadCaptures,
+    Done,
+}
+
+struct ScoredMove {
+    chess_move: ChessMove,
+    score: i32,
+}
+
+/// Hands out moves one at a time in the order hash move, captures that don't
+/// lose material by MVV-LVA, killer moves, quiet moves by history score and
+/// finally the losing captures. Each stage only does its work once the
+/// previous one is exhausted, so a cutoff on an early move skips scoring and
+/// selecting the rest.
+pub struct MovePicker {
+    stage: PickerStage,
+    moves: Vec<ChessMove>,
+    hash_move: Option<ChessMove>,
+    killers: [Option<ChessMove>; 2],
+    killer_idx: usize,
+    captures: Vec<ScoredMove>,
+    bad_captures: Vec<ScoredMove>,
+    quiets: Vec<ScoredMove>,
+    captures_only: bool,
+}
+
+impl MovePicker {
+    pub fn new(
+        moves: Vec<ChessMove>,
+        hash_move: Option<ChessMove>,
+        killers: [Option<ChessMove>; 2],
+    ) -> Self {
+        Self {
+            stage: PickerStage::HashMove,
+            moves,
+            hash_move,
+            killers,
+            killer_idx: 0,
+            captures: vec![],
+            bad_captures: vec![],
+            quiets: vec![],
+            captures_only: false,
+        }
+    }
+    /// A picker for the quiescence search that only yields captures which don't lose material.
+    pub fn new_captures(moves: Vec<ChessMove>) -> Self {
+        let mut instance = Self::new(moves, None, [None; 2]);
+        instance.captures_only = true;
+        instance
+    }
+    pub fn next(&mut self, board: &ChessBoard, history: &HistoryTable) -> Option<ChessMove> {
+        loop {
+            match self.stage {
+                PickerStage::HashMove => {
+                    self.stage = PickerStage::GoodCaptures;
+                    if let Some(hash_move) = self.hash_move {
+                        if let Some(idx) = self.moves.iter().position(|&m| m == hash_move) {
+                            return Some(self.moves.swap_remove(idx));
+                        }
+                        self.hash_move = None;
+                    }
+                }
+                PickerStage::GoodCaptures => {
+                    if self.captures.is_empty() && !self.moves.is_empty() {
+                        self.partition_moves(board);
+                    }
+                    if let Some(idx) = Self::best_idx(&self.captures) {
+                        let scored_move = self.captures.swap_remove(idx);
+                        if see(board, scored_move.chess_move) < 0 {
+                            self.bad_captures.push(scored_move);
+                            continue;
+                        }
+                        return Some(scored_move.chess_move);
+                    }
+                    self.stage = if self.captures_only {
+                        PickerStage::Done
+                    } else {
+                        PickerStage::Killers
+                    };
+                }
+                PickerStage::Killers => {
+                    if self.killer_idx >= self.killers.len() {
+                        self.stage = PickerStage::Quiets;
+                        for scored_move in self.quiets.iter_mut() {
+                            scored_move.score =
+                                history.get(board.color_to_move, &scored_move.chess_move);
+                        }
+                        continue;
+                    }
+                    let killer = self.killers[self.killer_idx];
+                    self.killer_idx += 1;
+                    if let Some(killer) = killer {
+                        if let Some(idx) = self.quiets.iter().position(|m| m.chess_move == killer) {
+                            return Some(self.quiets.swap_remove(idx).chess_move);
+                        }
+                    }
+                }
+                PickerStage::Quiets => {
+                    if let Some(idx) = Self::best_idx(&self.quiets) {
+                        return Some(self.quiets.swap_remove(idx).chess_move);
+                    }
+                    self.stage = PickerStage::BadCaptures;
+                }
+                PickerStage::BadCaptures => {
+                    if let Some(idx) = Self::best_idx(&self.bad_captures) {
+                        return Some(self.bad_captures.swap_remove(idx).chess_move);
+                    }
+                    self.stage = PickerStage::Done;
+                }
+                PickerStage::Done => return None,
+            }
+        }
+    }
+    fn partition_moves(&mut self, board: &ChessBoard) {
+        for chess_move in self.moves.drain(..) {
+            if board.is_capture(&chess_move) {
+                self.captures.push(ScoredMove {
+                    chess_move,
+                    score: mvv_lva(board, &chess_move),
+                });
+            } else {
+                self.quiets.push(ScoredMove {
+                    chess_move,
+                    score: 0,
+                });
+            }
+        }
+    }
+    //Selection instead of a full sort: only the moves actually searched are ever ordered
+    fn best_idx(moves: &[ScoredMove]) -> Option<usize> {
+        if moves.is_empty() {
+            return None;
+        }
+        let mut best_idx = 0;
+        for idx in 1..moves.len() {
+            if moves[idx].score > moves[best_idx].score {
+                best_idx = idx;
+            }
+        }
+        Some(best_idx)
+    }
+}
@@ -0,0 +1,562 @@
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::chess::chess_board::ChessBoard;
+use crate::chess::chess_color::ChessColor;
+use crate::chess::chess_move::{ChessMove, MoveFlags};
+use crate::chess::chess_piece::ChessPiece;
+use crate::chess::CHESS_BOARD_WIDTH;
+use crate::engine::score::MATE_SCORE;
+use crate::engine::MAX_PLY;
+
+//An efficiently updatable network: one hidden layer seen from both sides, whose inputs are
+//(king square, piece, square) triples. Only a few inputs change per move, so the hidden layer
+//(the accumulator) is updated by adding and subtracting weight rows instead of recomputed.
+//
+//Network files are little endian:
+//
+//  magic        4 bytes   "JMNN"
+//  version      u32       1
+//  feature set  u32       0 = HalfKP, 1 = HalfKA
+//  hidden size  u32       a multiple of 16
+//  biases       i16 x hidden
+//  weights      i16 x inputs x hidden, one row of hidden values per input
+//  output       i16 x 2 x hidden, the side to move's half first
+//  output bias  i32
+//
+//Inputs are numbered king * kinds * 64 + kind * 64 + square from each side's own view, with
+//squares from a1 = 0 to h8 = 63 and the board flipped vertically for black. A piece's kind is
+//2 * type + 1 for the opponent's pieces, type running from pawn = 0 to king = 5. HalfKP leaves
+//kings out (10 kinds, 40960 inputs), HalfKA includes them (12 kinds, 49152 inputs).
+//
+//The accumulator is clipped to [0, 255] before the output layer, whose weights are scaled by 64,
+//so the evaluation in centipawns is (output + bias) * 400 / (255 * 64).
+
+const MAGIC: [u8; 4] = *b"JMNN";
+const VERSION: u32 = 1;
+const HEADER_SIZE: usize = 16;
+
+const ACTIVATION_MAX: i16 = 255;
+const OUTPUT_SCALE: i32 = 400;
+const WEIGHT_SCALE: i32 = 64;
+const SIMD_WIDTH: usize = 16;
+
+//Keeps network scores clear of the mate and tablebase range
+const MAX_EVAL: i32 = MATE_SCORE / 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeatureSet {
+    HalfKp,
+    HalfKa,
+}
+
+impl FeatureSet {
+    fn kinds(self) -> usize {
+        match self {
+            FeatureSet::HalfKp => 10,
+            FeatureSet::HalfKa => 12,
+        }
+    }
+    pub fn inputs(self) -> usize {
+        64 * self.kinds() * 64
+    }
+    //None for kings under HalfKP
+    fn index(
+        self,
+        perspective: ChessColor,
+        king: usize,
+        piece: ChessPiece,
+        idx: usize,
+    ) -> Option<usize> {
+        let piece_type = piece.get_type_idx();
+        if piece_type >= self.kinds() / 2 {
+            return None;
+        }
+        let kind = 2 * piece_type + !piece.has_color(perspective) as usize;
+        Some((orient(perspective, king) * self.kinds() + kind) * 64 + orient(perspective, idx))
+    }
+}
+
+//Board index to a square seen from `perspective`, a1 = 0 for white and a8 = 0 for black
+fn orient(perspective: ChessColor, idx: usize) -> usize {
+    if perspective == ChessColor::WHITE {
+        idx ^ 56
+    } else {
+        idx
+    }
+}
+
+/// Whether this CPU can run the AVX2 version of the network.
+pub fn simd_available() -> bool {
+    #[cfg(target_arch = "x86_64")]
+    {
+        is_x86_feature_detected!("avx2")
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        false
+    }
+}
+
+/// The weights of a network, loaded from a file in the format above.
+#[derive(Debug, Clone)]
+pub struct Network {
+    feature_set: FeatureSet,
+    hidden: usize,
+    biases: Vec<i16>,
+    weights: Vec<i16>,
+    output_weights: Vec<i16>,
+    output_bias: i32,
+}
+
+impl Network {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Self::from_bytes(&fs::read(path)?)
+            .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, path.display().to_string()))
+    }
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let word = |i: usize| {
+            Some(u32::from_le_bytes(
+                bytes.get(4 * i..4 * i + 4)?.try_into().ok()?,
+            ))
+        };
+        if bytes.get(0..4)? != MAGIC || word(1)? != VERSION {
+            return None;
+        }
+        let feature_set = match word(2)? {
+            0 => FeatureSet::HalfKp,
+            1 => FeatureSet::HalfKa,
+            _ => return None,
+        };
+        let hidden = word(3)? as usize;
+        if hidden == 0 || !hidden.is_multiple_of(SIMD_WIDTH) {
+            return None;
+        }
+
+        let inputs = feature_set.inputs();
+        let values = hidden + inputs * hidden + 2 * hidden;
+        let body = bytes.get(HEADER_SIZE..)?;
+        if body.len() != 2 * values + 4 {
+            return None;
+        }
+        let mut values: Vec<i16> = body[..2 * values]
+            .chunks_exact(2)
+            .map(|chunk| i16::from_le_bytes([chunk[0], chunk[1]]))
+            .collect();
+        let output_bias = i32::from_le_bytes(body[body.len() - 4..].try_into().ok()?);
+        let output_weights = values.split_off(hidden + inputs * hidden);
+        let weights = values.split_off(hidden);
+
+        Some(Self {
+            feature_set,
+            hidden,
+            biases: values,
+            weights,
+            output_weights,
+            output_bias,
+        })
+    }
+    pub fn feature_set(&self) -> FeatureSet {
+        self.feature_set
+    }
+    pub fn hidden_size(&self) -> usize {
+        self.hidden
+    }
+    fn row(&self, feature: usize) -> &[i16] {
+        &self.weights[feature * self.hidden..(feature + 1) * self.hidden]
+    }
+    //Recomputes one side's half of the accumulator from scratch
+    fn refresh(&self, board: &ChessBoard, perspective: ChessColor, values: &mut [i16], simd: bool) {
+        values.copy_from_slice(&self.biases);
+        let Some(king) = board.king_square(perspective) else {
+            return;
+        };
+        for (idx, &piece) in board.layout.iter().enumerate() {
+            if piece.is_empty() {
+                continue;
+            }
+            if let Some(feature) = self.feature_set.index(perspective, king, piece, idx) {
+                add_row(values, self.row(feature), simd);
+            }
+        }
+    }
+    fn output(&self, us: &[i16], them: &[i16], simd: bool) -> i32 {
+        let (our_weights, their_weights) = self.output_weights.split_at(self.hidden);
+        let sum = clipped_dot(us, our_weights, simd) as i64
+            + clipped_dot(them, their_weights, simd) as i64
+            + self.output_bias as i64;
+        let eval = sum * OUTPUT_SCALE as i64 / (ACTIVATION_MAX as i64 * WEIGHT_SCALE as i64);
+        eval.clamp(-MAX_EVAL as i64, MAX_EVAL as i64) as i32
+    }
+    /// Evaluates `board` for the side to move without any incremental state.
+    pub fn evaluate(&self, board: &ChessBoard, simd: bool) -> i32 {
+        let simd = simd && simd_available();
+        let mut accumulator = Accumulator::new(self.hidden);
+        for color in [ChessColor::WHITE, ChessColor::BLACK] {
+            self.refresh(board, color, accumulator.side_mut(color), simd);
+        }
+        let us = board.color_to_move;
+        self.output(accumulator.side(us), accumulator.side(us.opposite()), simd)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Accumulator {
+    //White's half, then black's
+    values: Vec<i16>,
+}
+
+impl Accumulator {
+    fn new(hidden: usize) -> Self {
+        Self {
+            values: vec![0; 2 * hidden],
+        }
+    }
+    fn side(&self, color: ChessColor) -> &[i16] {
+        let hidden = self.values.len() / 2;
+        &self.values[color.get_idx() * hidden..(color.get_idx() + 1) * hidden]
+    }
+    fn side_mut(&mut self, color: ChessColor) -> &mut [i16] {
+        let hidden = self.values.len() / 2;
+        &mut self.values[color.get_idx() * hidden..(color.get_idx() + 1) * hidden]
+    }
+}
+
+/// The pieces a move puts on or takes off the board, worked out before it is made.
+#[derive(Debug, Clone, Copy)]
+pub struct PieceChanges {
+    //Piece, board index and whether it is added
+    changes: [(ChessPiece, usize, bool); 4],
+    len: usize,
+    king_moved: Option<ChessColor>,
+}
+
+impl PieceChanges {
+    pub fn new(board: &ChessBoard, chess_move: &ChessMove) -> Self {
+        let (start_sq, end_sq, flags) = chess_move.get_idx();
+        let (start_idx, end_idx) = (start_sq as usize, end_sq as usize);
+        let piece = board.layout[start_idx];
+        let mut changes = Self {
+            changes: [(ChessPiece::empty(), 0, false); 4],
+            len: 0,
+            king_moved: None,
+        };
+
+        changes.push(piece, start_idx, false);
+        let captured_piece = board.layout[end_idx];
+        if !captured_piece.is_empty() {
+            changes.push(captured_piece, end_idx, false);
+        }
+        match flags {
+            MoveFlags::EN_PASSANT => {
+                let captured_idx = (start_idx / CHESS_BOARD_WIDTH) * CHESS_BOARD_WIDTH
+                    + end_idx % CHESS_BOARD_WIDTH;
+                changes.push(board.layout[captured_idx], captured_idx, false);
+            }
+            MoveFlags::CASTLE_SHORT => {
+                let rook = board.layout[start_idx + 3];
+                changes.push(rook, start_idx + 3, false);
+                changes.push(rook, start_idx + 1, true);
+            }
+            MoveFlags::CASTLE_LONG => {
+                let rook = board.layout[start_idx - 4];
+                changes.push(rook, start_idx - 4, false);
+                changes.push(rook, start_idx - 1, true);
+            }
+            _ => (),
+        }
+        let end_piece = match chess_move.get_promotion() {
+            Some(promotion) => promotion | board.color_to_move.to_piece(),
+            None => piece,
+        };
+        changes.push(end_piece, end_idx, true);
+        if piece.contains(ChessPiece::KING) {
+            changes.king_moved = Some(board.color_to_move);
+        }
+        changes
+    }
+    fn push(&mut self, piece: ChessPiece, idx: usize, added: bool) {
+        //A castling move can add 2 pieces and remove 2, nothing else needs more
+        if self.len < self.changes.len() {
+            self.changes[self.len] = (piece, idx, added);
+            self.len += 1;
+        }
+    }
+}
+
+/// A network with a stack of accumulators, one per ply of the line being searched.
+#[derive(Debug, Clone)]
+pub struct NnueState {
+    network: Arc<Network>,
+    stack: Vec<Accumulator>,
+    current: usize,
+    simd: bool,
+}
+
+impl NnueState {
+    pub fn new(network: Arc<Network>, simd: bool) -> Self {
+        Self {
+            stack: vec![Accumulator::new(network.hidden); MAX_PLY + 1],
+            network,
+            current: 0,
+            simd: simd && simd_available(),
+        }
+    }
+    pub fn network(&self) -> &Arc<Network> {
+        &self.network
+    }
+    pub fn simd(&self) -> bool {
+        self.simd
+    }
+    /// Starts a new line at `board`.
+    pub fn reset(&mut self, board: &ChessBoard) {
+        self.current = 0;
+        for color in [ChessColor::WHITE, ChessColor::BLACK] {
+            let values = self.stack[0].side_mut(color);
+            self.network.refresh(board, color, values, self.simd);
+        }
+    }
+    /// Updates the accumulator for a move, `board` being the position after it.
+    pub fn push(&mut self, changes: &PieceChanges, board: &ChessBoard) {
+        let (done, rest) = self.stack.split_at_mut(self.current + 1);
+        let (parent, child) = (&done[self.current], &mut rest[0]);
+        self.current += 1;
+        for color in [ChessColor::WHITE, ChessColor::BLACK] {
+            let values = child.side_mut(color);
+            //Every input depends on the own king's square
+            if changes.king_moved == Some(color) {
+                self.network.refresh(board, color, values, self.simd);
+                continue;
+            }
+            values.copy_from_slice(parent.side(color));
+            let Some(king) = board.king_square(color) else {
+                continue;
+            };
+            for &(piece, idx, added) in &changes.changes[..changes.len] {
+                let Some(feature) = self.network.feature_set.index(color, king, piece, idx) else {
+                    continue;
+                };
+                let row = self.network.row(feature);
+                if added {
+                    add_row(values, row, self.simd);
+                } else {
+                    sub_row(values, row, self.simd);
+                }
+            }
+        }
+    }
+    pub fn pop(&mut self) {
+        self.current -= 1;
+    }
+    /// The evaluation for `color_to_move`, which must match the position of the last push.
+    pub fn evaluate(&self, color_to_move: ChessColor) -> i32 {
+        let accumulator = &self.stack[self.current];
+        self.network.output(
+            accumulator.side(color_to_move),
+            accumulator.side(color_to_move.opposite()),
+            self.simd,
+        )
+    }
+}
+
+fn add_row(values: &mut [i16], row: &[i16], simd: bool) {
+    #[cfg(target_arch = "x86_64")]
+    if simd {
+        //Safety: simd is only set when AVX2 was detected, lengths are multiples of 16
+        unsafe { avx2::add_row(values, row) };
+        return;
+    }
+    let _ = simd;
+    for (value, weight) in values.iter_mut().zip(row) {
+        *value = value.wrapping_add(*weight);
+    }
+}
+
+fn sub_row(values: &mut [i16], row: &[i16], simd: bool) {
+    #[cfg(target_arch = "x86_64")]
+    if simd {
+        //Safety: as in add_row
+        unsafe { avx2::sub_row(values, row) };
+        return;
+    }
+    let _ = simd;
+    for (value, weight) in values.iter_mut().zip(row) {
+        *value = value.wrapping_sub(*weight);
+    }
+}
+
+//Sum of clamp(value, 0, 255) * weight
+fn clipped_dot(values: &[i16], weights: &[i16], simd: bool) -> i32 {
+    #[cfg(target_arch = "x86_64")]
+    if simd {
+        //Safety: as in add_row
+        return unsafe { avx2::clipped_dot(values, weights) };
+    }
+    let _ = simd;
+    values
+        .iter()
+        .zip(weights)
+        .map(|(&value, &weight)| value.clamp(0, ACTIVATION_MAX) as i32 * weight as i32)
+        .sum()
+}
+
+#[cfg(target_arch = "x86_64")]
+mod avx2 {
+    use std::arch::x86_64::*;
+
+    use super::{ACTIVATION_MAX, SIMD_WIDTH};
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn add_row(values: &mut [i16], row: &[i16]) {
+        for (value, weight) in values
+            .chunks_exact_mut(SIMD_WIDTH)
+            .zip(row.chunks_exact(SIMD_WIDTH))
+        {
+            let sum = _mm256_add_epi16(
+                _mm256_loadu_si256(value.as_ptr().cast()),
+                _mm256_loadu_si256(weight.as_ptr().cast()),
+            );
+            _mm256_storeu_si256(value.as_mut_ptr().cast(), sum);
+        }
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn sub_row(values: &mut [i16], row: &[i16]) {
+        for (value, weight) in values
+            .chunks_exact_mut(SIMD_WIDTH)
+            .zip(row.chunks_exact(SIMD_WIDTH))
+        {
+            let difference = _mm256_sub_epi16(
+                _mm256_loadu_si256(value.as_ptr().cast()),
+                _mm256_loadu_si256(weight.as_ptr().cast()),
+            );
+            _mm256_storeu_si256(value.as_mut_ptr().cast(), difference);
+        }
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn clipped_dot(values: &[i16], weights: &[i16]) -> i32 {
+        let zero = _mm256_setzero_si256();
+        let max = _mm256_set1_epi16(ACTIVATION_MAX);
+        let mut sum = _mm256_setzero_si256();
+        for (value, weight) in values
+            .chunks_exact(SIMD_WIDTH)
+            .zip(weights.chunks_exact(SIMD_WIDTH))
+        {
+            let clipped = _mm256_min_epi16(
+                _mm256_max_epi16(_mm256_loadu_si256(value.as_ptr().cast()), zero),
+                max,
+            );
+            //Pairs of 16 bit products summed into 32 bits, 255 * 32767 * 2 can't overflow
+            let products = _mm256_madd_epi16(clipped, _mm256_loadu_si256(weight.as_ptr().cast()));
+            sum = _mm256_add_epi32(sum, products);
+        }
+        let halves = _mm_add_epi32(
+            _mm256_castsi256_si128(sum),
+            _mm256_extracti128_si256(sum, 1),
+        );
+        let pairs = _mm_add_epi32(halves, _mm_shuffle_epi32(halves, 0b01_00_11_10));
+        let total = _mm_add_epi32(pairs, _mm_shuffle_epi32(pairs, 0b10_11_00_01));
+        _mm_cvtsi128_si32(total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::chess_random::ChessRandom;
+
+    const HIDDEN: usize = 16;
+
+    //A network file with small seeded weights, so accumulators stay in range but some values
+    //are clipped on both ends
+    fn network_bytes(feature_set: FeatureSet, seed: u64) -> Vec<u8> {
+        let mut random = ChessRandom::new(seed);
+        let mut weight = |range: usize| random.below(2 * range) as i16 - range as i16;
+        let mut bytes = MAGIC.to_vec();
+        let feature_code = match feature_set {
+            FeatureSet::HalfKp => 0u32,
+            FeatureSet::HalfKa => 1,
+        };
+        for word in [VERSION, feature_code, HIDDEN as u32] {
+            bytes.extend(word.to_le_bytes());
+        }
+        let values = HIDDEN + feature_set.inputs() * HIDDEN + 2 * HIDDEN;
+        for i in 0..values {
+            let range = if i < HIDDEN { 200 } else { 64 };
+            bytes.extend(weight(range).to_le_bytes());
+        }
+        bytes.extend(1234i32.to_le_bytes());
+        bytes
+    }
+
+    fn network(feature_set: FeatureSet) -> Network {
+        Network::from_bytes(&network_bytes(feature_set, 0x4E4E5545)).unwrap()
+    }
+
+    //Castling both ways, en passant, a capturing promotion and king moves
+    const FEN: &str = "r3k2r/6P1/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1";
+    const LINE: [&str; 8] = [
+        "e5d6", "e8c8", "e1g1", "c8b8", "g7h8q", "d8h8", "g1g2", "h8h2",
+    ];
+
+    #[test]
+    fn incremental_updates_match_full_evaluation() {
+        for feature_set in [FeatureSet::HalfKp, FeatureSet::HalfKa] {
+            let network = Arc::new(network(feature_set));
+            let mut evaluations = vec![];
+            for simd in [false, true] {
+                let mut board = ChessBoard::new_from_fen(FEN).unwrap();
+                let mut state = NnueState::new(network.clone(), simd);
+                state.reset(&board);
+                let mut line = vec![network.evaluate(&board, simd)];
+                assert_eq!(state.evaluate(board.color_to_move), line[0]);
+                let mut played = vec![];
+                for notation in LINE {
+                    let chess_move = board.find_move(notation).unwrap();
+                    let changes = PieceChanges::new(&board, &chess_move);
+                    board.make_move_unchecked(&chess_move);
+                    state.push(&changes, &board);
+                    let evaluation = network.evaluate(&board, simd);
+                    assert_eq!(
+                        state.evaluate(board.color_to_move),
+                        evaluation,
+                        "{notation}"
+                    );
+                    line.push(evaluation);
+                    played.push(chess_move);
+                }
+                //Going back returns the earlier accumulators
+                for (chess_move, &evaluation) in played.iter().rev().zip(line.iter().rev().skip(1))
+                {
+                    board.unmake_move(chess_move);
+                    state.pop();
+                    assert_eq!(state.evaluate(board.color_to_move), evaluation);
+                }
+                evaluations.push(line);
+            }
+            assert_eq!(evaluations[0], evaluations[1]);
+            //The weights are random enough that the line isn't evaluated the same throughout
+            assert!(evaluations[0].iter().any(|&e| e != evaluations[0][0]));
+        }
+    }
+
+    #[test]
+    fn rejects_broken_files() {
+        let bytes = network_bytes(FeatureSet::HalfKp, 1);
+        assert!(Network::from_bytes(&bytes).is_some());
+        assert!(Network::from_bytes(&bytes[..bytes.len() - 1]).is_none());
+        assert!(Network::from_bytes(&bytes[..HEADER_SIZE - 1]).is_none());
+        let mut longer = bytes.clone();
+        longer.push(0);
+        assert!(Network::from_bytes(&longer).is_none());
+        let mut wrong_hidden = bytes.clone();
+        wrong_hidden[12] = 15;
+        assert!(Network::from_bytes(&wrong_hidden).is_none());
+        let mut wrong_magic = bytes;
+        wrong_magic[0] = b'X';
+        assert!(Network::from_bytes(&wrong_magic).is_none());
+    }
+}
@@ -0,0 +1,130 @@
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::Path;
+
+use crate::chess::chess_board::ChessBoard;
+use crate::chess::chess_move::ChessMove;
+use crate::chess::chess_random::ChessRandom;
+use crate::engine::polyglot::{decode_move, polyglot_key};
+
+//Key, move, weight and learn value, all big endian
+const ENTRY_SIZE: usize = 16;
+
+/// One record of a Polyglot book.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BookEntry {
+    pub key: u64,
+    pub polyglot_move: u16,
+    pub weight: u16,
+    pub learn: u32,
+}
+
+impl BookEntry {
+    fn from_bytes(bytes: &[u8]) -> Self {
+        Self {
+            key: u64::from_be_bytes(bytes[0..8].try_into().unwrap()),
+            polyglot_move: u16::from_be_bytes(bytes[8..10].try_into().unwrap()),
+            weight: u16::from_be_bytes(bytes[10..12].try_into().unwrap()),
+            learn: u32::from_be_bytes(bytes[12..16].try_into().unwrap()),
+        }
+    }
+    fn to_bytes(self) -> [u8; ENTRY_SIZE] {
+        let mut bytes = [0; ENTRY_SIZE];
+        bytes[0..8].copy_from_slice(&self.key.to_be_bytes());
+        bytes[8..10].copy_from_slice(&self.polyglot_move.to_be_bytes());
+        bytes[10..12].copy_from_slice(&self.weight.to_be_bytes());
+        bytes[12..16].copy_from_slice(&self.learn.to_be_bytes());
+        bytes
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BookSelection {
+    /// Always the move with the highest weight.
+    Best,
+    /// A move picked with a probability proportional to its weight.
+    #[default]
+    WeightedRandom,
+}
+
+/// A Polyglot `.bin` opening book, kept in memory as the entries sorted by key.
+#[derive(Debug, Clone, Default)]
+pub struct OpeningBook {
+    entries: Vec<BookEntry>,
+}
+
+impl OpeningBook {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let data = fs::read(path)?;
+        if data.len() % ENTRY_SIZE != 0 {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "Polyglot book size is not a multiple of 16 bytes",
+            ));
+        }
+        let entries = data
+            .chunks_exact(ENTRY_SIZE)
+            .map(BookEntry::from_bytes)
+            .collect();
+        Ok(Self::from_entries(entries))
+    }
+    pub fn from_entries(mut entries: Vec<BookEntry>) -> Self {
+        //Books should already be sorted, but a binary search can't rely on it
+        if !entries.is_sorted_by_key(|entry| entry.key) {
+            entries.sort_by_key(|entry| entry.key);
+        }
+        Self { entries }
+    }
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let data: Vec<u8> = self
+            .entries
+            .iter()
+            .flat_map(|entry| entry.to_bytes())
+            .collect();
+        fs::write(path, data)
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    fn entries_for(&self, key: u64) -> &[BookEntry] {
+        let start = self.entries.partition_point(|entry| entry.key < key);
+        let end = self.entries.partition_point(|entry| entry.key <= key);
+        &self.entries[start..end]
+    }
+    /// The book moves for the position with their weights and learn values, best first.
+    /// Entries that don't decode to a legal move are left out.
+    pub fn moves(&self, board: &ChessBoard) -> Vec<(ChessMove, BookEntry)> {
+        let mut moves: Vec<(ChessMove, BookEntry)> = self
+            .entries_for(polyglot_key(board))
+            .iter()
+            .filter_map(|entry| Some((decode_move(board, entry.polyglot_move)?, *entry)))
+            .collect();
+        moves.sort_by_key(|(_chess_move, entry)| std::cmp::Reverse(entry.weight));
+        moves
+    }
+    pub fn choose(&self, board: &ChessBoard, selection: BookSelection) -> Option<ChessMove> {
+        let moves = self.moves(board);
+        let total_weight: u64 = moves.iter().map(|(_, entry)| entry.weight as u64).sum();
+        //Moves with weight 0 are in the book only to be recognised, not to be played
+        if total_weight == 0 {
+            return None;
+        }
+        match selection {
+            BookSelection::Best => moves.first().map(|(chess_move, _)| *chess_move),
+            BookSelection::WeightedRandom => {
+                //Good enough to vary the opening from game to game
+                let mut pick = ChessRandom::from_clock().next() % total_weight;
+                for (chess_move, entry) in moves {
+                    if pick < entry.weight as u64 {
+                        return Some(chess_move);
+                    }
+                    pick -= entry.weight as u64;
+                }
+                None
+            }
+        }
+    }
+}
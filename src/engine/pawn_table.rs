@@ -0,0 +1,61 @@
+use crate::chess::chess_board::ChessBoard;
+use crate::engine::evaluation::PawnStructure;
+
+const PAWN_TABLE_ENTRIES: usize = 1 << 14;
+
+/// Caches the pawn structure by `ChessBoard::pawn_key`. Pawns move rarely, so most positions of
+/// a search share their structure with one evaluated before. Every search thread keeps its own
+/// table, so there is no locking.
+pub struct PawnTable {
+    entries: Vec<Option<(u64, PawnStructure)>>,
+    probes: u64,
+    hits: u64,
+}
+
+impl PawnTable {
+    pub fn new() -> Self {
+        Self {
+            entries: vec![None; PAWN_TABLE_ENTRIES],
+            probes: 0,
+            hits: 0,
+        }
+    }
+    /// The pawn structure of the position, from the table if possible.
+    pub fn probe(&mut self, board: &ChessBoard) -> PawnStructure {
+        self.probes += 1;
+        let entry = &mut self.entries[(board.pawn_key % PAWN_TABLE_ENTRIES as u64) as usize];
+        match entry {
+            Some((key, pawns)) if *key == board.pawn_key => {
+                self.hits += 1;
+                *pawns
+            }
+            _ => {
+                let pawns = PawnStructure::new(board);
+                *entry = Some((board.pawn_key, pawns));
+                pawns
+            }
+        }
+    }
+    pub fn probes(&self) -> u64 {
+        self.probes
+    }
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+    /// Starts counting probes and hits anew, keeping the entries.
+    pub fn reset_stats(&mut self) {
+        self.probes = 0;
+        self.hits = 0;
+    }
+    pub fn clear(&mut self) {
+        self.entries.fill(None);
+        self.probes = 0;
+        self.hits = 0;
+    }
+}
+
+impl Default for PawnTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,97 @@
+use std::fmt;
+
+use crate::engine::MAX_PLY;
+
+//Search scores are plain centipawns, except for a band at both ends that holds mates. Being
+//mated at `ply` scores -(MATE_SCORE - ply), so a shorter mate is always preferred.
+pub const MATE_SCORE: i32 = 31000;
+const MATE_BOUND: i32 = MATE_SCORE - MAX_PLY as i32;
+//Tablebase wins rank below any mate the search has seen but above every evaluation
+const TB_WIN_SCORE: i32 = MATE_BOUND - MAX_PLY as i32 - 1;
+const TB_BOUND: i32 = TB_WIN_SCORE - MAX_PLY as i32;
+
+/// Score for the side to move delivering mate `ply` plies from the root.
+pub fn mate_in(ply: usize) -> i32 {
+    MATE_SCORE - ply as i32
+}
+
+/// Score for the side to move getting mated `ply` plies from the root.
+pub fn mated_in(ply: usize) -> i32 {
+    -MATE_SCORE + ply as i32
+}
+
+/// Score for a tablebase win reached `ply` plies from the root.
+pub fn tb_win_in(ply: usize) -> i32 {
+    TB_WIN_SCORE - ply as i32
+}
+
+pub fn tb_loss_in(ply: usize) -> i32 {
+    -TB_WIN_SCORE + ply as i32
+}
+
+pub fn is_mate_score(score: i32) -> bool {
+    score.abs() >= MATE_BOUND
+}
+
+/// Turns a mate or tablebase score relative to the root into one relative to the node at `ply`
+/// before it goes into the transposition table, where the same position can be reached at any ply.
+pub fn score_to_tt(score: i32, ply: usize) -> i32 {
+    if score >= TB_BOUND {
+        score + ply as i32
+    } else if score <= -TB_BOUND {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+/// Inverse of `score_to_tt` for a score read back at `ply`.
+pub fn score_from_tt(score: i32, ply: usize) -> i32 {
+    if score >= TB_BOUND {
+        score - ply as i32
+    } else if score <= -TB_BOUND {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+/// A search score as it is shown to the user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Score {
+    Centipawns(i32),
+    /// Mate in this many moves, negative when the side to move is the one getting mated.
+    Mate(i32),
+}
+
+impl Score {
+    pub fn new(score: i32) -> Self {
+        if score >= MATE_BOUND {
+            let plies = MATE_SCORE - score;
+            Score::Mate((plies + 1) / 2)
+        } else if score <= -MATE_BOUND {
+            let plies = MATE_SCORE + score;
+            Score::Mate(-(plies + 1) / 2)
+        } else {
+            Score::Centipawns(score)
+        }
+    }
+    /// The score in UCI notation, `cp <x>` or `mate <n>`.
+    pub fn to_uci(&self) -> String {
+        match self {
+            Score::Centipawns(centipawns) => format!("cp {}", centipawns),
+            Score::Mate(moves) => format!("mate {}", moves),
+        }
+    }
+}
+
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        //Padded as a whole so the score lines up in tables
+        let text = match self {
+            Score::Centipawns(centipawns) => centipawns.to_string(),
+            Score::Mate(moves) => format!("#{}", moves),
+        };
+        f.pad(&text)
+    }
+}
@@ -0,0 +1,1212 @@
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::chess::chess_board::ChessBoard;
+use crate::chess::chess_color::ChessColor;
+use crate::chess::chess_move::ChessMove;
+use crate::chess::chess_random::ChessRandom;
+use crate::engine::endgame_table::{Dtm, EndgameTables, MAX_TABLE_PIECES};
+use crate::engine::evaluation::{evaluate, game_phase, EvalWeights};
+use crate::engine::move_ordering::{HistoryTable, KillerMoves, MovePicker};
+use crate::engine::nnue::{simd_available, Network, NnueState, PieceChanges};
+use crate::engine::opening_book::{BookSelection, OpeningBook};
+use crate::engine::pawn_table::PawnTable;
+use crate::engine::score::{
+    is_mate_score, mate_in, mated_in, score_from_tt, score_to_tt, tb_loss_in, tb_win_in, Score,
+};
+use crate::engine::skill::{Skill, DEFAULT_ELO, MAX_ELO, MAX_SKILL_LEVEL, MIN_ELO, SKILL_LINES};
+use crate::engine::syzygy::{has_castling_rights, Tablebase, Wdl};
+use crate::engine::time_manager::{TimeControl, TimeManager};
+use crate::engine::transposition_table::{Bound, TranspositionTable};
+use crate::engine::MAX_PLY;
+
+pub const INFINITY: i32 = 32000;
+const DRAW_SCORE: i32 = 0;
+
+//How many nodes to search between checks of the clock
+const TIME_CHECK_INTERVAL: u64 = 2048;
+
+pub const MAX_THREADS: usize = 256;
+pub const MAX_TB_PIECES: usize = 7;
+pub const MAX_MULTI_PV: usize = 256;
+
+const NULL_MOVE_MIN_DEPTH: i32 = 3;
+const REVERSE_FUTILITY_MAX_DEPTH: i32 = 6;
+const REVERSE_FUTILITY_MARGIN: i32 = 80;
+// Indexed by remaining depth
+const FUTILITY_MARGINS: [i32; 4] = [0, 100, 200, 300];
+const LMR_MIN_DEPTH: i32 = 3;
+const LMR_MIN_MOVES: usize = 3;
+const ASPIRATION_MIN_DEPTH: i32 = 4;
+const ASPIRATION_WINDOW: i32 = 25;
+
+/// Switches for the selective parts of the search, so their effect can be measured one by one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchFeatures {
+    pub null_move_pruning: bool,
+    pub late_move_reductions: bool,
+    pub reverse_futility_pruning: bool,
+    pub futility_pruning: bool,
+    pub check_extensions: bool,
+}
+
+impl Default for SearchFeatures {
+    fn default() -> Self {
+        Self {
+            null_move_pruning: true,
+            late_move_reductions: true,
+            reverse_futility_pruning: true,
+            futility_pruning: true,
+            check_extensions: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SearchLimits {
+    pub depth: Option<i32>,
+    pub nodes: Option<u64>,
+    pub move_time: Option<Duration>,
+    pub time_control: Option<TimeControl>,
+    pub infinite: bool,
+    pub ponder: bool,
+    /// Root moves the search is restricted to, all legal moves when empty.
+    pub search_moves: Vec<ChessMove>,
+    /// Root moves left out of the search.
+    pub excluded_moves: Vec<ChessMove>,
+}
+
+impl SearchLimits {
+    /// Reads the arguments of a UCI `go` command, picking the clock of the side to move.
+    /// Besides `searchmoves`, moves to leave out can be listed after `excludemoves`.
+    pub fn from_go_args(args: &[&str], board: &ChessBoard) -> Self {
+        let mut limits = Self::default();
+        let (time_arg, increment_arg) = if board.color_to_move == ChessColor::WHITE {
+            ("wtime", "winc")
+        } else {
+            ("btime", "binc")
+        };
+        let mut time_left = None;
+        let mut increment = Duration::ZERO;
+        let mut moves_to_go = None;
+
+        let mut idx = 0;
+        while idx < args.len() {
+            let value = args
+                .get(idx + 1)
+                .and_then(|value| value.parse::<u64>().ok());
+            match args[idx] {
+                "infinite" => limits.infinite = true,
+                "ponder" => limits.ponder = true,
+                "depth" => limits.depth = value.map(|depth| depth as i32),
+                "nodes" => limits.nodes = value,
+                "movetime" => limits.move_time = value.map(Duration::from_millis),
+                "movestogo" => moves_to_go = value.map(|moves| moves as u32),
+                "searchmoves" | "excludemoves" => {
+                    //The list runs until the first argument that isn't a legal move
+                    let moves: Vec<ChessMove> = args[idx + 1..]
+                        .iter()
+                        .map_while(|arg| board.find_move(arg))
+                        .collect();
+                    let keyword = args[idx];
+                    idx += moves.len();
+                    if keyword == "searchmoves" {
+                        limits.search_moves = moves;
+                    } else {
+                        limits.excluded_moves = moves;
+                    }
+                }
+                arg if arg == time_arg => time_left = value.map(Duration::from_millis),
+                arg if arg == increment_arg => {
+                    increment = Duration::from_millis(value.unwrap_or(0));
+                }
+                _ => (),
+            }
+            idx += 1;
+        }
+
+        if let Some(time_left) = time_left {
+            limits.time_control = Some(TimeControl {
+                time_left,
+                increment,
+                moves_to_go,
+            });
+        }
+        limits
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchInfo {
+    pub depth: i32,
+    pub score: Score,
+    /// Whether `score` is exact or only a bound because the aspiration window failed.
+    pub bound: Bound,
+    pub nodes: u64,
+    pub time: Duration,
+    pub hashfull: usize,
+    pub tb_hits: u64,
+    /// Pawn hash table use of the main thread during this search.
+    pub pawn_hash_probes: u64,
+    pub pawn_hash_hits: u64,
+    /// Rank of the line among the MultiPV lines, from 1.
+    pub multipv: usize,
+    pub pv: Vec<ChessMove>,
+}
+
+impl SearchInfo {
+    pub fn nps(&self) -> u64 {
+        (self.nodes as f64 / self.time.as_secs_f64().max(0.001)) as u64
+    }
+    /// Share of pawn structure lookups answered from the pawn hash table, in percent.
+    pub fn pawn_hash_hit_rate(&self) -> f64 {
+        100.0 * self.pawn_hash_hits as f64 / self.pawn_hash_probes.max(1) as f64
+    }
+}
+
+/// One of the ranked root moves of a MultiPV search.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PvLine {
+    pub score: i32,
+    pub pv: Vec<ChessMove>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    pub best_move: Option<ChessMove>,
+    pub ponder_move: Option<ChessMove>,
+    pub score: i32,
+    pub depth: i32,
+    pub nodes: u64,
+    /// The best lines of the last finished iteration, best first, as many as MultiPV asks for.
+    pub lines: Vec<PvLine>,
+}
+
+/// Flags another thread can use to steer a running search.
+#[derive(Debug, Default)]
+pub struct SearchControl {
+    stop: AtomicBool,
+    pondering: AtomicBool,
+}
+
+impl SearchControl {
+    pub fn reset(&self, pondering: bool) {
+        self.stop.store(false, Ordering::SeqCst);
+        self.pondering.store(pondering, Ordering::SeqCst);
+    }
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::SeqCst);
+    }
+    pub fn is_stopped(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+    }
+    /// The opponent played the expected move, so the ponder search now runs on our own clock.
+    pub fn ponderhit(&self) {
+        self.pondering.store(false, Ordering::SeqCst);
+    }
+    pub fn is_pondering(&self) -> bool {
+        self.pondering.load(Ordering::Relaxed)
+    }
+}
+
+/// State shared by all threads taking part in one search.
+#[derive(Debug, Default)]
+struct SharedSearchState {
+    nodes: AtomicU64,
+    tb_hits: AtomicU64,
+    abort: AtomicBool,
+    //The moves searched at the root, fewer than the legal ones when tablebases decide
+    root_moves: Vec<ChessMove>,
+}
+
+/// Lazy SMP: every thread runs its own iterative deepening on the same position and they only
+/// cooperate through the shared transposition table. The main thread owns the clock, reports
+/// progress and decides the move, helpers just fill the table with results from other depths.
+pub struct Searcher {
+    tt: Arc<TranspositionTable>,
+    control: Arc<SearchControl>,
+    workers: Vec<SearchWorker>,
+    tablebase: Option<Arc<Tablebase>>,
+    tb_probe_limit: usize,
+    endgame_tables: Option<Arc<EndgameTables>>,
+    book: Option<Arc<OpeningBook>>,
+    pub book_selection: BookSelection,
+    eval_weights: EvalWeights,
+    network: Option<Arc<Network>>,
+    use_nnue: bool,
+    nnue_simd: bool,
+    multi_pv: usize,
+    skill_level: u32,
+    limit_strength: bool,
+    elo: u32,
+    skill_random: ChessRandom,
+    pub features: SearchFeatures,
+}
+
+impl Searcher {
+    pub fn new(hash_size_mb: usize) -> Self {
+        let tt = Arc::new(TranspositionTable::new(hash_size_mb));
+        let control = Arc::<SearchControl>::default();
+        Self {
+            workers: vec![SearchWorker::new(0, tt.clone(), control.clone())],
+            tt,
+            control,
+            tablebase: None,
+            tb_probe_limit: MAX_TB_PIECES,
+            endgame_tables: None,
+            book: None,
+            book_selection: BookSelection::default(),
+            eval_weights: EvalWeights::default(),
+            network: None,
+            use_nnue: false,
+            nnue_simd: simd_available(),
+            multi_pv: 1,
+            skill_level: MAX_SKILL_LEVEL,
+            limit_strength: false,
+            elo: DEFAULT_ELO,
+            skill_random: ChessRandom::from_clock(),
+            features: SearchFeatures::default(),
+        }
+    }
+    /// A single threaded searcher with its own table but the same tablebases, evaluation and
+    /// search features, for searching other positions alongside this one. The book is left out.
+    pub fn fork(&self, hash_size_mb: usize) -> Self {
+        Self {
+            tablebase: self.tablebase.clone(),
+            tb_probe_limit: self.tb_probe_limit,
+            endgame_tables: self.endgame_tables.clone(),
+            eval_weights: self.eval_weights,
+            network: self.network.clone(),
+            use_nnue: self.use_nnue,
+            nnue_simd: self.nnue_simd,
+            features: self.features,
+            ..Self::new(hash_size_mb)
+        }
+    }
+    pub fn control(&self) -> Arc<SearchControl> {
+        self.control.clone()
+    }
+    pub fn set_hash_size(&mut self, hash_size_mb: usize) {
+        self.tt = Arc::new(TranspositionTable::new(hash_size_mb));
+        for worker in self.workers.iter_mut() {
+            worker.tt = self.tt.clone();
+        }
+    }
+    pub fn threads(&self) -> usize {
+        self.workers.len()
+    }
+    pub fn set_threads(&mut self, threads: usize) {
+        let threads = threads.clamp(1, MAX_THREADS);
+        self.workers.truncate(threads);
+        while self.workers.len() < threads {
+            let id = self.workers.len();
+            self.workers
+                .push(SearchWorker::new(id, self.tt.clone(), self.control.clone()));
+        }
+    }
+    pub fn tablebase(&self) -> Option<Arc<Tablebase>> {
+        self.tablebase.clone()
+    }
+    /// Loads the Syzygy tables found in `path`, an empty path turns tablebases off.
+    pub fn set_tablebase_path(&mut self, path: &str) {
+        self.tablebase = if path.is_empty() || path == "<empty>" {
+            None
+        } else {
+            Some(Arc::new(Tablebase::new(path)))
+        };
+    }
+    pub fn endgame_tables(&self) -> Option<Arc<EndgameTables>> {
+        self.endgame_tables.clone()
+    }
+    pub fn set_endgame_tables(&mut self, tables: Option<Arc<EndgameTables>>) {
+        self.endgame_tables = tables;
+    }
+    /// Loads the tables written by the generator from `path`, an empty path turns them off.
+    pub fn set_endgame_table_path(&mut self, path: &str) -> bool {
+        if path.is_empty() || path == "<empty>" {
+            self.endgame_tables = None;
+            return true;
+        }
+        match EndgameTables::load(Path::new(path)) {
+            Ok(tables) => {
+                self.endgame_tables = Some(Arc::new(tables));
+                true
+            }
+            Err(_) => false,
+        }
+    }
+    pub fn book(&self) -> Option<Arc<OpeningBook>> {
+        self.book.clone()
+    }
+    /// Opens the Polyglot book at `path`, an empty path turns the book off.
+    pub fn set_book_path(&mut self, path: &str) -> bool {
+        if path.is_empty() || path == "<empty>" {
+            self.book = None;
+            return true;
+        }
+        match OpeningBook::open(Path::new(path)) {
+            Ok(book) => {
+                self.book = Some(Arc::new(book));
+                true
+            }
+            Err(_) => false,
+        }
+    }
+    pub fn eval_weights(&self) -> &EvalWeights {
+        &self.eval_weights
+    }
+    /// Loads the weights of the classical eval from a file written by the tuner, an empty path
+    /// goes back to the built in ones.
+    pub fn set_eval_weights_path(&mut self, path: &str) -> bool {
+        if path.is_empty() || path == "<empty>" {
+            self.eval_weights = EvalWeights::default();
+            return true;
+        }
+        match EvalWeights::load(Path::new(path)) {
+            Ok(weights) => {
+                self.eval_weights = weights;
+                true
+            }
+            Err(_) => false,
+        }
+    }
+    pub fn network(&self) -> Option<Arc<Network>> {
+        self.network.clone()
+    }
+    /// Loads the network at `path`, an empty path unloads it and the classical eval takes over.
+    pub fn set_eval_file(&mut self, path: &str) -> bool {
+        if path.is_empty() || path == "<empty>" {
+            self.network = None;
+            return true;
+        }
+        match Network::load(Path::new(path)) {
+            Ok(network) => {
+                self.network = Some(Arc::new(network));
+                true
+            }
+            Err(_) => false,
+        }
+    }
+    /// Whether searches evaluate with the network, which needs one loaded.
+    pub fn uses_nnue(&self) -> bool {
+        self.use_nnue && self.network.is_some()
+    }
+    pub fn nnue_simd(&self) -> bool {
+        self.nnue_simd
+    }
+    pub fn multi_pv(&self) -> usize {
+        self.multi_pv
+    }
+    /// How many of the best root moves get their own line, 1 for a normal search.
+    pub fn set_multi_pv(&mut self, lines: usize) {
+        self.multi_pv = lines.clamp(1, MAX_MULTI_PV);
+    }
+    /// The strength searches play at, None at full strength. UCI_LimitStrength with UCI_Elo
+    /// takes precedence over the skill level.
+    pub fn skill(&self) -> Option<Skill> {
+        if self.limit_strength {
+            Some(Skill::from_elo(self.elo))
+        } else {
+            Skill::new(self.skill_level)
+        }
+    }
+    /// The static evaluation of `board` for the side to move, by whichever eval searches use.
+    pub fn static_eval(&self, board: &ChessBoard) -> i32 {
+        match self.network.as_ref().filter(|_| self.use_nnue) {
+            Some(network) => network.evaluate(board, self.nnue_simd),
+            None => evaluate(board, &self.eval_weights, &mut PawnTable::new()),
+        }
+    }
+    /// Applies an option by its UCI name, returns false for unknown options or invalid values.
+    pub fn set_option(&mut self, name: &str, value: &str) -> bool {
+        let number = value.parse::<usize>();
+        let switch = value.parse::<bool>();
+        match (name.to_lowercase().as_str(), number, switch) {
+            ("hash", Ok(size_mb), _) => self.set_hash_size(size_mb),
+            ("threads", Ok(threads), _) => self.set_threads(threads),
+            ("multipv", Ok(lines), _) => self.set_multi_pv(lines),
+            ("skill level", Ok(level), _) => self.skill_level = (level as u32).min(MAX_SKILL_LEVEL),
+            ("uci_limitstrength", _, Ok(on)) => self.limit_strength = on,
+            ("uci_elo", Ok(elo), _) => self.elo = (elo as u32).clamp(MIN_ELO, MAX_ELO),
+            ("syzygypath", _, _) => self.set_tablebase_path(value),
+            ("endgametablepath", _, _) => return self.set_endgame_table_path(value),
+            ("bookfile", _, _) => return self.set_book_path(value),
+            ("evalweights", _, _) => return self.set_eval_weights_path(value),
+            ("evalfile", _, _) => return self.set_eval_file(value),
+            ("usennue", _, Ok(on)) => self.use_nnue = on,
+            ("nnuesimd", _, Ok(on)) => self.nnue_simd = on && simd_available(),
+            ("bestbookmove", _, Ok(best)) => {
+                self.book_selection = if best {
+                    BookSelection::Best
+                } else {
+                    BookSelection::WeightedRandom
+                }
+            }
+            ("syzygyprobelimit", Ok(pieces), _) => self.tb_probe_limit = pieces.min(MAX_TB_PIECES),
+            ("nullmovepruning", _, Ok(on)) => self.features.null_move_pruning = on,
+            ("latemovereductions", _, Ok(on)) => self.features.late_move_reductions = on,
+            ("reversefutilitypruning", _, Ok(on)) => self.features.reverse_futility_pruning = on,
+            ("futilitypruning", _, Ok(on)) => self.features.futility_pruning = on,
+            ("checkextensions", _, Ok(on)) => self.features.check_extensions = on,
+            _ => return false,
+        }
+        true
+    }
+    /// Forgets everything learned from earlier searches, for the start of a new game.
+    pub fn clear(&mut self) {
+        self.tt.clear();
+        for worker in self.workers.iter_mut() {
+            worker.killers.clear();
+            worker.pawn_table.clear();
+            worker.history.clear();
+        }
+    }
+    pub fn search(
+        &mut self,
+        board: &ChessBoard,
+        limits: &SearchLimits,
+        mut on_info: impl FnMut(&SearchInfo),
+    ) -> SearchResult {
+        let Some(skill) = self.skill() else {
+            return self.search_full_strength(board, limits, on_info);
+        };
+        //A weaker level searches less and compares a few lines to choose from, of which only
+        //the ones asked for are reported
+        let limits = SearchLimits {
+            depth: Some(
+                limits
+                    .depth
+                    .map_or(skill.max_depth(), |depth| depth.min(skill.max_depth())),
+            ),
+            nodes: Some(
+                limits
+                    .nodes
+                    .map_or(skill.max_nodes(), |nodes| nodes.min(skill.max_nodes())),
+            ),
+            ..limits.clone()
+        };
+        let multi_pv = self.multi_pv;
+        self.multi_pv = multi_pv.max(SKILL_LINES);
+        let mut result = self.search_full_strength(board, &limits, |info| {
+            if info.multipv <= multi_pv {
+                on_info(info);
+            }
+        });
+        self.multi_pv = multi_pv;
+
+        if let Some(line) = skill.choose(&result.lines, &mut self.skill_random) {
+            result.best_move = line.pv.first().copied();
+            result.ponder_move = line.pv.get(1).copied();
+            result.score = line.score;
+        }
+        result.lines.truncate(multi_pv);
+        result
+    }
+    fn search_full_strength(
+        &mut self,
+        board: &ChessBoard,
+        limits: &SearchLimits,
+        on_info: impl FnMut(&SearchInfo),
+    ) -> SearchResult {
+        let restricted = !limits.search_moves.is_empty() || !limits.excluded_moves.is_empty();
+        //A book move is played right away, except when the GUI wants the position analysed or
+        //only some of the moves looked at
+        if !limits.infinite && !limits.ponder && !restricted {
+            let book_move = self
+                .book
+                .as_ref()
+                .and_then(|book| book.choose(board, self.book_selection));
+            if book_move.is_some() {
+                return SearchResult {
+                    best_move: book_move,
+                    ponder_move: None,
+                    score: 0,
+                    depth: 0,
+                    nodes: 0,
+                    lines: vec![],
+                };
+            }
+        }
+        let mut root_moves = board.generate_legal_moves();
+        let allowed: Vec<ChessMove> = root_moves
+            .iter()
+            .filter(|chess_move| {
+                (limits.search_moves.is_empty() || limits.search_moves.contains(chess_move))
+                    && !limits.excluded_moves.contains(chess_move)
+            })
+            .copied()
+            .collect();
+        //A restriction that leaves nothing to play is ignored
+        if !allowed.is_empty() {
+            root_moves = allowed;
+        }
+        let mut tb_probe_limit = match &self.tablebase {
+            Some(tablebase) => self.tb_probe_limit.min(tablebase.max_pieces()),
+            None => 0,
+        };
+        //With the root in the tablebases only the moves keeping the best result are searched,
+        //ranked by DTZ so the win actually makes progress, and there is nothing left to probe
+        //The tablebases rank all moves, so a restricted root is searched the usual way
+        if let Some(tablebase) = self.tablebase.as_ref().filter(|_| !restricted) {
+            if board.piece_count() <= tb_probe_limit {
+                if let Some((_wdl, best_moves)) = tablebase.probe_root(&mut board.clone()) {
+                    root_moves = best_moves
+                        .iter()
+                        .map(|&(chess_move, _dtz)| chess_move)
+                        .collect();
+                    tb_probe_limit = 0;
+                }
+            }
+        }
+
+        let shared = Arc::new(SharedSearchState {
+            root_moves,
+            ..Default::default()
+        });
+        let network = self.network.clone().filter(|_| self.use_nnue);
+        for worker in self.workers.iter_mut() {
+            worker.set_network(network.clone(), self.nnue_simd);
+            worker.prepare(board, limits, shared.clone());
+            worker.features = self.features;
+            worker.eval_weights = self.eval_weights;
+            worker.tablebase = self.tablebase.clone();
+            worker.endgame_tables = self.endgame_tables.clone();
+            worker.tb_probe_limit = tb_probe_limit;
+            //Helpers only fill the table, so they search every root move as usual
+            worker.multi_pv = if worker.is_main() { self.multi_pv } else { 1 };
+        }
+
+        let (main_worker, helpers) = self.workers.split_first_mut().unwrap();
+        thread::scope(|scope| {
+            for helper in helpers.iter_mut() {
+                scope.spawn(move || helper.iterative_deepening(limits, |_| ()));
+            }
+            let result = main_worker.iterative_deepening(limits, on_info);
+
+            //The GUI expects no best move for pondering or infinite searches until it asks for one
+            while (limits.infinite || self.control.is_pondering()) && !self.control.is_stopped() {
+                thread::sleep(Duration::from_millis(1));
+            }
+            shared.abort.store(true, Ordering::SeqCst);
+            result
+        })
+    }
+}
+
+struct SearchWorker {
+    id: usize,
+    board: ChessBoard,
+    tt: Arc<TranspositionTable>,
+    killers: KillerMoves,
+    history: Box<HistoryTable>,
+    time_manager: TimeManager,
+    control: Arc<SearchControl>,
+    shared: Arc<SharedSearchState>,
+    features: SearchFeatures,
+    tablebase: Option<Arc<Tablebase>>,
+    tb_probe_limit: usize,
+    endgame_tables: Option<Arc<EndgameTables>>,
+    pondering: bool,
+    node_limit: Option<u64>,
+    nodes: u64,
+    reported_nodes: u64,
+    stopped: bool,
+    pv: Vec<Vec<ChessMove>>,
+    pawn_table: PawnTable,
+    eval_weights: EvalWeights,
+    nnue: Option<NnueState>,
+    multi_pv: usize,
+    //Root moves already given a line in this iteration of a MultiPV search
+    excluded_root_moves: Vec<ChessMove>,
+}
+
+impl SearchWorker {
+    fn new(id: usize, tt: Arc<TranspositionTable>, control: Arc<SearchControl>) -> Self {
+        Self {
+            id,
+            board: ChessBoard::new(),
+            tt,
+            killers: KillerMoves::new(),
+            history: Box::default(),
+            time_manager: TimeManager::infinite(),
+            control,
+            shared: Arc::default(),
+            features: SearchFeatures::default(),
+            tablebase: None,
+            tb_probe_limit: 0,
+            endgame_tables: None,
+            pondering: false,
+            node_limit: None,
+            nodes: 0,
+            reported_nodes: 0,
+            stopped: false,
+            pv: vec![vec![]; MAX_PLY + 1],
+            pawn_table: PawnTable::new(),
+            eval_weights: EvalWeights::default(),
+            nnue: None,
+            multi_pv: 1,
+            excluded_root_moves: vec![],
+        }
+    }
+    //Keeps the accumulator stack while the same network stays in use
+    fn set_network(&mut self, network: Option<Arc<Network>>, simd: bool) {
+        let unchanged = match (&self.nnue, &network) {
+            (Some(nnue), Some(network)) => {
+                Arc::ptr_eq(nnue.network(), network) && nnue.simd() == simd
+            }
+            (None, None) => true,
+            _ => false,
+        };
+        if !unchanged {
+            self.nnue = network.map(|network| NnueState::new(network, simd));
+        }
+    }
+    fn is_main(&self) -> bool {
+        self.id == 0
+    }
+    fn prepare(
+        &mut self,
+        board: &ChessBoard,
+        limits: &SearchLimits,
+        shared: Arc<SharedSearchState>,
+    ) {
+        self.board = board.clone();
+        self.shared = shared;
+        self.nodes = 0;
+        self.reported_nodes = 0;
+        self.stopped = false;
+        self.pondering = self.control.is_pondering();
+        self.node_limit = limits.nodes;
+        self.killers.clear();
+        self.excluded_root_moves.clear();
+        self.history.age();
+        self.pawn_table.reset_stats();
+        if let Some(nnue) = &mut self.nnue {
+            nnue.reset(board);
+        }
+        self.time_manager = if limits.infinite {
+            TimeManager::infinite()
+        } else {
+            TimeManager::new(
+                limits.time_control.as_ref(),
+                limits.move_time,
+                game_phase(board),
+            )
+        };
+    }
+    fn iterative_deepening(
+        &mut self,
+        limits: &SearchLimits,
+        mut on_info: impl FnMut(&SearchInfo),
+    ) -> SearchResult {
+        let mut result = SearchResult {
+            best_move: self.shared.root_moves.first().copied(),
+            ponder_move: None,
+            score: 0,
+            depth: 0,
+            nodes: 0,
+            lines: vec![],
+        };
+        let max_depth = limits.depth.unwrap_or(MAX_PLY as i32 - 1);
+        let multi_pv = self.multi_pv.min(self.shared.root_moves.len()).max(1);
+        //Every other helper runs a ply ahead so the threads don't all search the same tree
+        let depth_offset = (self.id % 2) as i32;
+
+        for depth in 1 + depth_offset..=max_depth {
+            //Each further line searches the root without the moves of the lines before it
+            let mut lines: Vec<PvLine> = vec![];
+            self.excluded_root_moves.clear();
+            for pv_index in 0..multi_pv {
+                let previous_score = result
+                    .lines
+                    .get(pv_index)
+                    .map_or(result.score, |line| line.score);
+                let score = self.aspiration_search(depth, previous_score, pv_index, &mut on_info);
+                if self.stopped {
+                    break;
+                }
+                let Some(&first_move) = self.pv[0].first() else {
+                    break;
+                };
+                self.excluded_root_moves.push(first_move);
+                lines.push(PvLine {
+                    score,
+                    pv: self.pv[0].clone(),
+                });
+            }
+            self.excluded_root_moves.clear();
+            //An unfinished iteration can't be trusted, fall back to the previous one
+            if self.stopped {
+                break;
+            }
+            //A later line can still beat an earlier one that was searched with less knowledge
+            lines.sort_by_key(|line| -line.score);
+            self.report_nodes();
+            let score = lines.first().map_or(result.score, |line| line.score);
+            result = SearchResult {
+                best_move: lines
+                    .first()
+                    .and_then(|line| line.pv.first().copied())
+                    .or(result.best_move),
+                ponder_move: lines.first().and_then(|line| line.pv.get(1).copied()),
+                score,
+                depth,
+                nodes: self.total_nodes(),
+                lines,
+            };
+            if !self.is_main() {
+                continue;
+            }
+            for (pv_index, line) in result.lines.iter().enumerate() {
+                self.report_info(
+                    depth,
+                    line.score,
+                    Bound::Exact,
+                    pv_index,
+                    &line.pv,
+                    &mut on_info,
+                );
+            }
+
+            self.time_manager.update(result.best_move, score);
+            self.update_pondering();
+            if (!self.pondering && self.time_manager.soft_limit_reached())
+                || self.node_limit_reached()
+                || self.control.is_stopped()
+            {
+                break;
+            }
+        }
+        self.report_nodes();
+        result.nodes = self.total_nodes();
+        result
+    }
+    /// Searches the root in a narrow window around the previous iteration's score, widening
+    /// it on the side that failed until the score lands inside.
+    fn aspiration_search(
+        &mut self,
+        depth: i32,
+        previous_score: i32,
+        pv_index: usize,
+        on_info: &mut impl FnMut(&SearchInfo),
+    ) -> i32 {
+        let mut delta = ASPIRATION_WINDOW;
+        let (mut alpha, mut beta) = if depth >= ASPIRATION_MIN_DEPTH {
+            (
+                (previous_score - delta).max(-INFINITY),
+                (previous_score + delta).min(INFINITY),
+            )
+        } else {
+            (-INFINITY, INFINITY)
+        };
+
+        loop {
+            let score = self.negamax(depth, 0, alpha, beta, true);
+            if self.stopped {
+                return score;
+            }
+            if score <= alpha {
+                let pv = self.pv[0].clone();
+                self.report_info(depth, score, Bound::Upper, pv_index, &pv, on_info);
+                beta = (alpha + beta) / 2;
+                alpha = (score - delta).max(-INFINITY);
+            } else if score >= beta {
+                let pv = self.pv[0].clone();
+                self.report_info(depth, score, Bound::Lower, pv_index, &pv, on_info);
+                beta = (score + delta).min(INFINITY);
+            } else {
+                return score;
+            }
+            delta *= 2;
+        }
+    }
+    fn report_info(
+        &mut self,
+        depth: i32,
+        score: i32,
+        bound: Bound,
+        pv_index: usize,
+        pv: &[ChessMove],
+        on_info: &mut impl FnMut(&SearchInfo),
+    ) {
+        if !self.is_main() {
+            return;
+        }
+        self.report_nodes();
+        on_info(&SearchInfo {
+            depth,
+            score: Score::new(score),
+            bound,
+            nodes: self.total_nodes(),
+            time: self.time_manager.elapsed(),
+            hashfull: self.tt.hashfull(),
+            tb_hits: self.shared.tb_hits.load(Ordering::Relaxed),
+            pawn_hash_probes: self.pawn_table.probes(),
+            pawn_hash_hits: self.pawn_table.hits(),
+            multipv: pv_index + 1,
+            pv: pv.to_vec(),
+        });
+    }
+    fn report_nodes(&mut self) {
+        self.shared
+            .nodes
+            .fetch_add(self.nodes - self.reported_nodes, Ordering::Relaxed);
+        self.reported_nodes = self.nodes;
+    }
+    fn total_nodes(&self) -> u64 {
+        self.shared.nodes.load(Ordering::Relaxed)
+    }
+    //On a ponderhit the clock starts running from that moment
+    fn update_pondering(&mut self) {
+        if self.pondering && !self.control.is_pondering() {
+            self.pondering = false;
+            self.time_manager.restart();
+        }
+    }
+    fn node_limit_reached(&self) -> bool {
+        self.node_limit
+            .is_some_and(|node_limit| self.total_nodes() >= node_limit)
+    }
+    fn check_limits(&mut self) {
+        if !self.nodes.is_multiple_of(TIME_CHECK_INTERVAL) {
+            return;
+        }
+        self.report_nodes();
+        if self.shared.abort.load(Ordering::Relaxed) || self.control.is_stopped() {
+            self.stopped = true;
+        }
+        //Only the main thread keeps an eye on the limits, helpers wait to be aborted
+        if !self.is_main() {
+            return;
+        }
+        self.update_pondering();
+        if (!self.pondering && self.time_manager.hard_limit_reached()) || self.node_limit_reached()
+        {
+            self.stopped = true;
+        }
+    }
+    fn make_move(&mut self, chess_move: &ChessMove) {
+        let changes = self
+            .nnue
+            .as_ref()
+            .map(|_| PieceChanges::new(&self.board, chess_move));
+        self.board.make_move_unchecked(chess_move);
+        if let (Some(nnue), Some(changes)) = (&mut self.nnue, changes) {
+            nnue.push(&changes, &self.board);
+        }
+    }
+    fn unmake_move(&mut self, chess_move: &ChessMove) {
+        self.board.unmake_move(chess_move);
+        if let Some(nnue) = &mut self.nnue {
+            nnue.pop();
+        }
+    }
+    fn evaluate(&mut self) -> i32 {
+        match &self.nnue {
+            Some(nnue) => nnue.evaluate(self.board.color_to_move),
+            None => evaluate(&self.board, &self.eval_weights, &mut self.pawn_table),
+        }
+    }
+    fn update_pv(&mut self, ply: usize, chess_move: ChessMove) {
+        let (head, tail) = self.pv.split_at_mut(ply + 1);
+        head[ply].clear();
+        head[ply].push(chess_move);
+        head[ply].extend_from_slice(&tail[0]);
+    }
+    fn negamax(
+        &mut self,
+        mut depth: i32,
+        ply: usize,
+        mut alpha: i32,
+        mut beta: i32,
+        allow_null_move: bool,
+    ) -> i32 {
+        self.pv[ply].clear();
+        if ply > 0 {
+            if self.board.is_repetition() || self.board.halfmove_clock >= 100 {
+                return DRAW_SCORE;
+            }
+            //Mate distance pruning: no line from here can beat a shorter mate already found
+            alpha = alpha.max(mated_in(ply));
+            beta = beta.min(mate_in(ply + 1));
+            if alpha >= beta {
+                return alpha;
+            }
+        }
+        let in_check = self.board.in_check();
+        //Never stand pat in check, look one ply further instead
+        if in_check && self.features.check_extensions {
+            depth += 1;
+        }
+        if depth <= 0 {
+            return self.quiescence(ply, alpha, beta);
+        }
+        if ply >= MAX_PLY - 1 {
+            return self.evaluate();
+        }
+
+        self.nodes += 1;
+        self.check_limits();
+        if self.stopped {
+            return 0;
+        }
+
+        //Only nodes searched with an open window can end up on the principal variation
+        let pv_node = beta - alpha > 1;
+        let key = self.board.zobrist_key;
+        let tt_entry = self.tt.probe(key);
+        if let Some(entry) = tt_entry {
+            if !pv_node && entry.depth >= depth {
+                let score = score_from_tt(entry.score, ply);
+                match entry.bound {
+                    Bound::Exact => return score,
+                    Bound::Lower if score >= beta => return score,
+                    Bound::Upper if score <= alpha => return score,
+                    _ => (),
+                }
+            }
+        }
+
+        //Right after a capture or pawn move the tablebases know the exact result
+        if ply > 0
+            && self.board.halfmove_clock == 0
+            && self.tb_probe_limit > 0
+            && self.board.piece_count() <= self.tb_probe_limit
+            && !has_castling_rights(&self.board)
+        {
+            if let Some(tablebase) = self.tablebase.clone() {
+                if let Some(wdl) = tablebase.probe_wdl(&mut self.board) {
+                    self.shared.tb_hits.fetch_add(1, Ordering::Relaxed);
+                    let score = match wdl {
+                        Wdl::Win => tb_win_in(ply),
+                        Wdl::Loss => tb_loss_in(ply),
+                        //Wins and losses the 50-move rule takes away are barely better than a draw
+                        Wdl::CursedWin => DRAW_SCORE + 1,
+                        Wdl::BlessedLoss => DRAW_SCORE - 1,
+                        Wdl::Draw => DRAW_SCORE,
+                    };
+                    self.tt
+                        .store(key, None, score_to_tt(score, ply), depth, Bound::Exact);
+                    return score;
+                }
+            }
+        }
+
+        //Our own distance-to-mate tables give exact mate scores
+        if ply > 0 && self.board.piece_count() <= MAX_TABLE_PIECES {
+            let dtm = self
+                .endgame_tables
+                .as_ref()
+                .filter(|_| !has_castling_rights(&self.board))
+                .and_then(|tables| tables.probe(&self.board));
+            if let Some(dtm) = dtm {
+                self.shared.tb_hits.fetch_add(1, Ordering::Relaxed);
+                return match dtm {
+                    Dtm::Win(plies) => mate_in(ply + plies as usize),
+                    Dtm::Loss(plies) => mated_in(ply + plies as usize),
+                    Dtm::Draw => DRAW_SCORE,
+                };
+            }
+        }
+
+        let color = self.board.color_to_move;
+        let static_eval = if in_check { -INFINITY } else { self.evaluate() };
+
+        //Reverse futility: so far above beta that a shallow search won't bring it back down
+        if self.features.reverse_futility_pruning
+            && !pv_node
+            && !in_check
+            && depth <= REVERSE_FUTILITY_MAX_DEPTH
+            && static_eval - REVERSE_FUTILITY_MARGIN * depth >= beta
+        {
+            return static_eval;
+        }
+
+        //Null move: if passing still fails high, a real move almost certainly would too. Not
+        //with only pawns left, where zugzwang makes passing better than any real move.
+        if self.features.null_move_pruning
+            && !pv_node
+            && allow_null_move
+            && !in_check
+            && depth >= NULL_MOVE_MIN_DEPTH
+            && static_eval >= beta
+            && self.board.has_non_pawn_material(color)
+        {
+            let reduction = 3 + depth / 6;
+            self.board.make_null_move();
+            let score = -self.negamax(depth - 1 - reduction, ply + 1, -beta, -beta + 1, false);
+            self.board.unmake_null_move();
+
+            if self.stopped {
+                return 0;
+            }
+            if score >= beta {
+                //Don't trust a mate found by passing
+                return if is_mate_score(score) { beta } else { score };
+            }
+        }
+
+        let moves = if ply == 0 {
+            let excluded = &self.excluded_root_moves;
+            self.shared
+                .root_moves
+                .iter()
+                .filter(|chess_move| !excluded.contains(chess_move))
+                .copied()
+                .collect()
+        } else {
+            self.board.generate_legal_moves()
+        };
+        if moves.is_empty() {
+            return if in_check { mated_in(ply) } else { DRAW_SCORE };
+        }
+
+        //Futility: near the leaves, quiet moves can't lift a hopeless static eval above alpha
+        let futile = self.features.futility_pruning
+            && !pv_node
+            && !in_check
+            && (depth as usize) < FUTILITY_MARGINS.len()
+            && static_eval + FUTILITY_MARGINS[depth as usize] <= alpha;
+
+        let original_alpha = alpha;
+        let mut best_score = -INFINITY;
+        let mut best_move = None;
+        let mut moves_searched = 0;
+        let mut quiets_tried = vec![];
+        let killers = self.killers.get(ply);
+        let mut picker =
+            MovePicker::new(moves, tt_entry.and_then(|entry| entry.best_move), killers);
+
+        while let Some(chess_move) = picker.next(&self.board, &self.history) {
+            let is_quiet =
+                !self.board.is_capture(&chess_move) && chess_move.get_promotion().is_none();
+
+            self.make_move(&chess_move);
+            let gives_check = self.board.in_check();
+
+            if futile && is_quiet && !gives_check && moves_searched > 0 {
+                self.unmake_move(&chess_move);
+                continue;
+            }
+
+            //Principal variation search: the first move gets the full window, the rest only need
+            //to be proven worse with a zero window and are searched again if that fails
+            let mut score;
+            if moves_searched == 0 {
+                score = -self.negamax(depth - 1, ply + 1, -beta, -alpha, true);
+            } else {
+                //Late move reductions: quiet moves ordered late rarely matter, so look at them
+                //less deeply first and only search them fully when they turn out to beat alpha
+                let reduction = if self.features.late_move_reductions
+                    && depth >= LMR_MIN_DEPTH
+                    && moves_searched >= LMR_MIN_MOVES
+                    && is_quiet
+                    && !in_check
+                    && !gives_check
+                    && !killers.contains(&Some(chess_move))
+                {
+                    Self::late_move_reduction(depth, moves_searched).min(depth - 2)
+                } else {
+                    0
+                };
+                score = -self.negamax(depth - 1 - reduction, ply + 1, -alpha - 1, -alpha, true);
+                if score > alpha && reduction > 0 {
+                    score = -self.negamax(depth - 1, ply + 1, -alpha - 1, -alpha, true);
+                }
+                if score > alpha && score < beta {
+                    score = -self.negamax(depth - 1, ply + 1, -beta, -alpha, true);
+                }
+            }
+            self.unmake_move(&chess_move);
+            moves_searched += 1;
+
+            if self.stopped {
+                return 0;
+            }
+
+            if score > best_score {
+                best_score = score;
+                best_move = Some(chess_move);
+                if score > alpha {
+                    alpha = score;
+                    self.update_pv(ply, chess_move);
+                    if score >= beta {
+                        if is_quiet {
+                            self.killers.store(ply, chess_move);
+                            self.history.reward(color, &chess_move, depth);
+                            for quiet_move in &quiets_tried {
+                                self.history.penalize(color, quiet_move, depth);
+                            }
+                        }
+                        break;
+                    }
+                }
+            }
+            if is_quiet {
+                quiets_tried.push(chess_move);
+            }
+        }
+
+        let bound = if best_score >= beta {
+            Bound::Lower
+        } else if best_score > original_alpha {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        self.tt
+            .store(key, best_move, score_to_tt(best_score, ply), depth, bound);
+        best_score
+    }
+    fn late_move_reduction(depth: i32, moves_searched: usize) -> i32 {
+        (0.75 + (depth as f64).ln() * (moves_searched as f64).ln() / 2.25) as i32
+    }
+    fn quiescence(&mut self, ply: usize, mut alpha: i32, beta: i32) -> i32 {
+        self.nodes += 1;
+        self.check_limits();
+        if self.stopped {
+            return 0;
+        }
+
+        if ply >= MAX_PLY - 1 {
+            return self.evaluate();
+        }
+        let moves = self.board.generate_legal_moves();
+        let (mut best_score, mut picker) = if self.board.in_check() {
+            //No standing pat in check: every evasion is searched and having none is mate
+            if moves.is_empty() {
+                return mated_in(ply);
+            }
+            (-INFINITY, MovePicker::new(moves, None, [None; 2]))
+        } else {
+            let stand_pat = self.evaluate();
+            if stand_pat >= beta {
+                return stand_pat;
+            }
+            alpha = alpha.max(stand_pat);
+            (stand_pat, MovePicker::new_captures(moves))
+        };
+        while let Some(chess_move) = picker.next(&self.board, &self.history) {
+            self.make_move(&chess_move);
+            let score = -self.quiescence(ply + 1, -beta, -alpha);
+            self.unmake_move(&chess_move);
+
+            if self.stopped {
+                return 0;
+            }
+            if score > best_score {
+                best_score = score;
+                if score > alpha {
+                    alpha = score;
+                    if score >= beta {
+                        break;
+                    }
+                }
+            }
+        }
+        best_score
+    }
+}
@@ -0,0 +1,79 @@
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use crate::chess::chess_board::ChessBoard;
+use crate::engine::search::{SearchControl, SearchInfo, SearchLimits, SearchResult, Searcher};
+
+/// Runs a `Searcher` in the background so the caller can keep reading commands, e.g. to
+/// stop an infinite analysis or to tell a ponder search that the expected move was played.
+pub struct SearchThread {
+    searcher: Option<Searcher>,
+    control: Arc<SearchControl>,
+    handle: Option<JoinHandle<(Searcher, SearchResult)>>,
+}
+
+impl SearchThread {
+    pub fn new(searcher: Searcher) -> Self {
+        Self {
+            control: searcher.control(),
+            searcher: Some(searcher),
+            handle: None,
+        }
+    }
+    /// Starts searching `board`, stopping any search still running. `on_info` is called after
+    /// every iteration and `on_finish` with the final result, both on the search thread.
+    pub fn start(
+        &mut self,
+        board: &ChessBoard,
+        limits: SearchLimits,
+        on_info: impl FnMut(&SearchInfo) + Send + 'static,
+        on_finish: impl FnOnce(&SearchResult) + Send + 'static,
+    ) {
+        self.stop();
+        let mut searcher = self.searcher_mut_take();
+        let board = board.clone();
+
+        self.control.reset(limits.ponder);
+        self.handle = Some(thread::spawn(move || {
+            let result = searcher.search(&board, &limits, on_info);
+            on_finish(&result);
+            (searcher, result)
+        }));
+    }
+    pub fn is_searching(&self) -> bool {
+        self.handle
+            .as_ref()
+            .is_some_and(|handle| !handle.is_finished())
+    }
+    pub fn ponderhit(&self) {
+        self.control.ponderhit();
+    }
+    pub fn stop(&mut self) -> Option<SearchResult> {
+        if self.handle.is_some() {
+            self.control.stop();
+        }
+        self.wait()
+    }
+    /// Blocks until the current search ends on its own and returns its result.
+    pub fn wait(&mut self) -> Option<SearchResult> {
+        let handle = self.handle.take()?;
+        let (searcher, result) = handle.join().unwrap();
+        self.searcher = Some(searcher);
+        Some(result)
+    }
+    /// The idle searcher, waiting for a running search to finish first.
+    pub fn searcher_mut(&mut self) -> &mut Searcher {
+        self.wait();
+        self.searcher.as_mut().unwrap()
+    }
+    fn searcher_mut_take(&mut self) -> Searcher {
+        self.wait();
+        self.searcher.take().unwrap()
+    }
+}
+
+impl Drop for SearchThread {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
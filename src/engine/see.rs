@@ -0,0 +1,112 @@
+use std::cmp;
+
+use crate::chess::chess_board::ChessBoard;
+use crate::chess::chess_move::{ChessMove, MoveFlags};
+use crate::chess::chess_piece::ChessPiece;
+use crate::chess::CHESS_BOARD_WIDTH;
+
+// Indexed by piece type, pawn = 0 .. king = 5
+pub const SEE_PIECE_VALUES: [i32; 6] = [100, 320, 330, 500, 900, 20000];
+
+/// Static exchange evaluation: the material balance, from the point of view of
+/// the side making `chess_move`, after both sides keep recapturing on the
+/// target square with their least valuable attacker for as long as it pays off.
+pub fn see(board: &ChessBoard, chess_move: ChessMove) -> i32 {
+    let (start_sq, end_sq, flags) = chess_move.get_idx();
+    let start_idx = start_sq as usize;
+    let target = end_sq as usize;
+
+    let mut layout = board.layout;
+    let mut gain = [0; 32];
+    let mut depth = 0;
+
+    gain[0] = if flags == MoveFlags::EN_PASSANT {
+        //The captured pawn sits beside the capturing one, not on the target square
+        let captured_idx =
+            (start_idx / CHESS_BOARD_WIDTH) * CHESS_BOARD_WIDTH + target % CHESS_BOARD_WIDTH;
+        layout[captured_idx] = ChessPiece::empty();
+        SEE_PIECE_VALUES[ChessPiece::PAWN.get_type_idx()]
+    } else if layout[target].is_empty() {
+        0
+    } else {
+        SEE_PIECE_VALUES[layout[target].get_type_idx()]
+    };
+
+    let mut piece_on_target = layout[start_idx];
+    layout[start_idx] = ChessPiece::empty();
+    layout[target] = piece_on_target;
+    let mut color = board.color_to_move.opposite();
+
+    while depth + 1 < gain.len() {
+        let attackers = board.attackers_to(&layout, target, color);
+        let Some(&attacker_idx) = attackers
+            .iter()
+            .min_by_key(|&&idx| layout[idx].get_type_idx())
+        else {
+            break;
+        };
+
+        //A king may only recapture when the other side has nothing left to take back with
+        if layout[attacker_idx].contains(ChessPiece::KING)
+            && !board
+                .attackers_to(&layout, target, color.opposite())
+                .is_empty()
+        {
+            break;
+        }
+
+        depth += 1;
+        gain[depth] = SEE_PIECE_VALUES[piece_on_target.get_type_idx()] - gain[depth - 1];
+
+        //Neither side can improve on stopping here, so the rest of the sequence doesn't matter
+        if cmp::max(-gain[depth - 1], gain[depth]) < 0 {
+            break;
+        }
+
+        piece_on_target = layout[attacker_idx];
+        layout[attacker_idx] = ChessPiece::empty();
+        layout[target] = piece_on_target;
+        color = color.opposite();
+    }
+
+    while depth > 0 {
+        gain[depth - 1] = -cmp::max(-gain[depth - 1], gain[depth]);
+        depth -= 1;
+    }
+    gain[0]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn see_of(fen: &str, notation: &str) -> i32 {
+        let board = ChessBoard::new_from_fen(fen).unwrap();
+        see(&board, board.find_move(notation).unwrap())
+    }
+
+    #[test]
+    fn winning_exchange() {
+        //exd5 cxd5 wins a knight for a pawn
+        assert_eq!(see_of("4k3/8/2p5/3n4/4P3/8/8/4K3 w - - 0 1", "e4d5"), 220);
+    }
+
+    #[test]
+    fn losing_exchange() {
+        //The queen takes a pawn and is taken by another
+        assert_eq!(see_of("4k3/8/2p5/3p4/8/8/8/3QK3 w - - 0 1", "d1d5"), -800);
+    }
+
+    #[test]
+    fn rook_x_ray() {
+        //The rook on d1 recaptures through the one on d2
+        assert_eq!(see_of("3rk3/8/8/3p4/8/8/3R4/3RK3 w - - 0 1", "d2d5"), 100);
+    }
+
+    #[test]
+    fn king_recaptures_only_when_undefended() {
+        assert_eq!(see_of("8/8/8/2k5/3p4/4P3/8/4K3 w - - 0 1", "e3d4"), 0);
+        //The rook covers d4, so the king can't take back
+        assert_eq!(see_of("8/8/8/2k5/3p4/4P3/8/3RK3 w - - 0 1", "e3d4"), 100);
+    }
+}
@@ -0,0 +1,132 @@
+use crate::chess::chess_random::ChessRandom;
+use crate::engine::search::PvLine;
+
+/// Level 20 is full strength, every level below it plays weaker.
+pub const MAX_SKILL_LEVEL: u32 = 20;
+pub const MIN_ELO: u32 = 800;
+pub const MAX_ELO: u32 = 2800;
+pub const DEFAULT_ELO: u32 = 1500;
+/// Root moves searched as their own line so a weaker one can be picked.
+pub const SKILL_LINES: usize = 4;
+
+/// Playing strength below full: shallower searches with fewer nodes that don't always play
+/// the best move they found, but one close enough to it to look like a human's choice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Skill {
+    level: u32,
+}
+
+impl Skill {
+    /// None at full strength.
+    pub fn new(level: u32) -> Option<Self> {
+        (level < MAX_SKILL_LEVEL).then_some(Self { level })
+    }
+    /// The level playing at roughly `elo`. The bands are a rough linear guess, not calibrated
+    /// against a rating list.
+    pub fn from_elo(elo: u32) -> Self {
+        let elo = elo.clamp(MIN_ELO, MAX_ELO);
+        Self {
+            level: (elo - MIN_ELO) * (MAX_SKILL_LEVEL - 1) / (MAX_ELO - MIN_ELO),
+        }
+    }
+    pub fn level(&self) -> u32 {
+        self.level
+    }
+    /// Approximate Elo of the level, the inverse of `from_elo`.
+    pub fn elo(&self) -> u32 {
+        MIN_ELO + self.level * (MAX_ELO - MIN_ELO) / (MAX_SKILL_LEVEL - 1)
+    }
+    pub fn max_depth(&self) -> i32 {
+        1 + self.level as i32
+    }
+    pub fn max_nodes(&self) -> u64 {
+        (500.0 * 1.5f64.powi(self.level as i32)) as u64
+    }
+    /// How far behind the best line a move may be and still get played, in centipawns.
+    pub fn margin(&self) -> i32 {
+        (MAX_SKILL_LEVEL - self.level) as i32 * 15
+    }
+    /// Picks one of the lines within the margin of the best, the closer ones more often.
+    /// `lines` must be sorted best first.
+    pub fn choose<'a>(&self, lines: &'a [PvLine], random: &mut ChessRandom) -> Option<&'a PvLine> {
+        let best_score = lines.first()?.score;
+        let candidates: Vec<(&PvLine, u64)> = lines
+            .iter()
+            .filter(|line| !line.pv.is_empty())
+            .map(|line| (line, self.margin() - (best_score - line.score)))
+            .filter(|&(_, weight)| weight >= 0)
+            .map(|(line, weight)| (line, weight as u64 + 1))
+            .collect();
+        let total_weight: u64 = candidates.iter().map(|&(_, weight)| weight).sum();
+        if total_weight == 0 {
+            return lines.first();
+        }
+        let mut pick = random.next() % total_weight;
+        for (line, weight) in candidates {
+            if pick < weight {
+                return Some(line);
+            }
+            pick -= weight;
+        }
+        lines.first()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::chess_board::ChessBoard;
+    use crate::engine::score::mate_in;
+    use crate::engine::search::{SearchLimits, Searcher};
+
+    fn line(board: &ChessBoard, score: i32, notation: &str) -> PvLine {
+        PvLine {
+            score,
+            pv: vec![board.find_move(notation).unwrap()],
+        }
+    }
+
+    #[test]
+    fn weakest_level_keeps_a_forced_best_line() {
+        let board = ChessBoard::new_from_fen("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1").unwrap();
+        let lines = [
+            line(&board, mate_in(1), "d1d8"),
+            line(&board, -200, "h2h3"),
+            line(&board, -250, "g1f1"),
+        ];
+        let skill = Skill::new(0).unwrap();
+        let mut random = ChessRandom::new(1);
+        for _ in 0..100 {
+            let chosen = skill.choose(&lines, &mut random).unwrap();
+            assert_eq!(chosen.pv, lines[0].pv);
+        }
+    }
+
+    #[test]
+    fn picks_differ_between_calls() {
+        let board = ChessBoard::new_from_fen("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1").unwrap();
+        let lines = [
+            line(&board, 0, "h2h3"),
+            line(&board, 0, "g2g3"),
+            line(&board, 0, "f2f3"),
+            line(&board, 0, "g1f1"),
+        ];
+        let skill = Skill::new(0).unwrap();
+        let mut random = ChessRandom::new(1);
+        let first = &skill.choose(&lines, &mut random).unwrap().pv;
+        assert!((0..100).any(|_| &skill.choose(&lines, &mut random).unwrap().pv != first));
+    }
+
+    #[test]
+    fn weakest_level_plays_the_only_move() {
+        let board = ChessBoard::new_from_fen("k7/8/8/8/8/8/1r6/K7 w - - 0 1").unwrap();
+        let mut searcher = Searcher::new(1);
+        assert!(searcher.set_option("Skill Level", "0"));
+        let limits = SearchLimits {
+            depth: Some(4),
+            ..Default::default()
+        };
+        let result = searcher.search(&board, &limits, |_| ());
+        assert_eq!(result.best_move, board.find_move("a1b2"));
+    }
+}
@@ -0,0 +1,1425 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock, RwLock};
+
+use crate::chess::chess_board::ChessBoard;
+use crate::chess::chess_color::ChessColor;
+use crate::chess::chess_move::ChessMove;
+use crate::chess::chess_piece::ChessPiece;
+use crate::chess::CHESS_BOARD_SIZE;
+
+//Reader for Syzygy tablebases. The file layout and the position indexing follow the
+//reference implementation by Ronald de Man, in the form used by Stockfish. Squares in here
+//are numbered the Syzygy way, a1 = 0 up to h8 = 63, which is the board index XOR 56.
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xE8, 0x23, 0x5D];
+const DTZ_MAGIC: [u8; 4] = [0xD7, 0x66, 0x0C, 0xA5];
+const MAX_PIECES: usize = 7;
+//The order pieces appear in table names
+const PIECE_CHARS: [char; 6] = ['K', 'Q', 'R', 'B', 'N', 'P'];
+
+// Per table flags
+const FLAG_STM: u8 = 1;
+const FLAG_MAPPED: u8 = 2;
+const FLAG_WIN_PLIES: u8 = 4;
+const FLAG_LOSS_PLIES: u8 = 8;
+const FLAG_WIDE: u8 = 16;
+const FLAG_SINGLE_VALUE: u8 = 128;
+
+/// Win/draw/loss for the side to move. Cursed wins and blessed losses are decided by the
+/// 50-move rule, so they end up as draws with correct play.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Wdl {
+    Loss,
+    BlessedLoss,
+    Draw,
+    CursedWin,
+    Win,
+}
+
+impl Wdl {
+    fn new(value: i32) -> Self {
+        match value {
+            -2 => Wdl::Loss,
+            -1 => Wdl::BlessedLoss,
+            0 => Wdl::Draw,
+            1 => Wdl::CursedWin,
+            _ => Wdl::Win,
+        }
+    }
+    fn value(&self) -> i32 {
+        *self as i32 - 2
+    }
+    pub fn opposite(&self) -> Self {
+        Wdl::new(-self.value())
+    }
+    //The DTZ of the move leading to a position with this result, when it resets the 50-move
+    //counter
+    fn dtz_before_zeroing(&self) -> i32 {
+        match self {
+            Wdl::Win => 1,
+            Wdl::CursedWin => 101,
+            Wdl::Draw => 0,
+            Wdl::BlessedLoss => -101,
+            Wdl::Loss => -1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum TableKind {
+    Wdl,
+    Dtz,
+}
+
+impl TableKind {
+    fn extension(&self) -> &'static str {
+        match self {
+            TableKind::Wdl => "rtbw",
+            TableKind::Dtz => "rtbz",
+        }
+    }
+}
+
+//Lookup tables for turning piece placements into table indices
+struct Encoding {
+    map_b1h1h7: [usize; 64],
+    map_a1d1d4: [usize; 64],
+    map_kk: [[usize; 64]; 10],
+    binomial: [[u64; 64]; 6],
+    map_pawns: [usize; 64],
+    lead_pawn_idx: [[u64; 64]; 6],
+    lead_pawns_size: [[u64; 4]; 6],
+}
+
+fn file_of(sq: usize) -> usize {
+    sq & 7
+}
+fn rank_of(sq: usize) -> usize {
+    sq >> 3
+}
+fn off_a1h8(sq: usize) -> i32 {
+    rank_of(sq) as i32 - file_of(sq) as i32
+}
+
+impl Encoding {
+    fn get() -> &'static Encoding {
+        static ENCODING: OnceLock<Encoding> = OnceLock::new();
+        ENCODING.get_or_init(Encoding::new)
+    }
+    fn new() -> Self {
+        let mut map_b1h1h7 = [0; 64];
+        let mut code = 0;
+        for (sq, entry) in map_b1h1h7.iter_mut().enumerate() {
+            if off_a1h8(sq) < 0 {
+                *entry = code;
+                code += 1;
+            }
+        }
+
+        let mut map_a1d1d4 = [usize::MAX; 64];
+        let mut diagonal = vec![];
+        code = 0;
+        for (sq, entry) in map_a1d1d4.iter_mut().enumerate().take(28) {
+            if off_a1h8(sq) < 0 && file_of(sq) <= 3 {
+                *entry = code;
+                code += 1;
+            } else if off_a1h8(sq) == 0 && file_of(sq) <= 3 {
+                diagonal.push(sq);
+            }
+        }
+        //Squares on the diagonal come last
+        for sq in diagonal {
+            map_a1d1d4[sq] = code;
+            code += 1;
+        }
+
+        //The 462 placements of two kings with the first one in the a1-d1-d4 triangle
+        let mut map_kk = [[0; 64]; 10];
+        let mut both_on_diagonal = vec![];
+        code = 0;
+        for (idx, row) in map_kk.iter_mut().enumerate() {
+            for sq1 in (0..=27).filter(|&sq| map_a1d1d4[sq] == idx) {
+                for (sq2, entry) in row.iter_mut().enumerate() {
+                    let touching = file_of(sq1).abs_diff(file_of(sq2)) <= 1
+                        && rank_of(sq1).abs_diff(rank_of(sq2)) <= 1;
+                    if touching || (off_a1h8(sq1) == 0 && off_a1h8(sq2) > 0) {
+                        continue;
+                    }
+                    if off_a1h8(sq1) == 0 && off_a1h8(sq2) == 0 {
+                        both_on_diagonal.push((idx, sq2));
+                    } else {
+                        *entry = code;
+                        code += 1;
+                    }
+                }
+            }
+        }
+        for (idx, sq2) in both_on_diagonal {
+            map_kk[idx][sq2] = code;
+            code += 1;
+        }
+
+        let mut binomial = [[0; 64]; 6];
+        binomial[0][0] = 1;
+        for n in 1..64 {
+            for k in 0..6.min(n + 1) {
+                binomial[k][n] = if k > 0 { binomial[k - 1][n - 1] } else { 0 }
+                    + if k < n { binomial[k][n - 1] } else { 0 };
+            }
+        }
+
+        //Pawns nearer the edge and further back get the higher values, the one with the
+        //highest value leads
+        let mut map_pawns = [0; 64];
+        let mut lead_pawn_idx = [[0; 64]; 6];
+        let mut lead_pawns_size = [[0; 4]; 6];
+        let mut available_squares: usize = 47;
+        for lead_pawns in 1..=5 {
+            for (file, size) in lead_pawns_size[lead_pawns].iter_mut().enumerate() {
+                let mut idx = 0;
+                for rank in 1..7 {
+                    let sq = rank * 8 + file;
+                    if lead_pawns == 1 {
+                        map_pawns[sq] = available_squares;
+                        map_pawns[sq ^ 7] = available_squares - 1;
+                        //The last pair takes squares 1 and 0
+                        available_squares = available_squares.saturating_sub(2);
+                    }
+                    lead_pawn_idx[lead_pawns][sq] = idx;
+                    idx += binomial[lead_pawns - 1][map_pawns[sq]];
+                }
+                *size = idx;
+            }
+        }
+
+        Self {
+            map_b1h1h7,
+            map_a1d1d4,
+            map_kk,
+            binomial,
+            map_pawns,
+            lead_pawn_idx,
+            lead_pawns_size,
+        }
+    }
+}
+
+//Reads are checked so a truncated or corrupt table is rejected instead of panicking
+fn read_u8(data: &[u8], offset: usize) -> Option<u8> {
+    data.get(offset).copied()
+}
+fn read_bytes<const N: usize>(data: &[u8], offset: usize) -> Option<[u8; N]> {
+    data.get(offset..offset.checked_add(N)?)?.try_into().ok()
+}
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    read_bytes(data, offset).map(u16::from_le_bytes)
+}
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    read_bytes(data, offset).map(u32::from_le_bytes)
+}
+fn read_u32_be(data: &[u8], offset: usize) -> Option<u32> {
+    read_bytes(data, offset).map(u32::from_be_bytes)
+}
+fn read_u64_be(data: &[u8], offset: usize) -> Option<u64> {
+    read_bytes(data, offset).map(u64::from_be_bytes)
+}
+
+/// One compressed sub-table: the values for one side to move and, with pawns, one file of
+/// the leading pawn. Offsets point into the file data of the table.
+#[derive(Debug, Clone, Default)]
+struct PairsData {
+    flags: u8,
+    pieces: [u8; MAX_PIECES],
+    group_len: [usize; MAX_PIECES + 1],
+    group_idx: [u64; MAX_PIECES + 1],
+    block_size: u64,
+    span: u64,
+    sparse_index_size: u64,
+    blocks_num: u64,
+    block_length_size: u64,
+    min_sym_len: u8,
+    lowest_sym: usize,
+    base64: Vec<u64>,
+    symlen: Vec<u8>,
+    btree: usize,
+    sparse_index: usize,
+    block_length: usize,
+    data: usize,
+    //DTZ only, where the value map for each kind of result starts and its length
+    map_idx: [usize; 4],
+    map_len: [usize; 4],
+}
+
+struct Table {
+    data: Vec<u8>,
+    kind: TableKind,
+    has_pawns: bool,
+    //Both sides have the same material, so only white to move is stored
+    symmetric: bool,
+    has_unique_pieces: bool,
+    pawn_count: [usize; 2],
+    piece_count: usize,
+    //Indexed by side to move, then by the file of the leading pawn
+    pairs: Vec<Vec<PairsData>>,
+}
+
+impl Table {
+    fn new(name: &str, data: Vec<u8>, kind: TableKind) -> Option<Self> {
+        let (white, black) = name.split_once('v')?;
+        let count = |side: &str, piece: char| side.chars().filter(|&c| c == piece).count();
+        let has_pawns = name.contains('P');
+        let symmetric = white == black;
+        let has_unique_pieces = [white, black].iter().any(|side| {
+            PIECE_CHARS[1..]
+                .iter()
+                .any(|&piece| count(side, piece) == 1)
+        });
+        let (white_pawns, black_pawns) = (count(white, 'P'), count(black, 'P'));
+        //The side with fewer pawns leads as that compresses better
+        let white_leads = black_pawns == 0 || (white_pawns > 0 && black_pawns >= white_pawns);
+        let pawn_count = if white_leads {
+            [white_pawns, black_pawns]
+        } else {
+            [black_pawns, white_pawns]
+        };
+
+        let mut table = Self {
+            data,
+            kind,
+            has_pawns,
+            symmetric,
+            has_unique_pieces,
+            pawn_count,
+            piece_count: white.len() + black.len(),
+            pairs: vec![],
+        };
+        table.parse().map(|_| table)
+    }
+    fn parse(&mut self) -> Option<()> {
+        let magic = match self.kind {
+            TableKind::Wdl => WDL_MAGIC,
+            TableKind::Dtz => DTZ_MAGIC,
+        };
+        let data = &self.data;
+        if read_bytes::<4>(data, 0)? != magic || self.piece_count > MAX_PIECES {
+            return None;
+        }
+        if ((read_u8(data, 4)? & 2) != 0) != self.has_pawns {
+            return None;
+        }
+        let mut pos = 5;
+
+        let sides = if self.kind == TableKind::Wdl && !self.symmetric {
+            2
+        } else {
+            1
+        };
+        let files = if self.has_pawns { 4 } else { 1 };
+        let both_pawns = self.has_pawns && self.pawn_count[1] > 0;
+        let mut pairs = vec![vec![PairsData::default(); files]; sides];
+
+        for file in 0..files {
+            let lead_order = read_u8(data, pos)?;
+            let pawn_order = if both_pawns {
+                read_u8(data, pos + 1)?
+            } else {
+                0xFF
+            };
+            let order = [
+                [lead_order & 0xF, pawn_order & 0xF],
+                [lead_order >> 4, pawn_order >> 4],
+            ];
+            pos += 1 + both_pawns as usize;
+            for k in 0..self.piece_count {
+                let piece = read_u8(data, pos)?;
+                if [piece & 0xF, piece >> 4]
+                    .iter()
+                    .any(|code| !matches!(code, 1..=6 | 9..=14))
+                {
+                    return None;
+                }
+                for (side, side_pairs) in pairs.iter_mut().enumerate() {
+                    side_pairs[file].pieces[k] = if side == 1 { piece >> 4 } else { piece & 0xF };
+                }
+                pos += 1;
+            }
+            for (side, side_pairs) in pairs.iter_mut().enumerate() {
+                self.set_groups(&mut side_pairs[file], order[side], file)?;
+            }
+        }
+        pos += pos & 1;
+
+        for file in 0..files {
+            for side_pairs in pairs.iter_mut() {
+                pos = Self::set_sizes(data, &mut side_pairs[file], pos)?;
+            }
+        }
+
+        if self.kind == TableKind::Dtz {
+            for file in 0..files {
+                let pairs = &mut pairs[0][file];
+                if pairs.flags & FLAG_MAPPED == 0 {
+                    continue;
+                }
+                //Each map starts with its length
+                if pairs.flags & FLAG_WIDE != 0 {
+                    pos += pos & 1;
+                    for (map_idx, map_len) in pairs.map_idx.iter_mut().zip(&mut pairs.map_len) {
+                        *map_len = read_u16(data, pos)? as usize;
+                        *map_idx = pos + 2;
+                        pos += 2 * *map_len + 2;
+                    }
+                } else {
+                    for (map_idx, map_len) in pairs.map_idx.iter_mut().zip(&mut pairs.map_len) {
+                        *map_len = read_u8(data, pos)? as usize;
+                        *map_idx = pos + 1;
+                        pos += *map_len + 1;
+                    }
+                }
+            }
+            pos += pos & 1;
+        }
+
+        for file in 0..files {
+            for side_pairs in pairs.iter_mut() {
+                side_pairs[file].sparse_index = pos;
+                pos += side_pairs[file].sparse_index_size as usize * 6;
+            }
+        }
+        for file in 0..files {
+            for side_pairs in pairs.iter_mut() {
+                side_pairs[file].block_length = pos;
+                pos += side_pairs[file].block_length_size as usize * 2;
+            }
+        }
+        for file in 0..files {
+            for side_pairs in pairs.iter_mut() {
+                pos = pos.checked_add(0x3F)? & !0x3F;
+                side_pairs[file].data = pos;
+                let blocks_size = side_pairs[file]
+                    .blocks_num
+                    .checked_mul(side_pairs[file].block_size)?;
+                pos = pos.checked_add(usize::try_from(blocks_size).ok()?)?;
+                //Tables holding a single value end without any blocks
+                if blocks_size > 0 && pos > data.len() {
+                    return None;
+                }
+            }
+        }
+        if !pairs
+            .iter()
+            .flatten()
+            .all(|pairs| Self::check_offsets(data, pairs))
+        {
+            return None;
+        }
+
+        self.pairs = pairs;
+        Some(())
+    }
+    //Makes sure every offset decompress_pairs may follow stays inside the table
+    fn check_offsets(data: &[u8], pairs: &PairsData) -> bool {
+        if pairs.flags & FLAG_SINGLE_VALUE != 0 {
+            return true;
+        }
+        let in_data = |start: usize, len: u64| {
+            usize::try_from(len)
+                .ok()
+                .and_then(|len| start.checked_add(len))
+                .is_some_and(|end| end <= data.len())
+        };
+        if !in_data(pairs.sparse_index, pairs.sparse_index_size * 6)
+            || !in_data(pairs.block_length, pairs.block_length_size * 2)
+            || pairs.blocks_num == 0
+            || pairs.blocks_num > pairs.block_length_size
+            || pairs.block_size < 8
+        {
+            return false;
+        }
+        (0..pairs.sparse_index_size as usize).all(|k| {
+            read_u32(data, pairs.sparse_index + 6 * k)
+                .is_some_and(|block| (block as u64) < pairs.blocks_num)
+        })
+    }
+    //Splits the pieces into groups that are indexed together. The index of a position is
+    //g1 * N(g2) * N(g3) + g2 * N(g3) + g3 where N(g) counts the placements of group g, and
+    //the order of the groups is stored in the file.
+    fn set_groups(&self, pairs: &mut PairsData, order: [u8; 2], file: usize) -> Option<()> {
+        let encoding = Encoding::get();
+        let mut n = 0;
+        let mut first_len: i32 = if self.has_pawns {
+            0
+        } else if self.has_unique_pieces {
+            3
+        } else {
+            2
+        };
+        pairs.group_len[0] = 1;
+        for i in 1..self.piece_count {
+            first_len -= 1;
+            //The leading group takes the first pieces, then each run of equal pieces is a group
+            if first_len > 0 || pairs.pieces[i] == pairs.pieces[i - 1] {
+                pairs.group_len[n] += 1;
+            } else {
+                n += 1;
+                pairs.group_len[n] = 1;
+            }
+        }
+        n += 1;
+        pairs.group_len[n] = 0;
+        //Groups index into the lookup tables, which go up to five pieces
+        if pairs.group_len[..n].iter().any(|&len| len > 5) {
+            return None;
+        }
+
+        let both_pawns = self.has_pawns && self.pawn_count[1] > 0;
+        let mut next = if both_pawns { 2 } else { 1 };
+        let mut free_squares =
+            64 - pairs.group_len[0] - if both_pawns { pairs.group_len[1] } else { 0 };
+        let mut idx: u64 = 1;
+        let mut k = 0;
+        while next < n || k == order[0] as usize || k == order[1] as usize {
+            if k == order[0] as usize {
+                //Leading pawns or pieces
+                pairs.group_idx[0] = idx;
+                idx *= if self.has_pawns {
+                    encoding.lead_pawns_size[pairs.group_len[0]][file]
+                } else if self.has_unique_pieces {
+                    31332
+                } else {
+                    462
+                };
+            } else if k == order[1] as usize {
+                //Remaining pawns
+                pairs.group_idx[1] = idx;
+                idx *= encoding.binomial[pairs.group_len[1]][48 - pairs.group_len[0]];
+            } else {
+                pairs.group_idx[next] = idx;
+                idx *= encoding.binomial[pairs.group_len[next]][free_squares];
+                free_squares -= pairs.group_len[next];
+                next += 1;
+            }
+            k += 1;
+            //The order stored in the file names a group that doesn't exist
+            if k > MAX_PIECES {
+                return None;
+            }
+        }
+        pairs.group_idx[n] = idx;
+        Some(())
+    }
+    fn set_sizes(data: &[u8], pairs: &mut PairsData, mut pos: usize) -> Option<usize> {
+        pairs.flags = read_u8(data, pos)?;
+        pos += 1;
+        if pairs.flags & FLAG_SINGLE_VALUE != 0 {
+            //The single value is kept as the minimum symbol length
+            pairs.min_sym_len = read_u8(data, pos)?;
+            return Some(pos + 1);
+        }
+
+        let group_count = pairs.group_len.iter().position(|&len| len == 0)?;
+        let table_size = pairs.group_idx[group_count];
+        let [block_size_log2, span_log2, padding] = read_bytes(data, pos)?;
+        if block_size_log2 >= 32 || span_log2 >= 32 {
+            return None;
+        }
+        pairs.block_size = 1 << block_size_log2;
+        pairs.span = 1 << span_log2;
+        pairs.sparse_index_size = table_size.div_ceil(pairs.span);
+        pairs.blocks_num = read_u32(data, pos + 3)? as u64;
+        pairs.block_length_size = pairs.blocks_num + padding as u64;
+        let max_sym_len = read_u8(data, pos + 7)?;
+        pairs.min_sym_len = read_u8(data, pos + 8)?;
+        pos += 9;
+        //Codes are read from a 64-bit buffer that is refilled 32 bits at a time
+        if pairs.min_sym_len == 0 || max_sym_len < pairs.min_sym_len || max_sym_len > 32 {
+            return None;
+        }
+
+        //Canonical Huffman code: longer codes have lower values, base64[i] is the lowest code
+        //of length min_sym_len + i padded to 64 bits
+        pairs.lowest_sym = pos;
+        let lengths = (max_sym_len - pairs.min_sym_len) as usize + 1;
+        let lowest_sym = (0..lengths)
+            .map(|i| read_u16(data, pos + 2 * i).map(u64::from))
+            .collect::<Option<Vec<_>>>()?;
+        let mut base64 = vec![0u64; lengths];
+        for i in (0..lengths - 1).rev() {
+            base64[i] = (base64[i + 1]
+                .wrapping_add(lowest_sym[i])
+                .wrapping_sub(lowest_sym[i + 1]))
+                / 2;
+        }
+        for (i, base) in base64.iter_mut().enumerate() {
+            *base <<= 64 - i - pairs.min_sym_len as usize;
+        }
+        pairs.base64 = base64;
+        pos += lengths * 2;
+
+        //Recursive pairing: every symbol stands for a pair of smaller symbols
+        let symbols = read_u16(data, pos)? as usize;
+        pos += 2;
+        if symbols == 0 || symbols >= 0xFFF || pos + symbols * 3 > data.len() {
+            return None;
+        }
+        //Every code must decode to a symbol of the tree
+        if lowest_sym.iter().any(|&sym| sym as usize >= symbols) {
+            return None;
+        }
+        pairs.btree = pos;
+        pairs.symlen = vec![0; symbols];
+        let mut visited = vec![false; symbols];
+        for sym in 0..symbols {
+            if !visited[sym] {
+                pairs.symlen[sym] = Self::set_symlen(data, pairs, sym, &mut visited)?;
+            }
+        }
+        Some(pos + symbols * 3 + (symbols & 1))
+    }
+    fn set_symlen(
+        data: &[u8],
+        pairs: &mut PairsData,
+        sym: usize,
+        visited: &mut [bool],
+    ) -> Option<u8> {
+        visited[sym] = true;
+        let right = Self::btree_right(data, pairs, sym)?;
+        if right == 0xFFF {
+            return Some(0);
+        }
+        let left = Self::btree_left(data, pairs, sym)?;
+        if left >= pairs.symlen.len() || right >= pairs.symlen.len() {
+            return None;
+        }
+        if !visited[left] {
+            pairs.symlen[left] = Self::set_symlen(data, pairs, left, visited)?;
+        }
+        if !visited[right] {
+            pairs.symlen[right] = Self::set_symlen(data, pairs, right, visited)?;
+        }
+        //A symbol expands to at most 256 values
+        pairs.symlen[left]
+            .checked_add(pairs.symlen[right])?
+            .checked_add(1)
+    }
+    fn btree_left(data: &[u8], pairs: &PairsData, sym: usize) -> Option<usize> {
+        let [low, mid, _] = read_bytes(data, pairs.btree + sym * 3)?;
+        Some(((mid as usize & 0xF) << 8) | low as usize)
+    }
+    fn btree_right(data: &[u8], pairs: &PairsData, sym: usize) -> Option<usize> {
+        let [_, mid, high] = read_bytes(data, pairs.btree + sym * 3)?;
+        Some(((high as usize) << 4) | (mid as usize >> 4))
+    }
+    //Finds the value stored at `idx`, None if the table is corrupt
+    fn decompress_pairs(&self, pairs: &PairsData, idx: u64) -> Option<i32> {
+        if pairs.flags & FLAG_SINGLE_VALUE != 0 {
+            return Some(pairs.min_sym_len as i32);
+        }
+        let data = &self.data;
+
+        //The sparse index points into the blocks every `span` values, walk the block lengths
+        //from there to the block holding idx
+        let k = (idx / pairs.span) as usize;
+        let mut block = read_u32(data, pairs.sparse_index + 6 * k)? as usize;
+        let mut offset = read_u16(data, pairs.sparse_index + 6 * k + 4)? as i64;
+        offset += (idx % pairs.span) as i64 - (pairs.span / 2) as i64;
+        let block_length = |block: usize| {
+            read_u16(data, pairs.block_length + 2 * block).map(|length| length as i64)
+        };
+        while offset < 0 {
+            block = block.checked_sub(1)?;
+            offset += block_length(block)? + 1;
+        }
+        while offset > block_length(block)? {
+            offset -= block_length(block)? + 1;
+            block += 1;
+        }
+        if block as u64 >= pairs.blocks_num {
+            return None;
+        }
+
+        //The last code of a block may be followed by the end of the file, read zeros there
+        let block_end = pairs.data + (block + 1) * pairs.block_size as usize;
+        let read_bits = |ptr: usize| {
+            if ptr + 4 <= block_end {
+                read_u32_be(data, ptr)
+            } else {
+                Some(0)
+            }
+        };
+        let mut ptr = pairs.data + block * pairs.block_size as usize;
+        let mut buf64 = read_u64_be(data, ptr)?;
+        ptr += 8;
+        let mut buf64_size = 64;
+        let min_sym_len = pairs.min_sym_len as usize;
+        let mut sym;
+        loop {
+            let mut len = 0;
+            while buf64 < pairs.base64[len] {
+                len += 1;
+            }
+            sym = ((buf64 - pairs.base64[len]) >> (64 - len - min_sym_len)) as usize;
+            sym += read_u16(data, pairs.lowest_sym + 2 * len)? as usize;
+            if offset < *pairs.symlen.get(sym)? as i64 + 1 {
+                break;
+            }
+            offset -= pairs.symlen[sym] as i64 + 1;
+            len += min_sym_len;
+            buf64 <<= len;
+            buf64_size -= len;
+            if buf64_size <= 32 {
+                buf64_size += 32;
+                buf64 |= (read_bits(ptr)? as u64) << (64 - buf64_size);
+                ptr += 4;
+            }
+        }
+
+        //Expand the symbol down to the single value at our offset
+        //Symbols were checked when loading, so the tree only leads to known symbols
+        while pairs.symlen[sym] != 0 {
+            let left = Self::btree_left(data, pairs, sym)?;
+            if offset < pairs.symlen[left] as i64 + 1 {
+                sym = left;
+            } else {
+                offset -= pairs.symlen[left] as i64 + 1;
+                sym = Self::btree_right(data, pairs, sym)?;
+            }
+        }
+        Self::btree_left(data, pairs, sym).map(|value| value as i32)
+    }
+    //Turns a DTZ table value into plies, `wdl` being the result of the position
+    fn map_dtz(&self, file: usize, value: i32, wdl: Wdl) -> Option<i32> {
+        let pairs = &self.pairs[0][file];
+        let mut value = value;
+        if pairs.flags & FLAG_MAPPED != 0 {
+            let map = match wdl {
+                Wdl::Loss => 1,
+                Wdl::BlessedLoss => 3,
+                Wdl::CursedWin => 2,
+                Wdl::Draw | Wdl::Win => 0,
+            };
+            let entry = usize::try_from(value).ok()?;
+            if entry >= pairs.map_len[map] {
+                return None;
+            }
+            value = if pairs.flags & FLAG_WIDE != 0 {
+                read_u16(&self.data, pairs.map_idx[map] + 2 * entry)? as i32
+            } else {
+                read_u8(&self.data, pairs.map_idx[map] + entry)? as i32
+            };
+        }
+        if (wdl == Wdl::Win && pairs.flags & FLAG_WIN_PLIES == 0)
+            || (wdl == Wdl::Loss && pairs.flags & FLAG_LOSS_PLIES == 0)
+            || wdl == Wdl::CursedWin
+            || wdl == Wdl::BlessedLoss
+        {
+            value *= 2;
+        }
+        Some(value + 1)
+    }
+    /// Looks up the stored value of the position. With `flip` the table is for the other
+    /// colour, so colours are swapped and the board is mirrored. Fails with ChangeStm if the
+    /// DTZ table only holds the other side to move.
+    fn probe(&self, board: &ChessBoard, flip: bool, wdl: Wdl) -> Result<i32, Probe> {
+        let encoding = Encoding::get();
+        let flip_color = if flip { 8 } else { 0 };
+        let flip_squares = if flip { 56 } else { 0 };
+        let stm = flip as usize ^ (board.color_to_move == ChessColor::BLACK) as usize;
+
+        let mut squares = [0usize; MAX_PIECES];
+        let mut pieces = [0u8; MAX_PIECES];
+        let mut size = 0;
+        let mut lead_pawns = 0;
+        let mut lead_pawn_code = None;
+        let mut file = 0;
+
+        if self.has_pawns {
+            let lead = self.pairs[0][0].pieces[0] ^ flip_color;
+            lead_pawn_code = Some(lead);
+            for sq in 0..CHESS_BOARD_SIZE {
+                if piece_code(board.layout[sq ^ 56]) == lead {
+                    squares[size] = sq ^ flip_squares;
+                    size += 1;
+                }
+            }
+            lead_pawns = size;
+            let lead_idx = (0..lead_pawns)
+                .max_by_key(|&i| encoding.map_pawns[squares[i]])
+                .unwrap();
+            squares.swap(0, lead_idx);
+            file = file_of(squares[0]).min(7 - file_of(squares[0]));
+        }
+
+        if self.kind == TableKind::Dtz {
+            let flags = self.pairs[0][file].flags;
+            if (flags & FLAG_STM) as usize != stm && (!self.symmetric || self.has_pawns) {
+                return Err(Probe::ChangeStm);
+            }
+        }
+
+        for sq in 0..CHESS_BOARD_SIZE {
+            let code = piece_code(board.layout[sq ^ 56]);
+            if code == 0 || Some(code) == lead_pawn_code {
+                continue;
+            }
+            squares[size] = sq ^ flip_squares;
+            pieces[size] = code ^ flip_color;
+            size += 1;
+        }
+
+        let pairs = &self.pairs[stm % self.pairs.len()][file];
+
+        //Put the pieces in the order they are stored in
+        for i in lead_pawns..size.saturating_sub(1) {
+            for j in i + 1..size {
+                if pairs.pieces[i] == pieces[j] {
+                    pieces.swap(i, j);
+                    squares.swap(i, j);
+                    break;
+                }
+            }
+        }
+
+        //Mirror so the leading piece is on files a-d
+        if file_of(squares[0]) > 3 {
+            for sq in squares[..size].iter_mut() {
+                *sq ^= 7;
+            }
+        }
+
+        let mut idx: u64;
+        if self.has_pawns {
+            idx = encoding.lead_pawn_idx[lead_pawns][squares[0]];
+            squares[1..lead_pawns].sort_by_key(|&sq| encoding.map_pawns[sq]);
+            for (i, &sq) in squares.iter().enumerate().take(lead_pawns).skip(1) {
+                idx += encoding.binomial[i][encoding.map_pawns[sq]];
+            }
+        } else {
+            //Without pawns the board can also be mirrored vertically and diagonally, so the
+            //leading piece ends up in the a1-d1-d4 triangle
+            if rank_of(squares[0]) > 3 {
+                for sq in squares[..size].iter_mut() {
+                    *sq ^= 56;
+                }
+            }
+            for i in 0..pairs.group_len[0] {
+                if off_a1h8(squares[i]) == 0 {
+                    continue;
+                }
+                if off_a1h8(squares[i]) > 0 {
+                    for sq in squares[i..size].iter_mut() {
+                        *sq = ((*sq >> 3) | (*sq << 3)) & 63;
+                    }
+                }
+                break;
+            }
+
+            if self.has_unique_pieces {
+                let adjust1 = (squares[1] > squares[0]) as usize;
+                let adjust2 =
+                    (squares[2] > squares[0]) as usize + (squares[2] > squares[1]) as usize;
+                idx = if off_a1h8(squares[0]) != 0 {
+                    ((encoding.map_a1d1d4[squares[0]] * 63 + squares[1] - adjust1) * 62
+                        + squares[2]
+                        - adjust2) as u64
+                } else if off_a1h8(squares[1]) != 0 {
+                    ((6 * 63 + rank_of(squares[0]) * 28 + encoding.map_b1h1h7[squares[1]]) * 62
+                        + squares[2]
+                        - adjust2) as u64
+                } else if off_a1h8(squares[2]) != 0 {
+                    (6 * 63 * 62
+                        + 4 * 28 * 62
+                        + rank_of(squares[0]) * 7 * 28
+                        + (rank_of(squares[1]) - adjust1) * 28
+                        + encoding.map_b1h1h7[squares[2]]) as u64
+                } else {
+                    (6 * 63 * 62
+                        + 4 * 28 * 62
+                        + 4 * 7 * 28
+                        + rank_of(squares[0]) * 7 * 6
+                        + (rank_of(squares[1]) - adjust1) * 6
+                        + (rank_of(squares[2]) - adjust2)) as u64
+                };
+            } else {
+                idx = encoding.map_kk[encoding.map_a1d1d4[squares[0]]][squares[1]] as u64;
+            }
+        }
+
+        //The other groups, each as a combination of squares not taken by earlier groups
+        idx *= pairs.group_idx[0];
+        let mut group_start = pairs.group_len[0];
+        let mut remaining_pawns = self.has_pawns && self.pawn_count[1] > 0;
+        let mut next = 1;
+        while pairs.group_len[next] != 0 {
+            let group_end = group_start + pairs.group_len[next];
+            squares[group_start..group_end].sort_unstable();
+            let mut n = 0;
+            for i in 0..pairs.group_len[next] {
+                let sq = squares[group_start + i];
+                let adjust = squares[..group_start].iter().filter(|&&s| sq > s).count();
+                n += encoding.binomial[i + 1][sq - adjust - 8 * remaining_pawns as usize];
+            }
+            remaining_pawns = false;
+            idx += n * pairs.group_idx[next];
+            group_start = group_end;
+            next += 1;
+        }
+
+        let value = self.decompress_pairs(pairs, idx).ok_or(Probe::Fail)?;
+        match self.kind {
+            TableKind::Wdl => Ok(value - 2),
+            TableKind::Dtz => self.map_dtz(file, value, wdl).ok_or(Probe::Fail),
+        }
+    }
+}
+
+//Piece code used by the tables: pawn 1 to king 6, plus 8 for black
+fn piece_code(piece: ChessPiece) -> u8 {
+    if piece.is_empty() {
+        return 0;
+    }
+    let code = piece.get_type_idx() as u8 + 1;
+    if piece.contains(ChessPiece::BLACK) {
+        code + 8
+    } else {
+        code
+    }
+}
+
+//Table name and kind, e.g. KQvK and WDL for KQvK.rtbw
+type TableKey = (String, TableKind);
+
+/// Syzygy WDL and DTZ tables found in a list of directories. Tables are read from disk the
+/// first time a position needs them.
+pub struct Tablebase {
+    directories: Vec<PathBuf>,
+    available: HashSet<TableKey>,
+    max_pieces: usize,
+    tables: RwLock<HashMap<TableKey, Option<Arc<Table>>>>,
+}
+
+impl Tablebase {
+    /// `path` lists directories separated like the PATH variable.
+    pub fn new(path: &str) -> Self {
+        let directories: Vec<PathBuf> = std::env::split_paths(path).collect();
+        let mut available = HashSet::new();
+        let mut max_pieces = 0;
+        for directory in &directories {
+            let Ok(entries) = fs::read_dir(directory) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                let (Some(name), Some(extension)) = (path.file_stem(), path.extension()) else {
+                    continue;
+                };
+                let name = name.to_string_lossy().to_string();
+                let kind = match extension.to_str() {
+                    Some("rtbw") => TableKind::Wdl,
+                    Some("rtbz") => TableKind::Dtz,
+                    _ => continue,
+                };
+                if kind == TableKind::Wdl {
+                    max_pieces = max_pieces.max(name.len().saturating_sub(1));
+                }
+                available.insert((name, kind));
+            }
+        }
+        Self {
+            directories,
+            available,
+            max_pieces: max_pieces.min(MAX_PIECES),
+            tables: RwLock::default(),
+        }
+    }
+    /// Most pieces, kings included, of any WDL table found.
+    pub fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+    pub fn table_count(&self) -> usize {
+        self.available.len()
+    }
+    //The table holding the position, and whether its colours are reversed
+    fn table(&self, board: &ChessBoard, kind: TableKind) -> Option<(Arc<Table>, bool)> {
+        let white_first = board.material_signature(ChessColor::WHITE);
+        let (name, black_stronger) = if self.available.contains(&(white_first.clone(), kind)) {
+            (white_first, false)
+        } else {
+            (board.material_signature(ChessColor::BLACK), true)
+        };
+        let key = (name, kind);
+        if !self.available.contains(&key) {
+            return None;
+        }
+
+        if let Some(table) = self.tables.read().unwrap().get(&key) {
+            return table.clone().map(|table| (table, black_stronger));
+        }
+        let file_name = format!("{}.{}", key.0, kind.extension());
+        let table = self
+            .directories
+            .iter()
+            .find_map(|directory| fs::read(directory.join(&file_name)).ok())
+            .and_then(|data| Table::new(&key.0, data, kind))
+            .map(Arc::new);
+        self.tables.write().unwrap().insert(key, table.clone());
+        table.map(|table| (table, black_stronger))
+    }
+    fn probe_table(&self, board: &ChessBoard, kind: TableKind, wdl: Wdl) -> Result<i32, Probe> {
+        //Only the kings are left
+        if board.piece_count() == 2 {
+            return Ok(0);
+        }
+        let (table, black_stronger) = self.table(board, kind).ok_or(Probe::Fail)?;
+        let flip = black_stronger || (table.symmetric && board.color_to_move == ChessColor::BLACK);
+        table.probe(board, flip, wdl)
+    }
+    //Tables hold don't-care values where a capture is best and know nothing of en passant, so
+    //captures are searched first. Also tells whether the best move resets the 50-move counter.
+    fn search(
+        &self,
+        board: &mut ChessBoard,
+        check_zeroing_moves: bool,
+    ) -> Result<(Wdl, bool), Probe> {
+        let moves = board.generate_legal_moves();
+        let mut best = Wdl::Loss;
+        let mut searched = 0;
+        for chess_move in &moves {
+            let is_pawn_move =
+                board.layout[chess_move.get_idx().0 as usize].contains(ChessPiece::PAWN);
+            let zeroing = board.is_capture(chess_move) || (check_zeroing_moves && is_pawn_move);
+            if !zeroing {
+                continue;
+            }
+            searched += 1;
+            board.make_move_unchecked(chess_move);
+            let result = self.search(board, false);
+            board.unmake_move(chess_move);
+            let wdl = result?.0.opposite();
+            if wdl > best {
+                best = wdl;
+                if wdl == Wdl::Win {
+                    return Ok((wdl, true));
+                }
+            }
+        }
+
+        let no_more_moves = searched > 0 && searched == moves.len();
+        let wdl = if no_more_moves {
+            best
+        } else {
+            match self.probe_table(board, TableKind::Wdl, Wdl::Draw) {
+                Ok(value) => Wdl::new(value),
+                Err(_) => return Err(Probe::Fail),
+            }
+        };
+        if best >= wdl {
+            Ok((best, best > Wdl::Draw || no_more_moves))
+        } else {
+            Ok((wdl, false))
+        }
+    }
+    /// Win/draw/loss for the side to move, assuming the 50-move counter was just reset.
+    pub fn probe_wdl(&self, board: &mut ChessBoard) -> Option<Wdl> {
+        self.search(board, false).ok().map(|(wdl, _)| wdl)
+    }
+    /// Plies to the next capture or pawn move on the way to the result, negative when losing
+    /// and 0 for a draw. Counts exceeding 100 are cursed wins or blessed losses.
+    pub fn probe_dtz(&self, board: &mut ChessBoard) -> Option<i32> {
+        let (wdl, zeroing_is_best) = self.search(board, true).ok()?;
+        if wdl == Wdl::Draw {
+            return Some(0);
+        }
+        if zeroing_is_best {
+            return Some(wdl.dtz_before_zeroing());
+        }
+        let sign = wdl.value().signum();
+        match self.probe_table(board, TableKind::Dtz, wdl) {
+            Ok(dtz) => {
+                let cursed = matches!(wdl, Wdl::CursedWin | Wdl::BlessedLoss);
+                return Some((dtz + 100 * cursed as i32) * sign);
+            }
+            Err(Probe::Fail) => return None,
+            Err(Probe::ChangeStm) => (),
+        }
+
+        //The table only holds the other side to move, so look one move ahead
+        let mut min_dtz = i32::MAX;
+        for chess_move in board.generate_legal_moves() {
+            let zeroing = board.is_capture(&chess_move)
+                || board.layout[chess_move.get_idx().0 as usize].contains(ChessPiece::PAWN);
+            board.make_move_unchecked(&chess_move);
+            let dtz = if zeroing {
+                self.search(board, false)
+                    .map(|(wdl, _)| -wdl.dtz_before_zeroing())
+                    .ok()
+            } else {
+                self.probe_dtz(board).map(|dtz| -dtz)
+            };
+            let mates = board.in_check() && board.generate_legal_moves().is_empty();
+            board.unmake_move(&chess_move);
+
+            let mut dtz = dtz?;
+            if dtz == 1 && mates {
+                min_dtz = 1;
+            }
+            if !zeroing {
+                dtz += dtz.signum();
+            }
+            if dtz < min_dtz && dtz.signum() == sign {
+                min_dtz = dtz;
+            }
+        }
+        Some(if min_dtz == i32::MAX { -1 } else { min_dtz })
+    }
+    /// Ranks the moves at the root by DTZ and keeps those that reach the best result
+    /// fastest, taking the 50-move counter into account. Returns None if the position isn't
+    /// covered.
+    pub fn probe_root(&self, board: &mut ChessBoard) -> Option<(Wdl, Vec<(ChessMove, i32)>)> {
+        if board.piece_count() > self.max_pieces || has_castling_rights(board) {
+            return None;
+        }
+        let halfmove_clock = board.halfmove_clock as i32;
+        let mut ranked = vec![];
+        for chess_move in board.generate_legal_moves() {
+            board.make_move_unchecked(&chess_move);
+            let dtz = if board.halfmove_clock == 0 {
+                self.probe_wdl(board)
+                    .map(|wdl| wdl.opposite().dtz_before_zeroing())
+            } else if board.is_repetition() || board.halfmove_clock >= 100 {
+                Some(0)
+            } else {
+                //One ply further from the zeroing move than the position after it
+                self.probe_dtz(board).map(|dtz| -dtz - dtz.signum())
+            };
+            let mates = board.in_check() && board.generate_legal_moves().is_empty();
+            board.unmake_move(&chess_move);
+
+            let dtz = if mates { 1 } else { dtz? };
+            //Wins that the 50-move rule turns into draws rank below certain wins
+            let rank = if dtz > 0 {
+                if dtz + halfmove_clock <= 100 {
+                    1000 - dtz
+                } else {
+                    1
+                }
+            } else if dtz < 0 {
+                if -dtz + halfmove_clock <= 100 {
+                    -1000 - dtz
+                } else {
+                    -1
+                }
+            } else {
+                0
+            };
+            ranked.push((chess_move, dtz, rank));
+        }
+
+        let best_rank = ranked.iter().map(|&(_, _, rank)| rank).max()?;
+        let wdl = if best_rank > 1 {
+            Wdl::Win
+        } else if best_rank == 1 {
+            Wdl::CursedWin
+        } else if best_rank == 0 {
+            Wdl::Draw
+        } else if best_rank == -1 {
+            Wdl::BlessedLoss
+        } else {
+            Wdl::Loss
+        };
+        let best_moves = ranked
+            .into_iter()
+            .filter(|&(_, _, rank)| rank == best_rank)
+            .map(|(chess_move, dtz, _)| (chess_move, dtz))
+            .collect();
+        Some((wdl, best_moves))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Probe {
+    Fail,
+    //A DTZ table that only holds the other side to move
+    ChangeStm,
+}
+
+/// Tables never contain positions where castling is still possible.
+pub fn has_castling_rights(board: &ChessBoard) -> bool {
+    board
+        .castling_rights
+        .iter()
+        .any(|rights| !rights.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::endgame_table::{Dtm, EndgameTables};
+
+    //Small tables written by test-data/syzygy/generate.py, KQvK and KPvK store DTZ for white
+    //to move and KRvK for black to move
+    fn tablebase() -> Tablebase {
+        Tablebase::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test-data/syzygy"))
+    }
+
+    fn probe(tablebase: &Tablebase, fen: &str) -> (Option<Wdl>, Option<i32>) {
+        let mut board = ChessBoard::new_from_fen(fen).unwrap();
+        (
+            tablebase.probe_wdl(&mut board),
+            tablebase.probe_dtz(&mut board),
+        )
+    }
+
+    #[test]
+    fn finds_test_tables() {
+        let tablebase = tablebase();
+        assert_eq!(tablebase.table_count(), 10);
+        assert_eq!(tablebase.max_pieces(), 3);
+    }
+
+    #[test]
+    fn test_tables_use_the_whole_format() {
+        let directory = concat!(env!("CARGO_MANIFEST_DIR"), "/test-data/syzygy");
+        let load = |name: &str, kind: TableKind| {
+            let data = fs::read(format!("{directory}/{name}.{}", kind.extension())).unwrap();
+            Table::new(name, data, kind).unwrap()
+        };
+        for (name, kind) in [
+            ("KQvK", TableKind::Wdl),
+            ("KQvK", TableKind::Dtz),
+            ("KRvK", TableKind::Dtz),
+            ("KPvK", TableKind::Wdl),
+            ("KPvK", TableKind::Dtz),
+        ] {
+            for pairs in load(name, kind).pairs.iter().flatten() {
+                //Codes of several lengths, symbols standing for pairs and several blocks
+                assert!(pairs.base64.len() > 1, "{name}");
+                assert!(pairs.symlen.iter().any(|&len| len > 0), "{name}");
+                assert!(pairs.blocks_num > 1, "{name}");
+            }
+        }
+        assert_eq!(load("KPvK", TableKind::Wdl).pairs[1].len(), 4);
+        assert_eq!(
+            load("KRvK", TableKind::Dtz).pairs[0][0].flags & FLAG_WIDE,
+            FLAG_WIDE
+        );
+        assert_eq!(load("KPvK", TableKind::Dtz).pairs[0][3].flags, FLAG_MAPPED);
+        let single_value = load("KNvK", TableKind::Wdl);
+        assert_eq!(single_value.pairs[0][0].flags, FLAG_SINGLE_VALUE);
+    }
+
+    #[test]
+    fn probes_kqvk() {
+        let tablebase = tablebase();
+        //Qa8 mates
+        assert_eq!(
+            probe(&tablebase, "7k/8/6K1/8/8/8/Q7/8 w - - 0 1"),
+            (Some(Wdl::Win), Some(1))
+        );
+        //Kb8 is forced, then Qh8 mates
+        assert_eq!(
+            probe(&tablebase, "k7/8/1K6/8/8/8/8/7Q b - - 0 1"),
+            (Some(Wdl::Loss), Some(-2))
+        );
+        //The queen hangs
+        assert_eq!(
+            probe(&tablebase, "7k/6Q1/8/8/8/8/8/K7 b - - 0 1"),
+            (Some(Wdl::Draw), Some(0))
+        );
+        //Stalemate
+        assert_eq!(
+            probe(&tablebase, "7k/5Q2/6K1/8/8/8/8/8 b - - 0 1"),
+            (Some(Wdl::Draw), Some(0))
+        );
+    }
+
+    #[test]
+    fn probes_krvk() {
+        let tablebase = tablebase();
+        //Rh8 mates
+        assert_eq!(
+            probe(&tablebase, "1k6/8/1K6/8/8/8/8/7R w - - 0 1"),
+            (Some(Wdl::Win), Some(1))
+        );
+        assert_eq!(
+            probe(&tablebase, "k7/8/1K6/8/8/8/8/7R b - - 0 1"),
+            (Some(Wdl::Loss), Some(-2))
+        );
+        //The same with colours reversed
+        assert_eq!(
+            probe(&tablebase, "7r/8/8/8/8/1k6/8/K7 w - - 0 1"),
+            (Some(Wdl::Loss), Some(-2))
+        );
+        //Already mated
+        assert_eq!(
+            probe(&tablebase, "k6R/8/1K6/8/8/8/8/8 b - - 0 1"),
+            (Some(Wdl::Loss), Some(-1))
+        );
+        //One of the longest losses, mated in 16
+        assert_eq!(
+            probe(&tablebase, "8/8/8/8/8/2k5/1R6/K7 b - - 0 1"),
+            (Some(Wdl::Loss), Some(-32))
+        );
+        //Kxb2
+        assert_eq!(
+            probe(&tablebase, "8/8/8/8/8/2k5/1R6/7K b - - 0 1"),
+            (Some(Wdl::Draw), Some(0))
+        );
+    }
+
+    #[test]
+    fn rejects_corrupt_tables() {
+        let directory = concat!(env!("CARGO_MANIFEST_DIR"), "/test-data/syzygy");
+        let boards = [
+            "1k6/8/1K6/8/8/8/8/7R w - - 0 1",
+            "8/8/8/8/8/2k5/1R6/K7 b - - 0 1",
+        ]
+        .map(|fen| ChessBoard::new_from_fen(fen).unwrap());
+        for (file, kind) in [("KRvK.rtbw", TableKind::Wdl), ("KRvK.rtbz", TableKind::Dtz)] {
+            let data = fs::read(format!("{directory}/{file}")).unwrap();
+            assert!(Table::new("KRvK", data.clone(), kind).is_some());
+            for len in (0..data.len()).step_by(97) {
+                assert!(Table::new("KRvK", data[..len].to_vec(), kind).is_none());
+            }
+            //Damaged headers either fail to load or probe without panicking
+            for offset in 0..256.min(data.len()) {
+                let mut damaged = data.clone();
+                damaged[offset] ^= 0xA5;
+                if let Some(table) = Table::new("KRvK", damaged, kind) {
+                    for board in &boards {
+                        let _ = table.probe(board, false, Wdl::Loss);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn probes_kpvk() {
+        let tablebase = tablebase();
+        //e8=Q
+        assert_eq!(
+            probe(&tablebase, "8/4P3/8/8/8/8/k7/4K3 w - - 0 1"),
+            (Some(Wdl::Win), Some(1))
+        );
+        assert_eq!(
+            probe(&tablebase, "8/4P3/8/8/8/8/k7/4K3 b - - 0 1"),
+            (Some(Wdl::Loss), Some(-2))
+        );
+        //The pawn outruns the king
+        assert_eq!(
+            probe(&tablebase, "8/8/8/8/8/8/k6P/7K w - - 0 1"),
+            (Some(Wdl::Win), Some(1))
+        );
+        //Black has to give way, Kf7 and e6 follow
+        assert_eq!(
+            probe(&tablebase, "4k3/8/4K3/4P3/8/8/8/8 b - - 0 1"),
+            (Some(Wdl::Loss), Some(-4))
+        );
+        //The rook pawn can't be forced through
+        assert_eq!(
+            probe(&tablebase, "7k/8/6K1/7P/8/8/8/8 w - - 0 1"),
+            (Some(Wdl::Draw), Some(0))
+        );
+        //Kxd2
+        assert_eq!(
+            probe(&tablebase, "8/8/8/8/8/3k4/3P4/7K b - - 0 1"),
+            (Some(Wdl::Draw), Some(0))
+        );
+        //The same with colours reversed, e1=Q is check
+        assert_eq!(
+            probe(&tablebase, "k7/8/8/8/8/8/4p3/7K b - - 0 1"),
+            (Some(Wdl::Win), Some(1))
+        );
+        assert_eq!(
+            probe(&tablebase, "k7/8/8/8/8/8/4p3/7K w - - 0 1"),
+            (Some(Wdl::Loss), Some(-2))
+        );
+    }
+
+    //Probes every legal placement of the kings and one white piece in both tables, and
+    //returns how many positions were compared
+    fn compare_with_endgame_tables(material: &str, piece: char) -> usize {
+        let tablebase = tablebase();
+        let mut tables = EndgameTables::new();
+        assert!(tables.generate(material));
+        let is_pawn = piece == 'P';
+        let squares = if is_pawn { 8..56 } else { 0..CHESS_BOARD_SIZE };
+        let distance = |a: usize, b: usize| (a % 8).abs_diff(b % 8).max((a / 8).abs_diff(b / 8));
+        let mut positions = 0;
+        for piece_square in squares {
+            for white_king in 0..CHESS_BOARD_SIZE {
+                for black_king in 0..CHESS_BOARD_SIZE {
+                    let taken = [piece_square, white_king, black_king];
+                    if taken[0] == taken[1] || taken[0] == taken[2] || taken[1] == taken[2] {
+                        continue;
+                    }
+                    if distance(white_king, black_king) <= 1 {
+                        continue;
+                    }
+                    let mut layout = ['1'; CHESS_BOARD_SIZE];
+                    layout[white_king] = 'K';
+                    layout[black_king] = 'k';
+                    layout[piece_square] = piece;
+                    let placement = layout
+                        .chunks(8)
+                        .map(|rank| rank.iter().collect::<String>())
+                        .collect::<Vec<_>>()
+                        .join("/");
+                    for color in ["w", "b"] {
+                        let fen = format!("{placement} {color} - - 0 1");
+                        let mut board = ChessBoard::new_from_fen(&fen).unwrap();
+                        //Black can't be in check with white to move
+                        if color == "w" {
+                            board.color_to_move = ChessColor::BLACK;
+                            let illegal = board.in_check();
+                            board.color_to_move = ChessColor::WHITE;
+                            if illegal {
+                                continue;
+                            }
+                        }
+                        let dtm = tables.probe(&board).unwrap();
+                        let wdl = tablebase.probe_wdl(&mut board);
+                        let dtz = tablebase.probe_dtz(&mut board).expect(&fen);
+                        let expected = match dtm {
+                            Dtm::Win(_) => Wdl::Win,
+                            Dtm::Loss(_) => Wdl::Loss,
+                            Dtm::Draw => Wdl::Draw,
+                        };
+                        assert_eq!(wdl, Some(expected), "{fen}");
+                        if is_pawn {
+                            assert_eq!(dtz.signum(), expected.value().signum(), "{fen}");
+                        } else {
+                            //Only the loser can capture, so DTZ is the distance to mate
+                            let expected_dtz = match dtm {
+                                Dtm::Win(plies) => plies as i32,
+                                Dtm::Loss(plies) => -(plies.max(1) as i32),
+                                Dtm::Draw => 0,
+                            };
+                            assert_eq!(dtz, expected_dtz, "{fen}");
+                        }
+                        positions += 1;
+                    }
+                }
+            }
+        }
+        positions
+    }
+
+    #[test]
+    fn kqvk_agrees_with_endgame_tables() {
+        assert!(compare_with_endgame_tables("KQvK", 'Q') > 300_000);
+    }
+
+    #[test]
+    fn krvk_agrees_with_endgame_tables() {
+        assert!(compare_with_endgame_tables("KRvK", 'R') > 300_000);
+    }
+
+    #[test]
+    fn kpvk_agrees_with_endgame_tables() {
+        assert!(compare_with_endgame_tables("KPvK", 'P') > 300_000);
+    }
+}
@@ -0,0 +1,95 @@
+use std::time::{Duration, Instant};
+
+use crate::chess::chess_epd::EpdPosition;
+use crate::chess::chess_move::ChessMove;
+use crate::engine::search::{SearchLimits, Searcher};
+
+/// How one position of a test suite went.
+#[derive(Debug, Clone)]
+pub struct SuiteResult {
+    pub id: String,
+    pub best_move: Option<ChessMove>,
+    pub solved: bool,
+    /// When the search settled on a solution for good, if it did.
+    pub solved_after: Option<Duration>,
+    pub time: Duration,
+    pub nodes: u64,
+    pub depth: i32,
+}
+
+/// Totals over a test suite.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SuiteSummary {
+    pub solved: usize,
+    pub failed: usize,
+    /// Positions without a `bm` or `am` operation, which can't be scored.
+    pub skipped: usize,
+    pub time: Duration,
+    pub nodes: u64,
+}
+
+/// Whether `chess_move` is one of the best moves and none of the moves to avoid.
+pub fn is_solution(position: &EpdPosition, chess_move: ChessMove) -> bool {
+    let best_moves = position.best_moves();
+    (best_moves.is_empty() || best_moves.contains(&chess_move))
+        && !position.avoid_moves().contains(&chess_move)
+}
+
+/// Searches every position with `limits`, starting each from an empty transposition table.
+/// `on_result` is called after every position that has a `bm` or `am` operation.
+pub fn run_suite(
+    searcher: &mut Searcher,
+    positions: &[EpdPosition],
+    limits: &SearchLimits,
+    mut on_result: impl FnMut(&SuiteResult),
+) -> SuiteSummary {
+    let mut summary = SuiteSummary::default();
+    for (i, position) in positions.iter().enumerate() {
+        if position.best_moves().is_empty() && position.avoid_moves().is_empty() {
+            summary.skipped += 1;
+            continue;
+        }
+        searcher.clear();
+
+        //The time the first move of the reported lines last turned into a solution
+        let mut solved_after = None;
+        let timer = Instant::now();
+        let result = searcher.search(&position.board, limits, |info| {
+            if info.multipv > 1 {
+                return;
+            }
+            match info.pv.first() {
+                Some(&chess_move) if is_solution(position, chess_move) => {
+                    solved_after = solved_after.or(Some(info.time));
+                }
+                _ => solved_after = None,
+            }
+        });
+        let time = timer.elapsed();
+
+        let solved = result
+            .best_move
+            .is_some_and(|best_move| is_solution(position, best_move));
+        let suite_result = SuiteResult {
+            id: position
+                .id()
+                .map(str::to_string)
+                .unwrap_or_else(|| (i + 1).to_string()),
+            best_move: result.best_move,
+            solved,
+            solved_after: solved_after.filter(|_| solved),
+            time,
+            nodes: result.nodes,
+            depth: result.depth,
+        };
+        if solved {
+            summary.solved += 1;
+        } else {
+            summary.failed += 1;
+        }
+        summary.time += time;
+        summary.nodes += result.nodes;
+        on_result(&suite_result);
+    }
+    summary
+}
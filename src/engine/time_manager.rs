@@ -0,0 +1,121 @@
+use std::time::{Duration, Instant};
+
+use crate::chess::chess_move::ChessMove;
+use crate::engine::evaluation::MAX_PHASE;
+
+//Time kept back on every move for communication with the GUI
+const MOVE_OVERHEAD: Duration = Duration::from_millis(30);
+
+// Soft limit scale by how many iterations in a row the best move stayed the same
+const STABILITY_SCALE: [f64; 5] = [1.4, 1.1, 0.9, 0.75, 0.6];
+
+// Largest score drop between iterations, in centipawns, that still extends the soft limit further
+const MAX_SCORE_DROP: i32 = 100;
+
+#[derive(Debug, Clone, Copy)]
+pub struct TimeControl {
+    pub time_left: Duration,
+    pub increment: Duration,
+    pub moves_to_go: Option<u32>,
+}
+
+/// Decides when the iterative-deepening search should stop. The soft limit is checked between
+/// iterations and stretched or shrunk by how the search is going, the hard limit is checked
+/// inside the search and is never exceeded.
+#[derive(Debug, Clone)]
+pub struct TimeManager {
+    start: Instant,
+    soft_limit: Option<Duration>,
+    hard_limit: Option<Duration>,
+    best_move: Option<ChessMove>,
+    best_move_stability: usize,
+    previous_score: Option<i32>,
+    score_drop_scale: f64,
+}
+
+impl TimeManager {
+    pub fn new(
+        time_control: Option<&TimeControl>,
+        move_time: Option<Duration>,
+        phase: i32,
+    ) -> Self {
+        let mut instance = Self::infinite();
+        if let Some(move_time) = move_time {
+            instance.hard_limit = Some(move_time);
+        } else if let Some(time_control) = time_control {
+            let (soft_limit, hard_limit) = Self::allocate(time_control, phase);
+            instance.soft_limit = Some(soft_limit);
+            instance.hard_limit = Some(hard_limit);
+        }
+        instance
+    }
+    pub fn infinite() -> Self {
+        Self {
+            start: Instant::now(),
+            soft_limit: None,
+            hard_limit: None,
+            best_move: None,
+            best_move_stability: 0,
+            previous_score: None,
+            score_drop_scale: 1.0,
+        }
+    }
+    fn allocate(time_control: &TimeControl, phase: i32) -> (Duration, Duration) {
+        let available = time_control.time_left.saturating_sub(MOVE_OVERHEAD);
+
+        //Without moves to go, assume more of the game is left while there is more material
+        let moves_to_go = match time_control.moves_to_go {
+            Some(moves_to_go) => moves_to_go.max(1),
+            None => 25 + (15 * phase.clamp(0, MAX_PHASE) / MAX_PHASE) as u32,
+        };
+
+        let soft_limit = available / moves_to_go + time_control.increment * 3 / 4;
+        let hard_cap = if moves_to_go == 1 {
+            available * 9 / 10
+        } else {
+            available * 3 / 4
+        };
+        let hard_limit = (soft_limit * 4).min(hard_cap);
+        (soft_limit.min(hard_limit), hard_limit)
+    }
+    pub fn restart(&mut self) {
+        self.start = Instant::now();
+    }
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+    pub fn hard_limit_reached(&self) -> bool {
+        match self.hard_limit {
+            Some(hard_limit) => self.elapsed() >= hard_limit,
+            None => false,
+        }
+    }
+    pub fn soft_limit_reached(&self) -> bool {
+        let (soft_limit, hard_limit) = match (self.soft_limit, self.hard_limit) {
+            (Some(soft_limit), Some(hard_limit)) => (soft_limit, hard_limit),
+            _ => return false,
+        };
+        let stability_scale =
+            STABILITY_SCALE[self.best_move_stability.min(STABILITY_SCALE.len() - 1)];
+        let scaled_limit = soft_limit.mul_f64(stability_scale * self.score_drop_scale);
+        self.elapsed() >= scaled_limit.min(hard_limit)
+    }
+    /// Called after every completed iteration with its best move and score.
+    pub fn update(&mut self, best_move: Option<ChessMove>, score: i32) {
+        if best_move == self.best_move {
+            self.best_move_stability += 1;
+        } else {
+            self.best_move = best_move;
+            self.best_move_stability = 0;
+        }
+
+        self.score_drop_scale = match self.previous_score {
+            Some(previous_score) => {
+                let score_drop = (previous_score - score).clamp(0, MAX_SCORE_DROP);
+                1.0 + score_drop as f64 / MAX_SCORE_DROP as f64
+            }
+            None => 1.0,
+        };
+        self.previous_score = Some(score);
+    }
+}
@@ -0,0 +1,135 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::chess::chess_move::ChessMove;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TTEntry {
+    pub key: u64,
+    pub best_move: Option<ChessMove>,
+    pub score: i32,
+    pub depth: i32,
+    pub bound: Bound,
+}
+
+const MOVE_OFFSET: u64 = 0;
+const SCORE_OFFSET: u64 = 16;
+const DEPTH_OFFSET: u64 = 32;
+const BOUND_OFFSET: u64 = 40;
+
+impl TTEntry {
+    //A move of 0 would be a8a8, which can never be played, so it stands for no move
+    fn pack(&self) -> u64 {
+        let bound = match self.bound {
+            Bound::Exact => 1,
+            Bound::Lower => 2,
+            Bound::Upper => 3,
+        };
+        (self.best_move.map_or(0, |m| m.data) as u64) << MOVE_OFFSET
+            | (self.score as i16 as u16 as u64) << SCORE_OFFSET
+            | (self.depth.clamp(0, u8::MAX as i32) as u64) << DEPTH_OFFSET
+            | bound << BOUND_OFFSET
+    }
+    fn unpack(key: u64, data: u64) -> Option<Self> {
+        let bound = match (data >> BOUND_OFFSET) & 0xFF {
+            1 => Bound::Exact,
+            2 => Bound::Lower,
+            3 => Bound::Upper,
+            _ => return None,
+        };
+        let move_data = ((data >> MOVE_OFFSET) & 0xFFFF) as u16;
+        Some(Self {
+            key,
+            best_move: (move_data != 0).then_some(ChessMove { data: move_data }),
+            score: ((data >> SCORE_OFFSET) & 0xFFFF) as u16 as i16 as i32,
+            depth: ((data >> DEPTH_OFFSET) & 0xFF) as i32,
+            bound,
+        })
+    }
+}
+
+/// The key is stored XORed with the data, so an entry torn by two threads writing it at the
+/// same time no longer matches any key and is ignored instead of being read back corrupted.
+#[derive(Default)]
+struct AtomicEntry {
+    key_xor_data: AtomicU64,
+    data: AtomicU64,
+}
+
+/// A transposition table that can be shared between search threads without locking.
+pub struct TranspositionTable {
+    entries: Vec<AtomicEntry>,
+}
+
+impl TranspositionTable {
+    pub fn new(size_mb: usize) -> Self {
+        let num_entries = (size_mb.max(1) * 1024 * 1024) / std::mem::size_of::<AtomicEntry>();
+        let mut entries = Vec::with_capacity(num_entries);
+        entries.resize_with(num_entries, AtomicEntry::default);
+        Self { entries }
+    }
+    #[inline(always)]
+    fn entry(&self, key: u64) -> &AtomicEntry {
+        &self.entries[(key % self.entries.len() as u64) as usize]
+    }
+    pub fn probe(&self, key: u64) -> Option<TTEntry> {
+        let entry = self.entry(key);
+        let data = entry.data.load(Ordering::Relaxed);
+        let key_xor_data = entry.key_xor_data.load(Ordering::Relaxed);
+        if key_xor_data ^ data != key {
+            return None;
+        }
+        TTEntry::unpack(key, data)
+    }
+    pub fn store(
+        &self,
+        key: u64,
+        best_move: Option<ChessMove>,
+        score: i32,
+        depth: i32,
+        bound: Bound,
+    ) {
+        let mut new_entry = TTEntry {
+            key,
+            best_move,
+            score,
+            depth,
+            bound,
+        };
+        if let Some(old_entry) = self.probe(key) {
+            //Keep the deeper result for the same position, but keep its move if we have none
+            if old_entry.depth > depth {
+                return;
+            }
+            if best_move.is_none() {
+                new_entry.best_move = old_entry.best_move;
+            }
+        }
+
+        let data = new_entry.pack();
+        let entry = self.entry(key);
+        entry.key_xor_data.store(key ^ data, Ordering::Relaxed);
+        entry.data.store(data, Ordering::Relaxed);
+    }
+    /// Permille of the table in use, sampled from the first thousand entries.
+    pub fn hashfull(&self) -> usize {
+        let sample = self.entries.len().min(1000);
+        let used = self.entries[..sample]
+            .iter()
+            .filter(|entry| entry.data.load(Ordering::Relaxed) != 0)
+            .count();
+        used * 1000 / sample.max(1)
+    }
+    pub fn clear(&self) {
+        for entry in self.entries.iter() {
+            entry.key_xor_data.store(0, Ordering::Relaxed);
+            entry.data.store(0, Ordering::Relaxed);
+        }
+    }
+}
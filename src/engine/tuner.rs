@@ -0,0 +1,242 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::chess::chess_board::ChessBoard;
+use crate::engine::evaluation::{trace, EvalWeights, Weight, MAX_PHASE};
+
+//Texel tuning: the evaluation of a position, squashed by a sigmoid, is read as the expected
+//result of the game it comes from, and the weights are fitted so those expectations come as
+//close as possible to the real results.
+//
+//The evaluation is linear in the weights, so every position is traced once per weight up front
+//and reduced to how often each weight counts for white minus for black. The fit then works on
+//those counts alone and never has to generate a move.
+
+const ADAM_BETA1: f64 = 0.9;
+const ADAM_BETA2: f64 = 0.999;
+const ADAM_EPSILON: f64 = 1e-8;
+pub const DEFAULT_LEARNING_RATE: f64 = 1.0;
+
+/// A training position reduced to what the weights multiply.
+#[derive(Debug, Clone)]
+struct TuningPosition {
+    phase: i32,
+    //Everything the tuned weights don't cover, such as the piece-square tables
+    base: Weight,
+    //Weight index and its count for white minus its count for black
+    counts: Vec<(usize, i32)>,
+    result: f64,
+}
+
+pub struct Tuner {
+    positions: Vec<TuningPosition>,
+    names: Vec<String>,
+    //Midgame and endgame value of every weight, in the order of EvalWeights::named
+    params: Vec<[f64; 2]>,
+    zero_weights: EvalWeights,
+    unit_weights: Vec<EvalWeights>,
+    /// Scales centipawns in the sigmoid, fitted to the data before tuning.
+    pub scaling: f64,
+    pub learning_rate: f64,
+}
+
+impl Tuner {
+    pub fn new(weights: &EvalWeights) -> Self {
+        let named = weights.named();
+        let mut zero_weights = *weights;
+        for (_name, weight) in zero_weights.named_mut() {
+            *weight = Weight::default();
+        }
+        let unit_weights = (0..named.len())
+            .map(|i| {
+                let mut unit = zero_weights;
+                *unit.named_mut()[i].1 = Weight::new(1, 0);
+                unit
+            })
+            .collect();
+        Self {
+            positions: vec![],
+            params: named
+                .iter()
+                .map(|(_name, weight)| [weight.midgame as f64, weight.endgame as f64])
+                .collect(),
+            names: named.into_iter().map(|(name, _weight)| name).collect(),
+            zero_weights,
+            unit_weights,
+            scaling: 1.0,
+            learning_rate: DEFAULT_LEARNING_RATE,
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+    /// Adds a position with the result of its game for white, 1 for a win down to 0 for a loss.
+    pub fn add_position(&mut self, board: &ChessBoard, result: f64) {
+        let base = trace(board, &self.zero_weights);
+        let base_sum = base.sum();
+        let counts = self
+            .unit_weights
+            .iter()
+            .enumerate()
+            .map(|(i, unit)| (i, trace(board, unit).sum().midgame - base_sum.midgame))
+            .filter(|&(_i, count)| count != 0)
+            .collect();
+        self.positions.push(TuningPosition {
+            phase: base.phase,
+            base: base_sum,
+            counts,
+            result,
+        });
+    }
+    /// Reads a file of labelled positions, see `parse_labelled_position`. Returns how many
+    /// positions were added and how many lines couldn't be read.
+    pub fn load_positions(&mut self, path: &Path) -> io::Result<(usize, usize)> {
+        let text = fs::read_to_string(path)?;
+        let (mut added, mut skipped) = (0, 0);
+        for line in text.lines().filter(|line| !line.trim().is_empty()) {
+            match parse_labelled_position(line) {
+                Some((board, result)) => {
+                    self.add_position(&board, result);
+                    added += 1;
+                }
+                None => skipped += 1,
+            }
+        }
+        Ok((added, skipped))
+    }
+    //Evaluation from white's point of view with the current parameters
+    fn evaluate(&self, position: &TuningPosition) -> f64 {
+        let mut midgame = position.base.midgame as f64;
+        let mut endgame = position.base.endgame as f64;
+        for &(i, count) in &position.counts {
+            midgame += self.params[i][0] * count as f64;
+            endgame += self.params[i][1] * count as f64;
+        }
+        let phase = position.phase as f64;
+        (midgame * phase + endgame * (MAX_PHASE as f64 - phase)) / MAX_PHASE as f64
+    }
+    fn sigmoid(&self, eval: f64) -> f64 {
+        1.0 / (1.0 + 10f64.powf(-self.scaling * eval / 400.0))
+    }
+    /// Mean squared difference between results and the sigmoid of the evaluations.
+    pub fn error(&self) -> f64 {
+        if self.positions.is_empty() {
+            return 0.0;
+        }
+        let total: f64 = self
+            .positions
+            .iter()
+            .map(|position| (position.result - self.sigmoid(self.evaluate(position))).powi(2))
+            .sum();
+        total / self.positions.len() as f64
+    }
+    /// Picks the sigmoid scaling that fits the current weights best, so tuning only changes the
+    /// weights and not the scale of the whole evaluation.
+    pub fn fit_scaling(&mut self) -> f64 {
+        let (mut low, mut high) = (0.0, 10.0);
+        //Golden section search, the error is unimodal in the scaling
+        let ratio = (5f64.sqrt() - 1.0) / 2.0;
+        for _ in 0..60 {
+            let a = high - ratio * (high - low);
+            let b = low + ratio * (high - low);
+            self.scaling = a;
+            let error_a = self.error();
+            self.scaling = b;
+            let error_b = self.error();
+            if error_a < error_b {
+                high = b;
+            } else {
+                low = a;
+            }
+        }
+        self.scaling = (low + high) / 2.0;
+        self.scaling
+    }
+    fn gradient(&self) -> Vec<[f64; 2]> {
+        let mut gradient = vec![[0.0; 2]; self.params.len()];
+        let slope = self.scaling * 10f64.ln() / 400.0;
+        for position in &self.positions {
+            let sigmoid = self.sigmoid(self.evaluate(position));
+            let error = (sigmoid - position.result) * sigmoid * (1.0 - sigmoid) * slope;
+            let midgame_share = position.phase as f64 / MAX_PHASE as f64;
+            for &(i, count) in &position.counts {
+                gradient[i][0] += error * count as f64 * midgame_share;
+                gradient[i][1] += error * count as f64 * (1.0 - midgame_share);
+            }
+        }
+        //The constant factor 2 / positions only scales the step, which Adam normalises anyway
+        gradient
+    }
+    /// Runs Adam gradient descent for `iterations` full passes over the positions, calling
+    /// `on_progress` with the iteration and error every `report_interval` passes.
+    pub fn tune(
+        &mut self,
+        iterations: usize,
+        report_interval: usize,
+        mut on_progress: impl FnMut(usize, f64),
+    ) {
+        let mut momentum = vec![[0.0; 2]; self.params.len()];
+        let mut velocity = vec![[0.0; 2]; self.params.len()];
+        for iteration in 1..=iterations {
+            let gradient = self.gradient();
+            for i in 0..self.params.len() {
+                for phase in 0..2 {
+                    let g = gradient[i][phase];
+                    momentum[i][phase] = ADAM_BETA1 * momentum[i][phase] + (1.0 - ADAM_BETA1) * g;
+                    velocity[i][phase] =
+                        ADAM_BETA2 * velocity[i][phase] + (1.0 - ADAM_BETA2) * g * g;
+                    let m = momentum[i][phase] / (1.0 - ADAM_BETA1.powi(iteration as i32));
+                    let v = velocity[i][phase] / (1.0 - ADAM_BETA2.powi(iteration as i32));
+                    self.params[i][phase] -= self.learning_rate * m / (v.sqrt() + ADAM_EPSILON);
+                }
+            }
+            if iteration % report_interval.max(1) == 0 || iteration == iterations {
+                on_progress(iteration, self.error());
+            }
+        }
+    }
+    /// The tuned weights rounded to whole centipawns.
+    pub fn weights(&self) -> EvalWeights {
+        let mut weights = self.zero_weights;
+        for ((_name, weight), param) in weights.named_mut().into_iter().zip(&self.params) {
+            *weight = Weight::new(param[0].round() as i32, param[1].round() as i32);
+        }
+        weights
+    }
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+}
+
+/// Reads a line of training data: a FEN, with or without its move counters, followed by the
+/// result for white as `1-0`, `0-1`, `1/2-1/2` or a number such as `[0.5]`. Quotes, brackets,
+/// semicolons and EPD opcodes like `c9` around the result are ignored.
+pub fn parse_labelled_position(line: &str) -> Option<(ChessBoard, f64)> {
+    let parts: Vec<&str> = line.split_whitespace().collect();
+    if parts.len() < 5 {
+        return None;
+    }
+    let label = parts[parts.len() - 1].trim_matches(|c| "\"[];".contains(c));
+    let result = match label {
+        "1-0" => 1.0,
+        "0-1" => 0.0,
+        "1/2-1/2" => 0.5,
+        _ => label
+            .parse::<f64>()
+            .ok()
+            .filter(|r| (0.0..=1.0).contains(r))?,
+    };
+    //The move counters are optional, without them the position starts fresh
+    let counters = parts.len() >= 7 && parts[4..6].iter().all(|part| part.parse::<u32>().is_ok());
+    let fen = if counters {
+        parts[..6].join(" ")
+    } else {
+        format!("{} 0 1", parts[..4].join(" "))
+    };
+    let board = ChessBoard::new_from_fen(&fen).ok()?;
+    Some((board, result))
+}
@@ -0,0 +1,3 @@
+pub mod chess;
+pub mod engine;
+pub mod uci;
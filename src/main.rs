@@ -1,37 +1,587 @@
-use std::io::Write;
+use std::fs;
 use std::io::{stdin, stdout};
-use std::time::Instant;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-mod chess;
-use chess::chess_board::ChessBoard;
-use chess::chess_coordinate::{idx_to_notation, notation_to_idx};
+use chess_engine::chess::chess_board::{CastlingRights, ChessBoard};
+use chess_engine::chess::chess_color::ChessColor;
+use chess_engine::chess::chess_coordinate::{idx_to_notation, notation_to_idx};
+use chess_engine::chess::chess_epd::parse_epd;
+use chess_engine::chess::chess_move::ChessMove;
+use chess_engine::chess::chess_pgn::parse_pgn;
+use chess_engine::chess::START_POSITION_FEN;
+use chess_engine::engine::batch::{run_batch, BatchConfig};
+use chess_engine::engine::book_builder::BookBuilder;
+use chess_engine::engine::datagen::{generate, DatagenConfig, DatagenStats};
+use chess_engine::engine::elo::{Sprt, SprtVerdict};
+use chess_engine::engine::evaluation::{evaluate, trace};
+use chess_engine::engine::match_runner::{load_openings, run_match, EngineSpec, MatchConfig};
+use chess_engine::engine::mate_solver::MateSolver;
+use chess_engine::engine::opening_book::OpeningBook;
+use chess_engine::engine::pawn_table::PawnTable;
+use chess_engine::engine::score::Score;
+use chess_engine::engine::search::{SearchInfo, SearchLimits, SearchResult, Searcher};
+use chess_engine::engine::search_thread::SearchThread;
+use chess_engine::engine::see::see;
+use chess_engine::engine::skill::MAX_SKILL_LEVEL;
+use chess_engine::engine::syzygy::{has_castling_rights, Tablebase};
+use chess_engine::engine::test_suite::run_suite;
+use chess_engine::engine::transposition_table::Bound;
+use chess_engine::engine::tuner::Tuner;
+use chess_engine::uci;
 
-use chess::chess_piece::ChessPiece;
+const DEFAULT_MOVE_TIME: Duration = Duration::from_millis(1000);
+const DEFAULT_ANALYSIS_LINES: usize = 3;
+const DEFAULT_BOOK_PLY: usize = 20;
+const DEFAULT_TUNING_ITERATIONS: usize = 1000;
 
 fn main() {
     println!("\nJMCHESS 0.1 BETA\n");
 
-    let mut board =
-        ChessBoard::new_from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1")
-            .unwrap();
+    let mut board = ChessBoard::new_from_fen(START_POSITION_FEN).unwrap();
+    let mut search_thread = SearchThread::new(Searcher::new(16));
 
-    let timer = Instant::now();
-    let mut legal_moves = board.generate_legal_moves();
-    println!(
-        "Number of legal moves: {}\nGenerating legal Moves took {}μs\n",
-        legal_moves.len(),
-        timer.elapsed().as_micros()
-    );
-
-    display_board(&board);
+    let mut legal_moves = update_legal_moves(&board);
 
     loop {
         let mut line = String::new();
         print!("> ");
         stdout().flush().unwrap();
-        stdin().read_line(&mut line).unwrap();
+        if stdin().read_line(&mut line).unwrap() == 0 {
+            break;
+        }
         let parts: Vec<&str> = line.split_whitespace().collect();
-        if parts.len() == 1 {
+        if parts.is_empty() {
+            continue;
+        }
+        if parts[0] == "uci" {
+            uci::run();
+            break;
+        } else if parts[0] == "go" {
+            //go [limits] [searchmoves <moves>] [excludemoves <moves>]
+            let mut limits = SearchLimits::from_go_args(&parts[1..], &board);
+            if limits.depth.is_none()
+                && limits.nodes.is_none()
+                && limits.move_time.is_none()
+                && limits.time_control.is_none()
+                && !limits.infinite
+            {
+                limits.move_time = Some(DEFAULT_MOVE_TIME);
+            }
+            let (infinite, ponder) = (limits.infinite, limits.ponder);
+            search_thread.start(&board, limits, print_search_info, |_| ());
+            let result = if infinite || ponder {
+                //Only ends when stopped, so keep reading input instead of waiting for it
+                wait_for_stop(&mut search_thread, infinite, ponder)
+            } else {
+                search_thread.wait()
+            };
+            if let Some(best_move) = result.and_then(|result| result.best_move) {
+                println!("Engine plays {}", best_move);
+                board.make_move_unchecked(&best_move);
+                legal_moves = update_legal_moves(&board);
+            }
+        } else if parts[0] == "skill" {
+            //skill [level n | elo n | full], shows the current strength without arguments
+            let searcher = search_thread.searcher_mut();
+            let applied = match parts.get(1..3) {
+                Some(["level", level]) => {
+                    searcher.set_option("UCI_LimitStrength", "false")
+                        && searcher.set_option("Skill Level", level)
+                }
+                Some(["elo", elo]) => {
+                    searcher.set_option("UCI_LimitStrength", "true")
+                        && searcher.set_option("UCI_Elo", elo)
+                }
+                _ if parts.get(1) == Some(&"full") => {
+                    searcher.set_option("UCI_LimitStrength", "false")
+                        && searcher.set_option("Skill Level", &MAX_SKILL_LEVEL.to_string())
+                }
+                _ => parts.len() == 1,
+            };
+            if !applied {
+                println!("Usage: skill [level 0-{} | elo n | full]", MAX_SKILL_LEVEL);
+            }
+            match searcher.skill() {
+                Some(skill) => println!(
+                    "Skill level {} (about {} Elo): depth {}, {} nodes, moves up to {}cp worse",
+                    skill.level(),
+                    skill.elo(),
+                    skill.max_depth(),
+                    skill.max_nodes(),
+                    skill.margin()
+                ),
+                None => println!("Full strength"),
+            }
+        } else if parts[0] == "set" && parts.len() >= 3 {
+            let value = parts[parts.len() - 1];
+            let name = parts[1..parts.len() - 1].join(" ");
+            if !search_thread.searcher_mut().set_option(&name, value) {
+                println!("Unknown option or invalid value");
+            }
+        } else if parts[0] == "analyse" {
+            //analyse [multipv n] [searchmoves <moves>] [excludemoves <moves>], three lines
+            //until enter is pressed
+            let lines = parts
+                .iter()
+                .position(|&part| part == "multipv")
+                .and_then(|i| parts.get(i + 1))
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(DEFAULT_ANALYSIS_LINES);
+            let multi_pv = search_thread.searcher_mut().multi_pv();
+            search_thread.searcher_mut().set_multi_pv(lines);
+            let limits = SearchLimits {
+                infinite: true,
+                ..SearchLimits::from_go_args(&parts[1..], &board)
+            };
+            search_thread.start(&board, limits, print_search_info, |_| ());
+            println!("Analysing, press enter to stop");
+            stdin().read_line(&mut String::new()).unwrap();
+            if let Some(result) = search_thread.stop() {
+                for (i, line) in result.lines.iter().enumerate() {
+                    println!(
+                        "{:>2}. {:>8}  {}",
+                        i + 1,
+                        Score::new(line.score),
+                        board.to_san_line(&line.pv).join(" ")
+                    );
+                }
+            }
+            search_thread.searcher_mut().set_multi_pv(multi_pv);
+        } else if parts[0] == "fen" {
+            match ChessBoard::new_from_fen(&parts[1..].join(" ")) {
+                Ok(new_board) => {
+                    board = new_board;
+                    legal_moves = update_legal_moves(&board);
+                }
+                Err(_) => println!("Invalid FEN"),
+            }
+        } else if parts[0] == "mate" && parts.len() >= 2 {
+            //mate <n> [checks]
+            if let Ok(moves) = parts[1].parse::<u32>() {
+                let checks_only = parts.get(2) == Some(&"checks");
+                let timer = Instant::now();
+                let mut solver = MateSolver::new(&board, checks_only);
+                match solver.solve(moves) {
+                    Some(solution) => {
+                        println!("Mate in {}:", solution.length());
+                        print!("{}", solution);
+                    }
+                    None => println!("No mate in {}", moves),
+                }
+                println!(
+                    "{} nodes in {}ms",
+                    solver.nodes(),
+                    timer.elapsed().as_millis()
+                );
+            }
+        } else if parts[0] == "tb" {
+            match search_thread.searcher_mut().tablebase() {
+                Some(tablebase) => print_tablebase_verdict(&tablebase, &board),
+                None => println!("No tablebases loaded, set SyzygyPath first"),
+            }
+        } else if parts[0] == "tbgen" && parts.len() >= 2 {
+            //tbgen <material> [directory]
+            let searcher = search_thread.searcher_mut();
+            let mut tables = searcher
+                .endgame_tables()
+                .map(|tables| (*tables).clone())
+                .unwrap_or_default();
+            let timer = Instant::now();
+            if !tables.generate(parts[1]) {
+                println!("Can't generate {}", parts[1]);
+                continue;
+            }
+            let table = tables.get(parts[1]).unwrap();
+            println!(
+                "Generated {} ({} positions) in {}ms, longest mate {} moves",
+                table.material(),
+                table.size(),
+                timer.elapsed().as_millis(),
+                table.longest_mate().div_ceil(2)
+            );
+            if let Some(directory) = parts.get(2) {
+                match tables.save(Path::new(directory)) {
+                    Ok(()) => println!("Saved {} tables to {}", tables.len(), directory),
+                    Err(error) => println!("Saving failed: {}", error),
+                }
+            }
+            searcher.set_endgame_tables(Some(Arc::new(tables)));
+        } else if parts[0] == "dtm" {
+            let dtm = search_thread
+                .searcher_mut()
+                .endgame_tables()
+                .and_then(|tables| tables.probe(&board));
+            match dtm.map(|dtm| dtm.moves()) {
+                Some(Some(moves)) if moves > 0 => println!("Mate in {}", moves),
+                Some(Some(moves)) => println!("Mated in {}", -moves),
+                Some(None) => println!("Draw"),
+                None => println!("No endgame table covers this position"),
+            }
+        } else if parts[0] == "eval" {
+            //eval [fen], traces the current position without one
+            let traced = if parts.len() > 1 {
+                ChessBoard::new_from_fen(&parts[1..].join(" ")).ok()
+            } else {
+                Some(board.clone())
+            };
+            match traced {
+                Some(traced) => print!(
+                    "{}",
+                    trace(&traced, search_thread.searcher_mut().eval_weights())
+                ),
+                None => println!("Invalid FEN"),
+            }
+        } else if parts[0] == "nnue" {
+            //nnue [fen], the network's eval next to the classical one
+            let searcher = search_thread.searcher_mut();
+            let Some(network) = searcher.network() else {
+                println!("No network loaded, set EvalFile first");
+                continue;
+            };
+            let evaluated = if parts.len() > 1 {
+                ChessBoard::new_from_fen(&parts[1..].join(" ")).ok()
+            } else {
+                Some(board.clone())
+            };
+            match evaluated {
+                Some(evaluated) => println!(
+                    "NNUE ({:?}, {} hidden): {}  classical: {}  in use: {}",
+                    network.feature_set(),
+                    network.hidden_size(),
+                    network.evaluate(&evaluated, searcher.nnue_simd()),
+                    evaluate(&evaluated, searcher.eval_weights(), &mut PawnTable::new()),
+                    if searcher.uses_nnue() {
+                        "NNUE"
+                    } else {
+                        "classical"
+                    }
+                ),
+                None => println!("Invalid FEN"),
+            }
+        } else if parts[0] == "tune" && parts.len() >= 3 {
+            //tune <positions file> <weights file> [iterations]
+            let iterations = parts
+                .get(3)
+                .and_then(|p| p.parse().ok())
+                .unwrap_or(DEFAULT_TUNING_ITERATIONS);
+            let timer = Instant::now();
+            let mut tuner = Tuner::new(search_thread.searcher_mut().eval_weights());
+            match tuner.load_positions(Path::new(parts[1])) {
+                Ok((added, skipped)) => println!(
+                    "Read {} positions, skipped {} lines in {}ms",
+                    added,
+                    skipped,
+                    timer.elapsed().as_millis()
+                ),
+                Err(error) => {
+                    println!("Can't read {}: {}", parts[1], error);
+                    continue;
+                }
+            }
+            if tuner.is_empty() {
+                continue;
+            }
+            let scaling = tuner.fit_scaling();
+            println!("Scaling {:.4}, error {:.6}", scaling, tuner.error());
+            tuner.tune(iterations, 100, |iteration, error| {
+                println!("Iteration {:>6}  error {:.6}", iteration, error)
+            });
+            let weights = tuner.weights();
+            match weights.save(Path::new(parts[2])) {
+                Ok(()) => println!(
+                    "Wrote {} weights to {} in {}ms, load them with set EvalWeights {}",
+                    tuner.names().len(),
+                    parts[2],
+                    timer.elapsed().as_millis(),
+                    parts[2]
+                ),
+                Err(error) => println!("Can't write {}: {}", parts[2], error),
+            }
+        } else if parts[0] == "datagen" && parts.len() >= 3 {
+            //datagen <file> <games> [threads n] [nodes n] [depth n] [random n] [seed n]
+            let Ok(games) = parts[2].parse() else {
+                println!("Invalid number of games");
+                continue;
+            };
+            let mut config = DatagenConfig {
+                games,
+                ..Default::default()
+            };
+            let setting = |name| {
+                parts
+                    .iter()
+                    .position(|&part| part == name)
+                    .and_then(|i| parts.get(i + 1))
+                    .and_then(|value| value.parse::<u64>().ok())
+            };
+            config.threads = setting("threads").map_or(config.threads, |n| n as usize);
+            config.random_plies = setting("random").map_or(config.random_plies, |n| n as usize);
+            config.seed = setting("seed").unwrap_or(config.seed);
+            config.depth = setting("depth").map(|n| n as i32);
+            config.nodes = setting("nodes").or(config.nodes.filter(|_| config.depth.is_none()));
+
+            let mut out = match fs::File::create(parts[1]) {
+                Ok(file) => BufWriter::new(file),
+                Err(error) => {
+                    println!("Can't create {}: {}", parts[1], error);
+                    continue;
+                }
+            };
+            let timer = Instant::now();
+            let progress = |stats: &DatagenStats| {
+                if stats.games.is_multiple_of(10) || stats.games == games {
+                    println!(
+                        "{:>6} games  +{} ={} -{}  {} positions  {:.1} positions/s",
+                        stats.games,
+                        stats.white_wins,
+                        stats.draws,
+                        stats.black_wins,
+                        stats.positions,
+                        stats.positions as f64 / timer.elapsed().as_secs_f64()
+                    );
+                }
+            };
+            if let Err(error) = generate(&config, &mut out, progress) {
+                println!("Writing {} failed: {}", parts[1], error);
+            }
+        } else if parts[0] == "batch" && parts.len() >= 3 {
+            //batch <input> <output> [depth n] [nodes n] [threads n] [hash mb], depth 0 evaluates
+            let text = match fs::read(parts[1]) {
+                Ok(data) => String::from_utf8_lossy(&data).into_owned(),
+                Err(error) => {
+                    println!("Can't read {}: {}", parts[1], error);
+                    continue;
+                }
+            };
+            let setting = |name| {
+                parts
+                    .iter()
+                    .position(|&part| part == name)
+                    .and_then(|i| parts.get(i + 1))
+                    .and_then(|value| value.parse::<u64>().ok())
+            };
+            let mut config = BatchConfig::default();
+            config.depth = setting("depth").map_or(config.depth, |n| n as i32);
+            config.nodes = setting("nodes");
+            config.threads = setting("threads").map_or(config.threads, |n| n as usize);
+            config.hash_size_mb = setting("hash").map_or(config.hash_size_mb, |n| n as usize);
+
+            let mut out = match fs::File::create(parts[2]) {
+                Ok(file) => BufWriter::new(file),
+                Err(error) => {
+                    println!("Can't create {}: {}", parts[2], error);
+                    continue;
+                }
+            };
+            let lines: Vec<&str> = text.lines().collect();
+            let timer = Instant::now();
+            let mut invalid = 0;
+            let outcome = run_batch(
+                search_thread.searcher_mut(),
+                &lines,
+                &config,
+                &mut out,
+                |result| {
+                    if result.analysis.is_none() {
+                        invalid += 1;
+                        println!("Line {}: invalid position", result.line);
+                    }
+                },
+            );
+            match outcome {
+                Ok(written) => println!(
+                    "Analysed {} positions ({} invalid) in {}ms",
+                    written,
+                    invalid,
+                    timer.elapsed().as_millis()
+                ),
+                Err(error) => println!("Writing {} failed: {}", parts[2], error),
+            }
+        } else if parts[0] == "match" && parts.len() >= 3 {
+            //match <engine> <engine> [games n] [tc base+inc] [openings file] [sprt elo0 elo1]
+            //[margin ms] [hash mb], engines being internal[:Option=value,...] or executables
+            let specs = [EngineSpec::parse(parts[1]), EngineSpec::parse(parts[2])];
+            let mut config = MatchConfig::default();
+            let setting = |name| {
+                parts[3..]
+                    .iter()
+                    .position(|&part| part == name)
+                    .map(|i| &parts[3 + i + 1..])
+            };
+            let number = |name, default| {
+                setting(name)
+                    .and_then(|values| values.first()?.parse().ok())
+                    .unwrap_or(default)
+            };
+            config.games = number("games", config.games as f64) as usize;
+            config.hash_size_mb = number("hash", config.hash_size_mb as f64) as usize;
+            config.time_margin = Duration::from_millis(number("margin", 0.0) as u64);
+            if let Some(tc) = setting("tc").and_then(|values| values.first()) {
+                let (base, increment) = tc.split_once('+').unwrap_or((tc, "0"));
+                match (base.parse::<f64>(), increment.parse::<f64>()) {
+                    (Ok(base), Ok(increment)) => {
+                        config.base_time = Duration::from_secs_f64(base);
+                        config.increment = Duration::from_secs_f64(increment);
+                    }
+                    _ => {
+                        println!("Invalid time control {}, expected seconds+increment", tc);
+                        continue;
+                    }
+                }
+            }
+            if let Some(bounds) = setting("sprt") {
+                match bounds.get(..2).map(|b| (b[0].parse(), b[1].parse())) {
+                    Some((Ok(elo0), Ok(elo1))) => config.sprt = Some(Sprt::new(elo0, elo1)),
+                    _ => {
+                        println!("Invalid SPRT bounds, expected sprt <elo0> <elo1>");
+                        continue;
+                    }
+                }
+            }
+            if let Some(file) = setting("openings").and_then(|values| values.first()) {
+                match load_openings(Path::new(file)) {
+                    Ok(openings) => {
+                        println!("Loaded {} openings", openings.len());
+                        config.openings = openings;
+                    }
+                    Err(error) => {
+                        println!("Can't read {}: {}", file, error);
+                        continue;
+                    }
+                }
+            }
+
+            println!(
+                "{} vs {}, {} games at {}+{}s",
+                specs[0],
+                specs[1],
+                config.games,
+                config.base_time.as_secs_f64(),
+                config.increment.as_secs_f64()
+            );
+            let sprt = config.sprt;
+            let outcome = run_match([&specs[0], &specs[1]], &config, |game, score, verdict| {
+                let (white, black) = if game.white == 0 {
+                    (&specs[0], &specs[1])
+                } else {
+                    (&specs[1], &specs[0])
+                };
+                println!(
+                    "Game {:>4}: {} - {} {} ({}, {} moves)  {}",
+                    game.number + 1,
+                    white,
+                    black,
+                    game.result,
+                    game.end,
+                    game.moves.len().div_ceil(2),
+                    score
+                );
+                if let (Some(sprt), Some(verdict)) = (sprt, verdict) {
+                    let (lower, upper) = sprt.bounds();
+                    println!(
+                        "SPRT [{}, {}]: LLR {:.2} ({:.2}, {:.2}) {}",
+                        sprt.elo0,
+                        sprt.elo1,
+                        sprt.llr(score),
+                        lower,
+                        upper,
+                        match verdict {
+                            SprtVerdict::H0Accepted => "H0 accepted",
+                            SprtVerdict::H1Accepted => "H1 accepted",
+                            SprtVerdict::Continue => "",
+                        }
+                    );
+                }
+            });
+            match outcome {
+                Ok(score) => println!("Final: {}", score),
+                Err(error) => println!("Match aborted: {}", error),
+            }
+        } else if parts[0] == "epd" && parts.len() >= 2 {
+            //epd <file> [movetime ms] [depth n], one second per position by default
+            let positions = match fs::read(parts[1]) {
+                Ok(data) => parse_epd(&String::from_utf8_lossy(&data)),
+                Err(error) => {
+                    println!("Can't read {}: {}", parts[1], error);
+                    continue;
+                }
+            };
+            let mut limits = SearchLimits::from_go_args(&parts[2..], &board);
+            if limits.depth.is_none() && limits.move_time.is_none() {
+                limits.move_time = Some(DEFAULT_MOVE_TIME);
+            }
+            let summary = run_suite(
+                search_thread.searcher_mut(),
+                &positions,
+                &limits,
+                |result| {
+                    let best_move = result.best_move.map(|m| m.to_string()).unwrap_or_default();
+                    let solved_after = result
+                        .solved_after
+                        .map(|time| format!(", found after {}ms", time.as_millis()))
+                        .unwrap_or_default();
+                    println!(
+                        "{:<12} {} {:<6} depth {:>2} {:>10} nodes {:>6}ms{}",
+                        result.id,
+                        if result.solved { "solved" } else { "failed" },
+                        best_move,
+                        result.depth,
+                        result.nodes,
+                        result.time.as_millis(),
+                        solved_after
+                    );
+                },
+            );
+            println!(
+                "Solved {} of {}, {} skipped without bm or am, {}ms, {} nodes",
+                summary.solved,
+                summary.solved + summary.failed,
+                summary.skipped,
+                summary.time.as_millis(),
+                summary.nodes
+            );
+        } else if parts[0] == "book" {
+            match search_thread.searcher_mut().book() {
+                Some(book) => print_book_moves(&book, &board),
+                None => println!("No opening book loaded, set BookFile first"),
+            }
+        } else if parts[0] == "buildbook" && parts.len() >= 3 {
+            //buildbook <pgn file> <book file> [max ply] [min games] [min rating]
+            let argument =
+                |i: usize, default| parts.get(i).and_then(|p| p.parse().ok()).unwrap_or(default);
+            let mut builder = BookBuilder::new(
+                argument(3, DEFAULT_BOOK_PLY as u32) as usize,
+                argument(4, 1),
+                argument(5, 0),
+            );
+            let timer = Instant::now();
+            let pgn = match fs::read(parts[1]) {
+                Ok(data) => String::from_utf8_lossy(&data).into_owned(),
+                Err(error) => {
+                    println!("Can't read {}: {}", parts[1], error);
+                    continue;
+                }
+            };
+            let games = parse_pgn(&pgn);
+            for game in &games {
+                builder.add_game(game);
+            }
+            let book = builder.build();
+            match book.save(Path::new(parts[2])) {
+                Ok(()) => println!(
+                    "Used {} of {} games, {} moves seen, {} book entries written in {}ms",
+                    builder.games(),
+                    games.len(),
+                    builder.move_count(),
+                    book.len(),
+                    timer.elapsed().as_millis()
+                ),
+                Err(error) => println!("Can't write {}: {}", parts[2], error),
+            }
+        } else if parts.len() == 1 {
             if let Ok(start_pos) = notation_to_idx(parts[0]) {
                 for legal_move in &legal_moves {
                     let (move_start_pos, move_end_pos, _flags) = legal_move.get_idx();
@@ -45,41 +595,153 @@ fn main() {
                 if let Ok(end_pos) = notation_to_idx(parts[1]) {
                     for legal_move in &legal_moves {
                         let (move_start_pos, move_end_pos, _flags) = legal_move.get_idx();
-                        if start_pos as u16 == move_start_pos && end_pos as u16 == move_end_pos {
-                            board.make_move_unchecked(&legal_move);
+                        if start_pos == move_start_pos && end_pos == move_end_pos {
+                            board.make_move_unchecked(legal_move);
                             break;
                         }
                     }
 
-                    let timer = Instant::now();
-                    legal_moves = board.generate_legal_moves();
-                    println!(
-                        "Number of legal moves: {}\nGenerating legal Moves took {}μs\n",
-                        legal_moves.len(),
-                        timer.elapsed().as_micros()
-                    );
-                    display_board(&board);
+                    legal_moves = update_legal_moves(&board);
+                }
+            }
+        } else if parts.len() == 3 && parts[0] == "see" {
+            if let (Ok(start_pos), Ok(end_pos)) =
+                (notation_to_idx(parts[1]), notation_to_idx(parts[2]))
+            {
+                for legal_move in &legal_moves {
+                    let (move_start_pos, move_end_pos, _flags) = legal_move.get_idx();
+                    if start_pos == move_start_pos && end_pos == move_end_pos {
+                        println!("SEE: {}", see(&board, *legal_move));
+                        break;
+                    }
                 }
             }
         }
     }
 }
 
+//Reads input while an infinite or ponder search runs: ponderhit lets a ponder search finish
+//on its own clock, anything else stops the search
+fn wait_for_stop(
+    search_thread: &mut SearchThread,
+    infinite: bool,
+    mut ponder: bool,
+) -> Option<SearchResult> {
+    loop {
+        if ponder {
+            println!("Pondering, enter ponderhit to play on or press enter to stop");
+        } else {
+            println!("Searching, press enter to stop");
+        }
+        let mut line = String::new();
+        stdin().read_line(&mut line).unwrap();
+        if !ponder || line.trim() != "ponderhit" {
+            return search_thread.stop();
+        }
+        search_thread.ponderhit();
+        ponder = false;
+        if !infinite {
+            return search_thread.wait();
+        }
+    }
+}
+
+fn update_legal_moves(board: &ChessBoard) -> Vec<ChessMove> {
+    let timer = Instant::now();
+    let legal_moves = board.generate_legal_moves();
+    println!(
+        "Number of legal moves: {}\nGenerating legal Moves took {}μs\n",
+        legal_moves.len(),
+        timer.elapsed().as_micros()
+    );
+    display_board(board);
+    legal_moves
+}
+
+fn print_search_info(info: &SearchInfo) {
+    let pv: Vec<String> = info.pv.iter().map(|m| m.to_string()).collect();
+    let bound = match info.bound {
+        Bound::Exact => "  ",
+        Bound::Lower => "++",
+        Bound::Upper => "--",
+    };
+    println!(
+        "depth {:>2}  line {}  score {:>6}{}  nodes {:>10}  time {:>6}ms  pawn hash {:>5.1}%  pv {}",
+        info.depth,
+        info.multipv,
+        info.score,
+        bound,
+        info.nodes,
+        info.time.as_millis(),
+        info.pawn_hash_hit_rate(),
+        pv.join(" ")
+    );
+}
+
+fn print_tablebase_verdict(tablebase: &Tablebase, board: &ChessBoard) {
+    let mut board = board.clone();
+    if has_castling_rights(&board) || board.piece_count() > tablebase.max_pieces() {
+        println!(
+            "Position not covered, {} tables with up to {} pieces loaded",
+            tablebase.table_count(),
+            tablebase.max_pieces()
+        );
+        return;
+    }
+    match (
+        tablebase.probe_wdl(&mut board),
+        tablebase.probe_dtz(&mut board),
+    ) {
+        (Some(wdl), Some(dtz)) => println!("WDL: {:?}  DTZ: {}", wdl, dtz),
+        (Some(wdl), None) => println!("WDL: {:?}  DTZ: table missing", wdl),
+        _ => println!("Table missing"),
+    }
+    if let Some((_wdl, best_moves)) = tablebase.probe_root(&mut board) {
+        let best_moves: Vec<String> = best_moves
+            .iter()
+            .map(|(chess_move, dtz)| format!("{} ({})", chess_move, dtz))
+            .collect();
+        println!("Best moves: {}", best_moves.join(" "));
+    }
+}
+
+fn print_book_moves(book: &OpeningBook, board: &ChessBoard) {
+    let moves = book.moves(board);
+    if moves.is_empty() {
+        println!("Out of book");
+        return;
+    }
+    let total_weight: u32 = moves.iter().map(|(_, entry)| entry.weight as u32).sum();
+    for (chess_move, entry) in moves {
+        println!(
+            "{:<6} weight {:>5} ({:>5.1}%)  learn {}",
+            chess_move.to_string(),
+            entry.weight,
+            100.0 * entry.weight as f64 / total_weight.max(1) as f64,
+            entry.learn
+        );
+    }
+}
+
 fn display_board(board: &ChessBoard) {
     println!("---POSITION---");
     println!("{}", board);
     println!("---INFORMATION---");
-    println!(
-        "Color to move: {}",
-        if board.color_to_move == ChessPiece::WHITE {
-            "white"
-        } else {
-            "black"
-        }
-    );
+    println!("Color to move: {}", board.color_to_move);
 
-    println!("Can castle short: {:?}", board.can_catle_short);
-    println!("Can castle long: {:?}", board.can_catle_long);
+    for color in [ChessColor::WHITE, ChessColor::BLACK] {
+        let castling_rights = board.castling_rights[color.get_idx()];
+        println!(
+            "Can castle short ({}): {}",
+            color,
+            castling_rights.contains(CastlingRights::SHORT)
+        );
+        println!(
+            "Can castle long ({}): {}",
+            color,
+            castling_rights.contains(CastlingRights::LONG)
+        );
+    }
     println!("En passant target square: {:?}", board.en_passant_target);
     println!("Halfmove clock: {}", board.halfmove_clock);
     println!("Fullmove number: {}", board.fullmove_number);
@@ -0,0 +1,187 @@
+use std::io::{stdin, stdout, Write};
+
+use crate::chess::chess_board::ChessBoard;
+use crate::chess::START_POSITION_FEN;
+use crate::engine::nnue::simd_available;
+use crate::engine::search::{
+    SearchInfo, SearchLimits, SearchResult, Searcher, MAX_MULTI_PV, MAX_TB_PIECES, MAX_THREADS,
+};
+use crate::engine::search_thread::SearchThread;
+use crate::engine::skill::{DEFAULT_ELO, MAX_ELO, MAX_SKILL_LEVEL, MIN_ELO};
+use crate::engine::transposition_table::Bound;
+
+const DEFAULT_HASH_SIZE_MB: usize = 16;
+
+/// Speaks the UCI protocol on stdin/stdout until the GUI sends `quit`. Searches run on a
+/// background thread so `stop` and `ponderhit` are handled while the engine is thinking.
+pub fn run() {
+    let mut board = ChessBoard::new_from_fen(START_POSITION_FEN).unwrap();
+    let mut search_thread = SearchThread::new(Searcher::new(DEFAULT_HASH_SIZE_MB));
+
+    print_id();
+
+    loop {
+        let mut line = String::new();
+        if stdin().read_line(&mut line).unwrap() == 0 {
+            break;
+        }
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.is_empty() {
+            continue;
+        }
+
+        match parts[0] {
+            "uci" => print_id(),
+            "isready" => println!("readyok"),
+            "ucinewgame" => {
+                search_thread.stop();
+                search_thread.searcher_mut().clear();
+                board = ChessBoard::new_from_fen(START_POSITION_FEN).unwrap();
+            }
+            "position" => {
+                if let Some(new_board) = parse_position(&parts[1..]) {
+                    board = new_board;
+                }
+            }
+            "setoption" => {
+                if let Some((name, value)) = parse_option(&parts[1..]) {
+                    search_thread.searcher_mut().set_option(&name, &value);
+                }
+            }
+            "go" => {
+                let limits = SearchLimits::from_go_args(&parts[1..], &board);
+                search_thread.start(&board, limits, print_info, print_best_move);
+            }
+            "stop" => {
+                search_thread.stop();
+            }
+            "ponderhit" => search_thread.ponderhit(),
+            "quit" => {
+                search_thread.stop();
+                break;
+            }
+            _ => (),
+        }
+        stdout().flush().unwrap();
+    }
+}
+
+fn print_id() {
+    println!("id name JMCHESS 0.1");
+    println!(
+        "option name Hash type spin default {} min 1 max 4096",
+        DEFAULT_HASH_SIZE_MB
+    );
+    println!(
+        "option name Threads type spin default 1 min 1 max {}",
+        MAX_THREADS
+    );
+    println!("option name Ponder type check default false");
+    println!(
+        "option name MultiPV type spin default 1 min 1 max {}",
+        MAX_MULTI_PV
+    );
+    println!("option name SyzygyPath type string default <empty>");
+    println!("option name EndgameTablePath type string default <empty>");
+    println!("option name BookFile type string default <empty>");
+    println!("option name BestBookMove type check default false");
+    println!("option name EvalWeights type string default <empty>");
+    println!("option name EvalFile type string default <empty>");
+    println!("option name UseNNUE type check default false");
+    println!(
+        "option name NNUESimd type check default {}",
+        simd_available()
+    );
+    println!(
+        "option name Skill Level type spin default {} min 0 max {}",
+        MAX_SKILL_LEVEL, MAX_SKILL_LEVEL
+    );
+    println!("option name UCI_LimitStrength type check default false");
+    println!(
+        "option name UCI_Elo type spin default {} min {} max {}",
+        DEFAULT_ELO, MIN_ELO, MAX_ELO
+    );
+    println!(
+        "option name SyzygyProbeLimit type spin default {} min 0 max {}",
+        MAX_TB_PIECES, MAX_TB_PIECES
+    );
+    for name in [
+        "NullMovePruning",
+        "LateMoveReductions",
+        "ReverseFutilityPruning",
+        "FutilityPruning",
+        "CheckExtensions",
+    ] {
+        println!("option name {} type check default true", name);
+    }
+    println!("uciok");
+}
+
+fn print_best_move(result: &SearchResult) {
+    match (result.best_move, result.ponder_move) {
+        (Some(best_move), Some(ponder_move)) => {
+            println!("bestmove {} ponder {}", best_move, ponder_move)
+        }
+        (Some(best_move), None) => println!("bestmove {}", best_move),
+        (None, _) => println!("bestmove 0000"),
+    }
+}
+
+pub fn print_info(info: &SearchInfo) {
+    let pv: Vec<String> = info.pv.iter().map(|m| m.to_string()).collect();
+    let bound = match info.bound {
+        Bound::Exact => "",
+        Bound::Lower => " lowerbound",
+        Bound::Upper => " upperbound",
+    };
+    println!(
+        "info depth {} multipv {} score {}{} nodes {} nps {} time {} hashfull {} tbhits {}{}",
+        info.depth,
+        info.multipv,
+        info.score.to_uci(),
+        bound,
+        info.nodes,
+        info.nps(),
+        info.time.as_millis(),
+        info.hashfull,
+        info.tb_hits,
+        //A failed low root has no move that raised alpha, so there is no pv to show
+        if pv.is_empty() {
+            String::new()
+        } else {
+            format!(" pv {}", pv.join(" "))
+        }
+    );
+}
+
+/// Builds the board for `position [startpos | fen <fen>] [moves <move>...]`.
+pub fn parse_position(args: &[&str]) -> Option<ChessBoard> {
+    let moves_idx = args.iter().position(|&arg| arg == "moves");
+    let setup = &args[..moves_idx.unwrap_or(args.len())];
+
+    let mut board = match setup.first() {
+        Some(&"startpos") => ChessBoard::new_from_fen(START_POSITION_FEN).ok()?,
+        Some(&"fen") => ChessBoard::new_from_fen(&setup[1..].join(" ")).ok()?,
+        _ => return None,
+    };
+
+    if let Some(moves_idx) = moves_idx {
+        for notation in &args[moves_idx + 1..] {
+            let chess_move = board.find_move(notation)?;
+            board.make_move_unchecked(&chess_move);
+        }
+    }
+    Some(board)
+}
+
+//Splits `name <name> value <value>`, names may contain spaces
+fn parse_option(args: &[&str]) -> Option<(String, String)> {
+    let name_idx = args.iter().position(|&arg| arg == "name")?;
+    let value_idx = args.iter().position(|&arg| arg == "value");
+    let name = args[name_idx + 1..value_idx.unwrap_or(args.len())].join(" ");
+    let value = match value_idx {
+        Some(value_idx) => args[value_idx + 1..].join(" "),
+        None => String::new(),
+    };
+    Some((name, value))
+}
@@ -0,0 +1,655 @@
+#!/usr/bin/env python3
+"""Writes KQvK, KRvK, KBvK, KNvK and KPvK Syzygy tables for the tablebase tests.
+
+The official tables can't be downloaded when the tests run, so these are solved here by
+retrograde analysis and written the way the Syzygy generator lays them out:
+
+- the Stockfish index encodings for three unique pieces and for a leading pawn, split by
+  the file of the pawn;
+- values are replaced by symbol pairs (Re-Pair) and stored as canonical Huffman codes in
+  small blocks, so lookups walk the sparse index, the block lengths and the pair tree;
+- DTZ values go through the value maps, u8 for KPvK and u16 for KRvK, while KQvK stores
+  them directly. KQvK and KPvK count wins in moves, KRvK counts losses in plies;
+- KBvK and KNvK are drawn throughout, so they hold a single value.
+
+Any Syzygy reader can probe them. Run from this directory:
+
+    python3 generate.py
+"""
+
+import heapq
+import struct
+from collections import Counter
+
+WHITE_PAWN, WHITE_KNIGHT, WHITE_BISHOP, WHITE_ROOK, WHITE_QUEEN = 1, 2, 3, 4, 5
+WHITE_KING, BLACK_KING = 6, 14
+
+KING_STEPS = [(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)]
+ROOK_STEPS = [(-1, 0), (1, 0), (0, -1), (0, 1)]
+QUEEN_STEPS = KING_STEPS
+
+# Small blocks, so probes cross blocks both ways from the sparse index entry
+BLOCK_SIZE_LOG2 = 6
+SPAN_LOG2 = 8
+# Values a block may hold, the sparse index stores offsets into blocks as u16
+MAX_BLOCK_VALUES = 1 << 14
+# Pairs stop once they get rare or the tree gets big, it holds at most 4095 symbols
+MIN_PAIR_COUNT = 8
+MAX_SYMBOLS = 1024
+# Symbols expand to at most 256 values
+MAX_SYMBOL_VALUES = 256
+
+FLAG_STM = 1
+FLAG_MAPPED = 2
+FLAG_WIN_PLIES = 4
+FLAG_LOSS_PLIES = 8
+FLAG_WIDE = 16
+FLAG_SINGLE_VALUE = 128
+
+WDL_MAGIC = bytes([0x71, 0xE8, 0x23, 0x5D])
+DTZ_MAGIC = bytes([0xD7, 0x66, 0x0C, 0xA5])
+
+
+# Squares run from a1 = 0 to h8 = 63
+def file_of(sq):
+    return sq & 7
+
+
+def rank_of(sq):
+    return sq >> 3
+
+
+def step(sq, df, dr):
+    f, r = file_of(sq) + df, rank_of(sq) + dr
+    return r * 8 + f if 0 <= f < 8 and 0 <= r < 8 else None
+
+
+def king_targets(sq):
+    return [t for t in (step(sq, df, dr) for df, dr in KING_STEPS) if t is not None]
+
+
+def adjacent(a, b):
+    return max(abs(file_of(a) - file_of(b)), abs(rank_of(a) - rank_of(b))) <= 1
+
+
+def slides(sq, steps, blockers):
+    """Squares a slider on `sq` reaches, stopping before any blocker."""
+    targets = []
+    for df, dr in steps:
+        t = step(sq, df, dr)
+        while t is not None and t not in blockers:
+            targets.append(t)
+            t = step(t, df, dr)
+    return targets
+
+
+def attacks(sq, steps, target, blockers):
+    for df, dr in steps:
+        t = step(sq, df, dr)
+        while t is not None:
+            if t == target:
+                return True
+            if t in blockers:
+                break
+            t = step(t, df, dr)
+    return False
+
+
+def pawn_attacks(pawn, target):
+    return rank_of(target) == rank_of(pawn) + 1 and abs(file_of(target) - file_of(pawn)) == 1
+
+
+def key(wk, wx, bk, stm):
+    return ((wk * 64 + wx) * 64 + bk) * 2 + stm
+
+
+def solve(steps):
+    """Distance to mate in plies of every legal position with a white queen or rook,
+    positive when the side to move wins and negative when it gets mated, None for draws
+    and illegal positions."""
+
+    def legal(wk, wx, bk, stm):
+        if len({wk, wx, bk}) < 3 or adjacent(wk, bk):
+            return False
+        return stm == 1 or not attacks(wx, steps, bk, {wk})
+
+    positions = [
+        (wk, wx, bk, stm)
+        for wk in range(64)
+        for wx in range(64)
+        for bk in range(64)
+        for stm in (0, 1)
+        if legal(wk, wx, bk, stm)
+    ]
+    dtm = {}
+    moves_left = {}
+    queue = []
+    for wk, wx, bk, stm in positions:
+        if stm == 0:
+            continue
+        count = 0
+        for t in king_targets(bk):
+            if t == wk or adjacent(t, wk):
+                continue
+            if t == wx:
+                # Capturing leaves bare kings
+                count += 1
+            elif not attacks(wx, steps, t, {wk}):
+                count += 1
+        moves_left[(wk, wx, bk)] = count
+        if count == 0 and attacks(wx, steps, bk, {wk}):
+            dtm[key(wk, wx, bk, 1)] = 0
+            queue.append((wk, wx, bk, 1))
+
+    # Breadth first, so wins get their shortest distance and losses their longest
+    head = 0
+    while head < len(queue):
+        wk, wx, bk, stm = queue[head]
+        head += 1
+        plies = abs(dtm[key(wk, wx, bk, stm)]) + 1
+        if stm == 1:
+            # White moves that led here
+            origins = [(k, wx) for k in king_targets(wk) if k not in (wx, bk)]
+            origins += [(wk, x) for x in slides(wx, steps, {wk, bk})]
+            for k, x in origins:
+                if legal(k, x, bk, 0) and key(k, x, bk, 0) not in dtm:
+                    dtm[key(k, x, bk, 0)] = plies
+                    queue.append((k, x, bk, 0))
+        else:
+            for b in king_targets(bk):
+                if b in (wk, wx) or not legal(wk, wx, b, 1) or key(wk, wx, b, 1) in dtm:
+                    continue
+                moves_left[(wk, wx, b)] -= 1
+                if moves_left[(wk, wx, b)] == 0:
+                    dtm[key(wk, wx, b, 1)] = -plies
+                    queue.append((wk, wx, b, 1))
+    return positions, dtm
+
+
+def solve_pawn(queen_dtm, rook_dtm):
+    """WDL and DTZ in plies of every legal KPvK position, white having the pawn. DTZ counts
+    to the next pawn move, with 1 for a winning pawn move or mate and -1 when mated, and
+    is 0 for draws. Promotions are looked up in the KQvK and KRvK results, with black to
+    move; minor pieces can't win."""
+
+    def legal(wk, p, bk, stm):
+        if len({wk, p, bk}) < 3 or adjacent(wk, bk):
+            return False
+        return stm == 1 or not pawn_attacks(p, bk)
+
+    def promotion_wins(wk, q, bk):
+        # Black to move is decided only when it gets mated
+        return any(key(wk, q, bk, 1) in dtm for dtm in (queen_dtm, rook_dtm))
+
+    wdl = {}
+    dtz = {}
+    # Pawn moves only go forward, so the slices of later pawn squares are done first
+    for p in sorted(range(8, 56), key=rank_of, reverse=True):
+        positions = [
+            (wk, bk, stm)
+            for wk in range(64)
+            for bk in range(64)
+            for stm in (0, 1)
+            if legal(wk, p, bk, stm)
+        ]
+        moves_left = {}
+        # Black positions get their distance as mated = 0, mated in n = 2n, so the BFS
+        # levels stay in order; turned into DTZ when stored
+        level = {}
+        mated = []
+        pushes = []
+        for wk, bk, stm in positions:
+            if stm == 1:
+                targets = [t for t in king_targets(bk) if t != wk and not adjacent(t, wk)]
+                targets = [t for t in targets if t == p or not pawn_attacks(p, t)]
+                moves_left[(wk, bk)] = len(targets)
+                if not targets and pawn_attacks(p, bk):
+                    level[(wk, bk, 1)] = 0
+                    mated.append((wk, bk, 1))
+                continue
+            wins = False
+            forward = p + 8
+            if forward not in (wk, bk):
+                if rank_of(forward) == 7:
+                    wins = promotion_wins(wk, forward, bk)
+                else:
+                    wins = wdl.get((wk, forward, bk, 1)) == -2
+                    double = forward + 8
+                    if rank_of(p) == 1 and double not in (wk, bk):
+                        wins = wins or wdl.get((wk, double, bk, 1)) == -2
+            if wins:
+                level[(wk, bk, 0)] = 1
+                pushes.append((wk, bk, 0))
+
+        queue = mated + pushes
+        head = 0
+        while head < len(queue):
+            wk, bk, stm = queue[head]
+            head += 1
+            distance = level[(wk, bk, stm)] + 1
+            if stm == 1:
+                for k in king_targets(wk):
+                    if k in (p, bk) or not legal(k, p, bk, 0) or (k, bk, 0) in level:
+                        continue
+                    level[(k, bk, 0)] = distance
+                    queue.append((k, bk, 0))
+            else:
+                for b in king_targets(bk):
+                    if b == p or not legal(wk, p, b, 1) or (wk, b, 1) in level:
+                        continue
+                    moves_left[(wk, b)] -= 1
+                    if moves_left[(wk, b)] == 0:
+                        level[(wk, b, 1)] = distance
+                        queue.append((wk, b, 1))
+
+        for wk, bk, stm in positions:
+            distance = level.get((wk, bk, stm))
+            if distance is None:
+                wdl[(wk, p, bk, stm)] = 0
+                dtz[(wk, p, bk, stm)] = 0
+            elif stm == 0:
+                wdl[(wk, p, bk, stm)] = 2
+                dtz[(wk, p, bk, stm)] = distance
+            else:
+                wdl[(wk, p, bk, stm)] = -2
+                dtz[(wk, p, bk, stm)] = -max(distance, 1)
+    return wdl, dtz
+
+
+def map_a1d1d4():
+    mapping = {}
+    code = 0
+    diagonal = []
+    for sq in range(28):
+        if rank_of(sq) < file_of(sq) and file_of(sq) <= 3:
+            mapping[sq] = code
+            code += 1
+        elif rank_of(sq) == file_of(sq) and file_of(sq) <= 3:
+            diagonal.append(sq)
+    for sq in diagonal:
+        mapping[sq] = code
+        code += 1
+    return mapping
+
+
+MAP_A1D1D4 = map_a1d1d4()
+MAP_B1H1H7 = {sq: code for code, sq in enumerate(s for s in range(64) if rank_of(s) < file_of(s))}
+
+
+def off_a1h8(sq):
+    return rank_of(sq) - file_of(sq)
+
+
+PIECE_TABLE_SIZE = 31332
+# Leading pawn on one of six ranks, then the two kings on the squares left
+PAWN_TABLE_SIZE = 6 * 63 * 62
+
+
+def encode(squares):
+    """Stockfish's index of three unique pieces, squares in table piece order."""
+    squares = list(squares)
+    if file_of(squares[0]) > 3:
+        squares = [sq ^ 7 for sq in squares]
+    if rank_of(squares[0]) > 3:
+        squares = [sq ^ 56 for sq in squares]
+    for i in range(3):
+        if off_a1h8(squares[i]) == 0:
+            continue
+        if off_a1h8(squares[i]) > 0:
+            squares[i:] = [((sq >> 3) | (sq << 3)) & 63 for sq in squares[i:]]
+        break
+    s0, s1, s2 = squares
+    adjust1 = int(s1 > s0)
+    adjust2 = int(s2 > s0) + int(s2 > s1)
+    if off_a1h8(s0):
+        return (MAP_A1D1D4[s0] * 63 + (s1 - adjust1)) * 62 + s2 - adjust2
+    if off_a1h8(s1):
+        return (6 * 63 + rank_of(s0) * 28 + MAP_B1H1H7[s1]) * 62 + s2 - adjust2
+    if off_a1h8(s2):
+        return (
+            6 * 63 * 62
+            + 4 * 28 * 62
+            + rank_of(s0) * 7 * 28
+            + (rank_of(s1) - adjust1) * 28
+            + MAP_B1H1H7[s2]
+        )
+    return (
+        6 * 63 * 62
+        + 4 * 28 * 62
+        + 4 * 7 * 28
+        + rank_of(s0) * 7 * 6
+        + (rank_of(s1) - adjust1) * 6
+        + (rank_of(s2) - adjust2)
+    )
+
+
+def encode_pawn(p, wk, bk):
+    """File of the pawn and Stockfish's index of a leading pawn followed by two kings, each
+    group multiplying the ones before: pawn rank, then the white and the black king counted
+    over the squares not taken yet."""
+    if file_of(p) > 3:
+        p, wk, bk = p ^ 7, wk ^ 7, bk ^ 7
+    wk_code = wk - int(wk > p)
+    bk_code = bk - int(bk > p) - int(bk > wk)
+    return file_of(p), rank_of(p) - 1 + 6 * wk_code + 6 * 63 * bk_code
+
+
+def fill(values):
+    """Indices no position maps to repeat the value before them, which pairs up well."""
+    last = 0
+    filled = []
+    for value in values:
+        if value is None:
+            value = last
+        filled.append(value)
+        last = value
+    return filled
+
+
+def store(table, idx, value):
+    assert table[idx] in (None, value), "symmetric positions disagree"
+    table[idx] = value
+
+
+def repair(values):
+    """Re-Pair: keeps replacing the most common pair of neighbouring symbols by a new
+    symbol. Returns the symbol sequence, the tree of (left, right) with leaves standing for
+    (value, None) and the number of values each symbol expands to."""
+    distinct = sorted(set(values))
+    tree = [(value, None) for value in distinct]
+    width = [1] * len(tree)
+    leaf = {value: sym for sym, value in enumerate(distinct)}
+    sequence = [leaf[value] for value in values]
+    while len(tree) < MAX_SYMBOLS:
+        pairs = Counter(zip(sequence, sequence[1:]))
+        best = None
+        for pair, count in pairs.most_common():
+            if count < MIN_PAIR_COUNT:
+                break
+            if width[pair[0]] + width[pair[1]] <= MAX_SYMBOL_VALUES:
+                best = pair
+                break
+        if best is None:
+            break
+        sym = len(tree)
+        tree.append(best)
+        width.append(width[best[0]] + width[best[1]])
+        paired = []
+        i = 0
+        while i < len(sequence):
+            if i + 1 < len(sequence) and (sequence[i], sequence[i + 1]) == best:
+                paired.append(sym)
+                i += 2
+            else:
+                paired.append(sequence[i])
+                i += 1
+        sequence = paired
+    return sequence, tree, width
+
+
+def huffman_lengths(frequencies):
+    """Code length of every symbol of a Huffman code over `frequencies`."""
+    heap = [(frequency, [sym]) for sym, frequency in frequencies.items()]
+    heapq.heapify(heap)
+    lengths = dict.fromkeys(frequencies, 0)
+    while len(heap) > 1:
+        frequency1, symbols1 = heapq.heappop(heap)
+        frequency2, symbols2 = heapq.heappop(heap)
+        for sym in symbols1 + symbols2:
+            lengths[sym] += 1
+        heapq.heappush(heap, (frequency1 + frequency2, symbols1 + symbols2))
+    return lengths
+
+
+def compress(values, flags):
+    """Header, sparse index, block lengths and blocks of one sub-table."""
+    if len(set(values)) == 1:
+        return bytes([flags | FLAG_SINGLE_VALUE, values[0]]), b"", b"", []
+
+    sequence, tree, width = repair(values)
+    frequencies = Counter(sequence)
+    if len(frequencies) == 1:
+        # A code needs two symbols, add any other one
+        frequencies[next(sym for sym in range(len(tree)) if sym not in frequencies)] = 1
+    lengths = huffman_lengths(frequencies)
+    max_len, min_len = max(lengths.values()), min(lengths.values())
+    assert max_len <= 32
+
+    # Canonical code: symbols are renumbered so the longest codes come first, symbols
+    # without a code (only reached through the tree) go last
+    coded = sorted(lengths, key=lambda sym: (-lengths[sym], sym))
+    uncoded = [sym for sym in range(len(tree)) if sym not in lengths]
+    renumber = {old: new for new, old in enumerate(coded + uncoded)}
+    counts = Counter(lengths.values())
+    lowest_sym = [0] * (max_len - min_len + 1)
+    base = [0] * (max_len - min_len + 1)
+    for i in range(max_len - min_len - 1, -1, -1):
+        length = min_len + i + 1
+        lowest_sym[i] = lowest_sym[i + 1] + counts[length]
+        assert (base[i + 1] + counts[length]) % 2 == 0, "incomplete Huffman code"
+        base[i] = (base[i + 1] + counts[length]) // 2
+
+    def code(sym):
+        i = lengths[sym] - min_len
+        return base[i] + renumber[sym] - lowest_sym[i], lengths[sym]
+
+    symbols = [None] * len(tree)
+    for old, (left, right) in enumerate(tree):
+        if right is None:
+            symbols[renumber[old]] = (left, 0xFFF)
+        else:
+            symbols[renumber[old]] = (renumber[left], renumber[right])
+
+    header = bytes([flags, BLOCK_SIZE_LOG2, SPAN_LOG2, 0])
+    blocks_at = len(header)
+    header += bytes(4)
+    header += bytes([max_len, min_len])
+    for sym in lowest_sym:
+        header += struct.pack("<H", sym)
+    header += struct.pack("<H", len(symbols))
+    for left, right in symbols:
+        header += bytes([left & 0xFF, (left >> 8) | ((right & 0xF) << 4), right >> 4])
+    header += bytes(len(symbols) & 1)
+
+    # Blocks take whole symbols while their codes fit
+    block_size = 1 << BLOCK_SIZE_LOG2
+    blocks = []
+    block_starts = []
+    start = 0
+    bits, bit_count, value_count = 0, 0, 0
+    for sym in sequence:
+        value, length = code(sym)
+        if bit_count + length > block_size * 8 or value_count + width[sym] > MAX_BLOCK_VALUES:
+            blocks.append((bits << (block_size * 8 - bit_count), value_count))
+            block_starts.append(start)
+            start += value_count
+            bits, bit_count, value_count = 0, 0, 0
+        bits = (bits << length) | value
+        bit_count += length
+        value_count += width[sym]
+    blocks.append((bits << (block_size * 8 - bit_count), value_count))
+    block_starts.append(start)
+    header = header[:blocks_at] + struct.pack("<I", len(blocks)) + header[blocks_at + 4 :]
+
+    span = 1 << SPAN_LOG2
+    sparse_index = b""
+    for k in range(-(-len(values) // span)):
+        position = k * span + span // 2
+        block = max(b for b, start in enumerate(block_starts) if start <= position)
+        sparse_index += struct.pack("<IH", block, position - block_starts[block])
+
+    block_lengths = b"".join(struct.pack("<H", count - 1) for _, count in blocks)
+    data = [bits.to_bytes(block_size, "big") for bits, _ in blocks]
+    return header, sparse_index, block_lengths, data
+
+
+def value_maps(results, wide):
+    """DTZ value maps for wins, losses, cursed wins and blessed losses, from the stored
+    values each result needs. Returns the bytes and the map index of every value."""
+    data = b""
+    index = []
+    for values in results:
+        values = sorted(values)
+        index.append({value: i for i, value in enumerate(values)})
+        if wide:
+            data += struct.pack("<H", len(values))
+            data += b"".join(struct.pack("<H", value) for value in values)
+        else:
+            data += bytes([len(values)] + values)
+    return data, index
+
+
+def write_table(path, magic, pieces, files, maps=None):
+    """`files` holds for every file of the leading pawn, or just once without pawns, the
+    values and flags of each side to move. `maps` holds the value maps of each file."""
+    has_pawns = len(files) > 1
+    data = bytearray(magic)
+    # Split: the two sides to move differ
+    data.append(1 | 2 * has_pawns)
+    for _ in files:
+        # Order of the leading group of pieces, for both sides
+        data.append(0)
+        for piece in pieces:
+            data.append(piece | piece << 4)
+    data += bytes(len(data) & 1)
+
+    parts = [[compress(values, flags) for values, flags in sides] for sides in files]
+    for sides in parts:
+        for header, _, _, _ in sides:
+            data += header
+    if magic == DTZ_MAGIC:
+        for file_maps in maps or []:
+            if file_maps is None:
+                continue
+            wide, map_data = file_maps
+            if wide:
+                data += bytes(len(data) & 1)
+            data += map_data
+        data += bytes(len(data) & 1)
+    for sides in parts:
+        for _, sparse_index, _, _ in sides:
+            data += sparse_index
+    for sides in parts:
+        for _, _, block_lengths, _ in sides:
+            data += block_lengths
+    for sides in parts:
+        for _, _, _, blocks in sides:
+            data += bytes(-len(data) & 0x3F)
+            for block in blocks:
+                data += block
+    with open(path, "wb") as file:
+        file.write(data)
+
+
+def generate_pieces(name, piece, steps, dtz_stm, dtz_flags, wide_maps):
+    positions, dtm = solve(steps)
+    pieces = [WHITE_KING, piece, BLACK_KING]
+
+    sides = []
+    for stm in (0, 1):
+        table = [None] * PIECE_TABLE_SIZE
+        for wk, wx, bk, side in positions:
+            if side == stm:
+                plies = dtm.get(key(wk, wx, bk, stm))
+                value = 2 if plies is None else 4 if plies > 0 else 0
+                store(table, encode([wk, wx, bk]), value)
+        sides.append((fill(table), 0))
+    write_table(name + ".rtbw", WDL_MAGIC, pieces, [sides])
+
+    # Without pawns or captures for the winner, DTZ is the distance to mate: 2n - 1 plies
+    # for a mate in n, -2n for getting mated in n and -1 when mated. Stored as |DTZ| - 1,
+    # halved where the result doesn't count plies.
+    def stored(plies):
+        value = max(abs(plies), 1) - 1
+        counts_plies = dtz_flags & (FLAG_WIN_PLIES if plies > 0 else FLAG_LOSS_PLIES)
+        return value if counts_plies else value // 2
+
+    results = {}
+    for wk, wx, bk, side in positions:
+        plies = dtm.get(key(wk, wx, bk, dtz_stm))
+        if side == dtz_stm and plies is not None:
+            results[(wk, wx, bk)] = (plies > 0, stored(plies))
+
+    maps = [None]
+    if dtz_flags & FLAG_MAPPED:
+        needed = [
+            {value for wins, value in results.values() if wins},
+            {value for wins, value in results.values() if not wins},
+            set(),
+            set(),
+        ]
+        map_data, index = value_maps(needed, wide_maps)
+        maps = [(wide_maps, map_data)]
+        results = {
+            squares: (wins, index[0 if wins else 1][value])
+            for squares, (wins, value) in results.items()
+        }
+    table = [None] * PIECE_TABLE_SIZE
+    for wk, wx, bk, side in positions:
+        if side == dtz_stm:
+            store(table, encode([wk, wx, bk]), results.get((wk, wx, bk), (0, 0))[1])
+    flags = dtz_stm * FLAG_STM | dtz_flags | FLAG_WIDE * wide_maps
+    write_table(name + ".rtbz", DTZ_MAGIC, pieces, [[(fill(table), flags)]], maps)
+
+    longest = max(plies for plies in dtm.values())
+    print("{}: longest mate {} moves".format(name, (longest + 1) // 2))
+    return dtm
+
+
+def generate_draw(name, piece):
+    pieces = [WHITE_KING, piece, BLACK_KING]
+    draws = [2] * PIECE_TABLE_SIZE
+    write_table(name + ".rtbw", WDL_MAGIC, pieces, [[(draws, 0), (draws, 0)]])
+    write_table(name + ".rtbz", DTZ_MAGIC, pieces, [[([0] * PIECE_TABLE_SIZE, 0)]])
+
+
+def generate_pawn(queen_dtm, rook_dtm):
+    wdl, dtz = solve_pawn(queen_dtm, rook_dtm)
+    pieces = [WHITE_PAWN, WHITE_KING, BLACK_KING]
+
+    files = [[[None] * PAWN_TABLE_SIZE for _ in (0, 1)] for _ in range(4)]
+    for (wk, p, bk, stm), value in wdl.items():
+        file, idx = encode_pawn(p, wk, bk)
+        store(files[file][stm], idx, value + 2)
+    write_table(
+        "KPvK.rtbw",
+        WDL_MAGIC,
+        pieces,
+        [[(fill(values), 0) for values in sides] for sides in files],
+    )
+
+    # White to move only, where every win takes an odd number of plies, so wins are
+    # stored in moves through a u8 map per file
+    files = [[None] * PAWN_TABLE_SIZE for _ in range(4)]
+    needed = [[set(), set(), set(), set()] for _ in range(4)]
+    for (wk, p, bk, stm), plies in dtz.items():
+        if stm == 0 and plies > 0:
+            assert plies % 2 == 1
+            needed[encode_pawn(p, wk, bk)[0]][0].add((plies - 1) // 2)
+    maps = [value_maps(file_needed, False) for file_needed in needed]
+    for (wk, p, bk, stm), plies in dtz.items():
+        if stm == 0:
+            file, idx = encode_pawn(p, wk, bk)
+            value = maps[file][1][0][(plies - 1) // 2] if plies > 0 else 0
+            store(files[file], idx, value)
+    write_table(
+        "KPvK.rtbz",
+        DTZ_MAGIC,
+        pieces,
+        [[(fill(values), FLAG_MAPPED)] for values in files],
+        [(False, map_data) for map_data, _ in maps],
+    )
+    longest = max(plies for plies in dtz.values())
+    print("KPvK: longest win {} plies to a pawn move".format(longest))
+
+
+if __name__ == "__main__":
+    # One DTZ table of each side to move, so probes also go through the one ply search
+    queen_dtm = generate_pieces("KQvK", WHITE_QUEEN, QUEEN_STEPS, 0, 0, False)
+    rook_dtm = generate_pieces(
+        "KRvK", WHITE_ROOK, ROOK_STEPS, 1, FLAG_MAPPED | FLAG_WIN_PLIES | FLAG_LOSS_PLIES, True
+    )
+    # Underpromotions to a minor piece end up in these
+    generate_draw("KBvK", WHITE_BISHOP)
+    generate_draw("KNvK", WHITE_KNIGHT)
+    generate_pawn(queen_dtm, rook_dtm)